@@ -0,0 +1,86 @@
+//! Compares the naive and greedy chunk meshers.
+//!
+//! Run with `cargo bench`.  Besides the timings, the number of vertices each
+//! mesher produces for each landscape is printed before it is measured.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use delve::{
+    resources::{self, Database},
+    world::{BlockPos, Chunk, ChunkMesh, ChunkPos, MeshMode, World, CHUNK_SIZE},
+};
+
+/// Creates a world with a loaded chunk at the origin, surrounded by air,
+/// filled by a function of each block's position and lit by the sky.
+fn landscape<F>(database: &Database, fill: F) -> World
+where
+    F: Fn(i64, i64, i64) -> Option<&'static str>,
+{
+    let mut world = World::new(1);
+    let changes = world.update_loaded(ChunkPos::default(), |_| Chunk::new());
+
+    let size = CHUNK_SIZE as i64;
+    for y in 0..size {
+        for z in 0..size {
+            for x in 0..size {
+                if let Some(name) = fill(x, y, z) {
+                    let id = database.block_id(name).expect("block in core.kdl");
+                    world.set_block(BlockPos::new(x, y, z), id);
+                }
+            }
+        }
+    }
+    world.light_chunks(&changes.loaded, database);
+    world
+}
+
+fn meshing(c: &mut Criterion) {
+    let database = resources::read_kdl("core.kdl").expect("loading core resources");
+
+    let landscapes = [
+        ("solid stone", landscape(&database, |_, _, _| Some("stone"))),
+        (
+            "flat ground",
+            landscape(&database, |_, y, _| match y {
+                0..=5 => Some("stone"),
+                6..=7 => Some("dirt"),
+                8 => Some("grass"),
+                _ => None,
+            }),
+        ),
+        (
+            "rolling hills",
+            landscape(&database, |x, y, z| {
+                let height = 6 + (x / 3 + z / 4) % 4;
+                (y <= height).then_some(if y == height { "grass" } else { "stone" })
+            }),
+        ),
+        (
+            "checkerboard",
+            landscape(&database, |x, y, z| {
+                ((x + y + z) % 2 == 0).then_some("stone")
+            }),
+        ),
+    ];
+
+    let mut group = c.benchmark_group("meshing");
+    for (name, world) in &landscapes {
+        for mode in [MeshMode::Naive, MeshMode::Greedy] {
+            let mesh = ChunkMesh::build(world, ChunkPos::default(), &database, mode);
+            println!("{name} ({mode:?}): {} vertices", mesh.vertex_count());
+
+            group.bench_with_input(
+                BenchmarkId::new(format!("{mode:?}"), name),
+                world,
+                |b, world| {
+                    b.iter(|| {
+                        ChunkMesh::build(black_box(world), ChunkPos::default(), &database, mode)
+                    })
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, meshing);
+criterion_main!(benches);
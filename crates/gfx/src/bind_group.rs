@@ -0,0 +1,149 @@
+use wgpu::{BindGroupDescriptor, BindGroupEntry, BindingResource, Device};
+
+use super::{Buffer, GfxError, Material, Texture};
+
+/// A group of resources bound to a shader.
+///
+/// # Notes
+///
+/// This is a wrapper around a WGPU bind group.  It is used to give a shader the
+/// textures or buffers that its material declares.
+///
+/// You can create a bind group for a material's textures using the
+/// [Screen::create_texture_bind_group] method, or for its buffers using the
+/// [Screen::create_buffer_bind_group] method, and bind it to a render pass
+/// using the [RenderPass::set_bind_group] method.
+///
+/// [Screen::create_texture_bind_group]: struct.Screen.html#method.create_texture_bind_group
+/// [Screen::create_buffer_bind_group]: struct.Screen.html#method.create_buffer_bind_group
+/// [RenderPass::set_bind_group]: struct.RenderPass.html#method.set_bind_group
+///
+#[derive(Debug)]
+pub struct BindGroup {
+    /// The underlying WGPU bind group.
+    bind_group: wgpu::BindGroup,
+}
+
+impl BindGroup {
+    /// Creates a new bind group holding textures for a material.
+    ///
+    /// # Parameters
+    ///
+    /// * `desc` - The name of the bind group for debugging purposes.
+    /// * `device` - The WGPU device.
+    /// * `material` - The material that declares the texture bindings.
+    /// * `textures` - The textures, in the same order as they were added to
+    ///   the material.
+    ///
+    /// # Returns
+    ///
+    /// The new bind group.
+    ///
+    /// # Errors
+    ///
+    /// * `GfxError::BadTextureBindings` - If the number of textures does not
+    ///   match the number of texture bindings of the material.
+    ///
+    pub(crate) fn new_textures(
+        desc: &str,
+        device: &Device,
+        material: &Material,
+        textures: &[&Texture],
+    ) -> Result<Self, GfxError> {
+        let expected = material.textures().len();
+        if textures.len() != expected || expected == 0 {
+            return Err(GfxError::BadTextureBindings {
+                expected,
+                found: textures.len(),
+            });
+        }
+        let layout = material.texture_bind_group_layout(device);
+
+        let entries = textures
+            .iter()
+            .enumerate()
+            .flat_map(|(index, texture)| {
+                let binding = index as u32 * 2;
+                [
+                    BindGroupEntry {
+                        binding,
+                        resource: BindingResource::TextureView(texture.view()),
+                    },
+                    BindGroupEntry {
+                        binding: binding + 1,
+                        resource: BindingResource::Sampler(texture.sampler()),
+                    },
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some(desc),
+            layout: &layout,
+            entries: &entries,
+        });
+
+        Ok(Self { bind_group })
+    }
+
+    /// Creates a new bind group holding buffers for a material.
+    ///
+    /// # Parameters
+    ///
+    /// * `desc` - The name of the bind group for debugging purposes.
+    /// * `device` - The WGPU device.
+    /// * `material` - The material that declares the buffer bindings.
+    /// * `buffers` - The buffers, in the same order as they were added to the
+    ///   material.
+    ///
+    /// # Returns
+    ///
+    /// The new bind group.
+    ///
+    /// # Errors
+    ///
+    /// * `GfxError::BadBufferBindings` - If the kinds of the buffers do not
+    ///   match the buffer bindings of the material.
+    ///
+    pub(crate) fn new_buffers(
+        desc: &str,
+        device: &Device,
+        material: &Material,
+        buffers: &[&Buffer],
+    ) -> Result<Self, GfxError> {
+        let found = buffers
+            .iter()
+            .map(|buffer| buffer.kind())
+            .collect::<Vec<_>>();
+        let expected = material.buffers();
+        if expected.is_empty() || !found.iter().copied().eq(expected.iter().copied().map(Some)) {
+            return Err(GfxError::BadBufferBindings {
+                expected: expected.to_vec(),
+                found,
+            });
+        }
+        let layout = material.buffer_bind_group_layout(device);
+
+        let entries = buffers
+            .iter()
+            .enumerate()
+            .map(|(index, buffer)| BindGroupEntry {
+                binding: index as u32,
+                resource: buffer.wgpu_buffer().as_entire_binding(),
+            })
+            .collect::<Vec<_>>();
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some(desc),
+            layout: &layout,
+            entries: &entries,
+        });
+
+        Ok(Self { bind_group })
+    }
+
+    /// Returns the underlying WGPU bind group.
+    pub(crate) fn wgpu_bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
@@ -15,6 +15,20 @@ pub struct Buffer {
 
     /// The number of elements in the buffer.
     size: usize,
+
+    /// How the buffer can be bound to a shader, if at all.
+    kind: Option<BufferKind>,
+}
+
+/// How a buffer is bound to a shader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BufferKind {
+    /// A small, read-only block of data, declared as `var<uniform>` in WGSL.
+    Uniform,
+
+    /// A large, read-only array of data, declared as `var<storage, read>` in
+    /// WGSL.
+    Storage,
 }
 
 impl Buffer {
@@ -67,7 +81,11 @@ impl Buffer {
             contents: cast_slice(data),
             usage,
         });
-        Self { buffer, size }
+        Self {
+            buffer,
+            size,
+            kind: None,
+        }
     }
 
     /// Create a new index buffer from the given data.
@@ -112,7 +130,64 @@ impl Buffer {
             contents: cast_slice(indices),
             usage,
         });
-        Self { buffer, size }
+        Self {
+            buffer,
+            size,
+            kind: None,
+        }
+    }
+
+    /// Create a new buffer that can be bound to a shader.
+    ///
+    /// # Parameters
+    ///
+    /// * `desc` - A description of the buffer for debugging purposes.
+    /// * `device` - The WGPU device.
+    /// * `kind` - How the buffer is bound to a shader.
+    /// * `data` - The initial contents of the buffer.
+    ///
+    /// # Returns
+    ///
+    /// A new uniform or storage buffer.
+    ///
+    /// # Notes
+    ///
+    /// The buffer is created with the `UNIFORM` or `STORAGE` usage flag, and
+    /// the `COPY_DST` usage flag so that it can be written to later with
+    /// [Screen::write_buffer].
+    ///
+    /// [Screen::write_buffer]: struct.Screen.html#method.write_buffer
+    ///
+    pub(crate) fn new_binding_buffer<T>(
+        desc: &'static str,
+        device: &Device,
+        kind: BufferKind,
+        data: &[T],
+    ) -> Self
+    where
+        T: Zeroable + Pod,
+    {
+        let size = data.len();
+        let buffer_size = size * std::mem::size_of::<T>();
+        debug!(
+            "Creating buffer: {} ({:?}, {} elements, {} bytes)",
+            desc, kind, size, buffer_size
+        );
+        let usage = match kind {
+            BufferKind::Uniform => BufferUsages::UNIFORM,
+            BufferKind::Storage => BufferUsages::STORAGE,
+        } | BufferUsages::COPY_DST;
+
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some(desc),
+            contents: cast_slice(data),
+            usage,
+        });
+        Self {
+            buffer,
+            size,
+            kind: Some(kind),
+        }
     }
 
     /// Returns the underlying WGPU buffer.
@@ -169,6 +244,22 @@ impl Buffer {
         self.size
     }
 
+    /// Returns true if the buffer has no elements.
+    ///
+    /// # Returns
+    ///
+    /// True if [Buffer::len] is 0.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Returns how the buffer can be bound to a shader, or `None` for vertex
+    /// and index buffers.
+    pub fn kind(&self) -> Option<BufferKind> {
+        self.kind
+    }
+
     /// Returns the complete range of indices in the buffer.
     ///
     /// # Returns
@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use super::BufferKind;
+
 /// An error that can occur when using the `gfx` crate.
 ///
 /// # See Also
@@ -27,4 +29,34 @@ pub enum GfxError {
 
     #[error("bad material: missing vertex shader")]
     BadMaterialMissingShaders,
+
+    #[error("bad texture: pixel data does not match the texture size")]
+    BadTextureData,
+
+    #[error("bad texture bindings: material expects {expected} textures, found {found}")]
+    BadTextureBindings { expected: usize, found: usize },
+
+    #[error("bad buffer bindings: material expects {expected:?}, found {found:?}")]
+    BadBufferBindings {
+        expected: Vec<BufferKind>,
+        found: Vec<Option<BufferKind>>,
+    },
+
+    #[error("unknown material: {0:?}")]
+    UnknownMaterial(String),
+
+    #[error("unknown buffer: {0:?}")]
+    UnknownBuffer(String),
+
+    #[error("surface format {0:?} is not supported")]
+    UnsupportedSurfaceFormat(wgpu::TextureFormat),
+
+    #[error("sample count {0} is not supported")]
+    UnsupportedSampleCount(u32),
+
+    #[error("bad texture: size is not a whole number of tiles")]
+    BadTileSize,
+
+    #[error("bad texture: unsupported number of array layers ({0})")]
+    BadLayerCount(u32),
 }
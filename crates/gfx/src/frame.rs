@@ -3,7 +3,7 @@ use wgpu::{
     TextureView, TextureViewDescriptor,
 };
 
-use super::{GfxError, RenderPass};
+use super::{ColourAttachment, GfxError, RenderPass, RenderPassDesc};
 
 /// A frame that can be rendered to.
 ///
@@ -122,7 +122,7 @@ impl<'queue> Frame<'queue> {
         })
     }
 
-    /// Creates a new render pass.
+    /// Creates a new render pass that clears the screen.
     ///
     /// # Parameters
     ///
@@ -152,13 +152,50 @@ impl<'queue> Frame<'queue> {
     ///
     /// * [wgpu::RenderPassDescriptor](https://docs.rs/wgpu/latest/wgpu/struct.RenderPassDescriptor.html)
     ///
-    pub fn create_render_pass(&mut self, render_pass_desc: &str, back_colour: Color) -> RenderPass {
-        RenderPass::new(
-            &mut self.encoder,
-            &self.texture_view,
-            render_pass_desc,
-            back_colour,
-        )
+    /// # Notes
+    ///
+    /// This is a shorthand for [Frame::begin_render_pass] with a single colour
+    /// attachment that clears the frame's surface.
+    ///
+    /// [Frame::begin_render_pass]: struct.Frame.html#method.begin_render_pass
+    ///
+    pub fn create_render_pass(
+        &mut self,
+        render_pass_desc: &str,
+        back_colour: Color,
+    ) -> RenderPass<'_> {
+        let desc = RenderPassDesc::new(render_pass_desc)
+            .colour_attachment(ColourAttachment::surface().clear(back_colour));
+        self.begin_render_pass(&desc)
+    }
+
+    /// Creates a new render pass from a description.
+    ///
+    /// # Parameters
+    ///
+    /// * `desc` - The render pass description.  This lists the colour
+    ///   attachments of the pass and how they are loaded and stored.
+    ///
+    /// # Returns
+    ///
+    /// The new render pass.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use gfx::{ColourAttachment, Frame, RenderPassDesc};
+    /// # fn example(frame: &mut Frame) {
+    /// // Draw a UI overlay on top of the world without clearing it.
+    /// let desc = RenderPassDesc::new("UI pass").colour_attachment(ColourAttachment::surface());
+    /// let render_pass = frame.begin_render_pass(&desc);
+    /// # }
+    /// ```
+    ///
+    pub fn begin_render_pass<'pass>(
+        &'pass mut self,
+        desc: &RenderPassDesc<'pass>,
+    ) -> RenderPass<'pass> {
+        RenderPass::new(&mut self.encoder, &self.texture_view, desc)
     }
 
     /// Finishes the frame.
@@ -1,15 +1,24 @@
+mod bind_group;
 mod buffer;
 mod error;
 mod frame;
 mod material;
+mod mipmap;
+mod pipeline_cache;
 mod render_pass;
 mod render_pipeline;
+mod render_target;
 mod screen;
+mod texture;
 
+pub use bind_group::*;
 pub use buffer::*;
 pub use error::*;
 pub use frame::*;
 pub use material::*;
+pub use pipeline_cache::*;
 pub use render_pass::*;
 pub use render_pipeline::*;
+pub use render_target::*;
 pub use screen::*;
+pub use texture::*;
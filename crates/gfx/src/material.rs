@@ -1,8 +1,12 @@
 use wgpu::{
-    ColorTargetState, Device, FragmentState, ShaderModule, ShaderModuleDescriptor,
+    BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType,
+    BufferBindingType, ColorTargetState, Device, FragmentState, SamplerBindingType, ShaderModule,
+    ShaderModuleDescriptor, ShaderStages, TextureSampleType, TextureViewDimension,
     VertexBufferLayout, VertexState,
 };
 
+use super::BufferKind;
+
 /// A material.
 ///
 /// # Notes
@@ -11,6 +15,15 @@ use wgpu::{
 /// A material represents a set of shaders and vertex buffer layouts that can be used
 /// to render a mesh.
 ///
+/// A material can also bind textures.  These are declared in order with
+/// [Material::add_texture] and are found in bind group 0 of the shader: the
+/// texture `n` is at binding `2n` and its sampler at binding `2n + 1`.
+///
+/// Likewise, uniform and storage buffers are declared in order with
+/// [Material::add_buffer] and are found in bind group 1 of the shader: the
+/// buffer `n` is at binding `n`.  If a material has buffers but no textures,
+/// bind group 0 is empty.
+///
 /// # See Also
 ///
 /// * [wgpu::ShaderModule](https://docs.rs/wgpu/latest/wgpu/struct.ShaderModule.html)
@@ -18,9 +31,11 @@ use wgpu::{
 #[derive(Debug)]
 pub struct Material<'layout> {
     shader: ShaderModule,
-    vertex_entry_point: &'static str,
-    fragment_entry_point: &'static str,
+    vertex_entry_point: String,
+    fragment_entry_point: String,
     buffer_layouts: Vec<VertexBufferLayout<'layout>>,
+    textures: Vec<TextureViewDimension>,
+    buffers: Vec<BufferKind>,
 }
 
 impl<'material> Material<'material> {
@@ -63,15 +78,17 @@ impl<'material> Material<'material> {
     pub(crate) fn new(
         device: &Device,
         shader: ShaderModuleDescriptor,
-        vertex_entry_point: &'static str,
-        fragment_entry_point: &'static str,
+        vertex_entry_point: &str,
+        fragment_entry_point: &str,
     ) -> Self {
         let shader = device.create_shader_module(shader);
         Self {
             shader,
-            vertex_entry_point,
-            fragment_entry_point,
+            vertex_entry_point: vertex_entry_point.to_string(),
+            fragment_entry_point: fragment_entry_point.to_string(),
             buffer_layouts: Vec::new(),
+            textures: Vec::new(),
+            buffers: Vec::new(),
         }
     }
 
@@ -89,10 +106,10 @@ impl<'material> Material<'material> {
     ///
     /// * [wgpu::VertexState](https://docs.rs/wgpu/latest/wgpu/struct.VertexState.html)
     ///
-    pub(crate) fn vertex_state(&self) -> VertexState {
+    pub(crate) fn vertex_state(&self) -> VertexState<'_> {
         VertexState {
             module: &self.shader,
-            entry_point: self.vertex_entry_point,
+            entry_point: &self.vertex_entry_point,
             buffers: &self.buffer_layouts,
         }
     }
@@ -118,14 +135,139 @@ impl<'material> Material<'material> {
     pub(crate) fn fragment_state<'a>(
         &'a self,
         targets: &'a [Option<ColorTargetState>],
-    ) -> FragmentState {
+    ) -> FragmentState<'a> {
         FragmentState {
             module: &self.shader,
-            entry_point: self.fragment_entry_point,
+            entry_point: &self.fragment_entry_point,
             targets,
         }
     }
 
+    /// Returns the vertex buffer layouts of the material.
+    pub(crate) fn buffer_layouts(&self) -> &[VertexBufferLayout<'material>] {
+        &self.buffer_layouts
+    }
+
+    /// Returns the view dimensions of the textures bound by the material.
+    pub(crate) fn textures(&self) -> &[TextureViewDimension] {
+        &self.textures
+    }
+
+    /// Returns the kinds of the buffers bound by the material.
+    pub(crate) fn buffers(&self) -> &[BufferKind] {
+        &self.buffers
+    }
+
+    /// Creates the layouts of the material's bind groups.
+    ///
+    /// # Parameters
+    ///
+    /// * `device` - The WGPU device.
+    ///
+    /// # Returns
+    ///
+    /// The layouts of bind group 0 (textures) and bind group 1 (buffers).
+    /// Groups after the last one that is used are left out.
+    ///
+    pub(crate) fn bind_group_layouts(&self, device: &Device) -> Vec<BindGroupLayout> {
+        let mut layouts = Vec::new();
+        if !self.textures.is_empty() || !self.buffers.is_empty() {
+            layouts.push(self.texture_bind_group_layout(device));
+        }
+        if !self.buffers.is_empty() {
+            layouts.push(self.buffer_bind_group_layout(device));
+        }
+        layouts
+    }
+
+    /// Creates the layout of the bind group holding the material's textures.
+    ///
+    /// # Parameters
+    ///
+    /// * `device` - The WGPU device.
+    ///
+    /// # Returns
+    ///
+    /// The bind group layout, which has no entries if the material binds no
+    /// textures.
+    ///
+    /// # Notes
+    ///
+    /// WGPU treats bind group layouts with the same entries as the same
+    /// layout, so the pipelines and bind groups of a material can each create
+    /// their own.
+    ///
+    pub(crate) fn texture_bind_group_layout(&self, device: &Device) -> BindGroupLayout {
+        let entries = self
+            .textures
+            .iter()
+            .enumerate()
+            .flat_map(|(index, &view_dimension)| {
+                let binding = index as u32 * 2;
+                [
+                    BindGroupLayoutEntry {
+                        binding,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: binding + 1,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Material texture bind group layout"),
+            entries: &entries,
+        })
+    }
+
+    /// Creates the layout of the bind group holding the material's buffers.
+    ///
+    /// # Parameters
+    ///
+    /// * `device` - The WGPU device.
+    ///
+    /// # Returns
+    ///
+    /// The bind group layout.  The buffers can be read by both the vertex and
+    /// fragment shaders.
+    ///
+    pub(crate) fn buffer_bind_group_layout(&self, device: &Device) -> BindGroupLayout {
+        let entries = self
+            .buffers
+            .iter()
+            .enumerate()
+            .map(|(index, kind)| BindGroupLayoutEntry {
+                binding: index as u32,
+                visibility: ShaderStages::VERTEX_FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: match kind {
+                        BufferKind::Uniform => BufferBindingType::Uniform,
+                        BufferKind::Storage => BufferBindingType::Storage { read_only: true },
+                    },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            })
+            .collect::<Vec<_>>();
+
+        device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Material buffer bind group layout"),
+            entries: &entries,
+        })
+    }
+
     /// Adds a vertex buffer layout.
     ///
     /// # Notes
@@ -152,4 +294,59 @@ impl<'material> Material<'material> {
         self.buffer_layouts.push(layout);
         self
     }
+
+    /// Adds a texture binding.
+    ///
+    /// # Notes
+    ///
+    /// This adds a texture and its sampler to bind group 0 of the material.
+    /// The textures themselves are bound with a bind group created by
+    /// [Screen::create_texture_bind_group].
+    ///
+    /// This is intended to be used in a builder pattern after the creation of the
+    /// material.
+    ///
+    /// # Parameters
+    ///
+    /// * `view_dimension` - The dimension of the texture as declared in the
+    ///   shader.  Textures created by the screen are viewed as
+    ///   `TextureViewDimension::D2Array`.
+    ///
+    /// # Returns
+    ///
+    /// The material.
+    ///
+    /// [Screen::create_texture_bind_group]: struct.Screen.html#method.create_texture_bind_group
+    ///
+    pub fn add_texture(mut self, view_dimension: TextureViewDimension) -> Self {
+        self.textures.push(view_dimension);
+        self
+    }
+
+    /// Adds a buffer binding.
+    ///
+    /// # Notes
+    ///
+    /// This adds a uniform or storage buffer to bind group 1 of the material.
+    /// The buffers themselves are bound with a bind group created by
+    /// [Screen::create_buffer_bind_group].
+    ///
+    /// This is intended to be used in a builder pattern after the creation of the
+    /// material.
+    ///
+    /// # Parameters
+    ///
+    /// * `kind` - Whether the shader declares the buffer as a uniform or
+    ///   storage buffer.
+    ///
+    /// # Returns
+    ///
+    /// The material.
+    ///
+    /// [Screen::create_buffer_bind_group]: struct.Screen.html#method.create_buffer_bind_group
+    ///
+    pub fn add_buffer(mut self, kind: BufferKind) -> Self {
+        self.buffers.push(kind);
+        self
+    }
 }
@@ -0,0 +1,223 @@
+use std::num::NonZeroU32;
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::{
+    include_wgsl,
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BufferBindingType, BufferUsages,
+    ColorTargetState, ColorWrites, CommandEncoder, Device, FragmentState, LoadOp, MultisampleState,
+    Operations, PipelineLayoutDescriptor, PrimitiveState, RenderPassColorAttachment,
+    RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, ShaderStages, Texture,
+    TextureFormat, TextureSampleType, TextureViewDescriptor, TextureViewDimension, VertexState,
+};
+
+/// The parameters passed to the mipmap shader for each level.
+///
+/// # Notes
+///
+/// This must match the `MipmapParams` struct in `mipmap.wgsl`.  Uniform
+/// buffers must be at least 16 bytes, hence the padding.
+///
+#[repr(C)]
+#[derive(Copy, Clone, Zeroable, Pod)]
+struct MipmapParams {
+    tile_size: [u32; 2],
+    _padding: [u32; 2],
+}
+
+/// Generates the mip chain of a texture on the GPU.
+///
+/// # Notes
+///
+/// Each mip level is rendered from the level above it with a blit render
+/// pass that averages 2x2 texels.  The texels that are averaged are clamped to
+/// the tile that they belong to, so the colours of neighbouring tiles in an
+/// atlas never bleed into each other.  For a texture array, each layer is
+/// treated as a single tile.
+///
+pub(crate) struct MipmapGenerator {
+    /// The layout of the bind group holding the source level and parameters.
+    bind_group_layout: BindGroupLayout,
+
+    /// The blit pipeline.
+    pipeline: RenderPipeline,
+}
+
+impl MipmapGenerator {
+    /// Creates a new mipmap generator for textures of the given format.
+    ///
+    /// # Parameters
+    ///
+    /// * `device` - The WGPU device.
+    /// * `format` - The format of the textures that will have mipmaps
+    ///   generated.
+    ///
+    /// # Returns
+    ///
+    /// The new mipmap generator.
+    ///
+    pub(crate) fn new(device: &Device, format: TextureFormat) -> Self {
+        let shader = device.create_shader_module(include_wgsl!("mipmap.wgsl"));
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Mipmap bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Mipmap pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Mipmap pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            bind_group_layout,
+            pipeline,
+        }
+    }
+
+    /// Encodes the commands to generate all the mip levels of a texture.
+    ///
+    /// # Parameters
+    ///
+    /// * `device` - The WGPU device.
+    /// * `encoder` - The command encoder that the blits are written to.
+    /// * `texture` - The texture.  Level 0 of every layer must already hold
+    ///   the image data.
+    /// * `mip_level_count` - The number of mip levels in the texture.
+    /// * `layer_count` - The number of array layers in the texture.
+    /// * `tile_size` - The size (in texels) of a tile at level 0.
+    ///
+    pub(crate) fn generate(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        texture: &Texture,
+        mip_level_count: u32,
+        layer_count: u32,
+        tile_size: (u32, u32),
+    ) {
+        for layer in 0..layer_count {
+            for level in 1..mip_level_count {
+                let view = |mip_level| {
+                    texture.create_view(&TextureViewDescriptor {
+                        label: Some("Mipmap view"),
+                        dimension: Some(TextureViewDimension::D2),
+                        base_mip_level: mip_level,
+                        mip_level_count: NonZeroU32::new(1),
+                        base_array_layer: layer,
+                        array_layer_count: NonZeroU32::new(1),
+                        ..Default::default()
+                    })
+                };
+                let source_view = view(level - 1);
+                let target_view = view(level);
+
+                let params = MipmapParams {
+                    tile_size: [
+                        (tile_size.0 >> (level - 1)).max(1),
+                        (tile_size.1 >> (level - 1)).max(1),
+                    ],
+                    _padding: [0; 2],
+                };
+                let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("Mipmap parameters"),
+                    contents: bytemuck::bytes_of(&params),
+                    usage: BufferUsages::UNIFORM,
+                });
+
+                let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                    label: Some("Mipmap bind group"),
+                    layout: &self.bind_group_layout,
+                    entries: &[
+                        BindGroupEntry {
+                            binding: 0,
+                            resource: BindingResource::TextureView(&source_view),
+                        },
+                        BindGroupEntry {
+                            binding: 1,
+                            resource: params_buffer.as_entire_binding(),
+                        },
+                    ],
+                });
+
+                let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                    label: Some("Mipmap pass"),
+                    color_attachments: &[Some(RenderPassColorAttachment {
+                        view: &target_view,
+                        resolve_target: None,
+                        ops: Operations {
+                            load: LoadOp::Load,
+                            store: true,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                });
+                render_pass.set_pipeline(&self.pipeline);
+                render_pass.set_bind_group(0, &bind_group, &[]);
+                render_pass.draw(0..3, 0..1);
+            }
+        }
+    }
+}
+
+/// Returns the number of mip levels that a texture made of tiles can have.
+///
+/// # Parameters
+///
+/// * `tile_size` - The size (in texels) of a tile at level 0.
+///
+/// # Returns
+///
+/// The number of mip levels until a tile is a single texel wide or high.
+/// Going further would merge neighbouring tiles together.
+///
+pub(crate) fn mip_level_count(tile_size: (u32, u32)) -> u32 {
+    let smallest = tile_size.0.min(tile_size.1).max(1);
+    u32::BITS - smallest.leading_zeros()
+}
@@ -0,0 +1,42 @@
+//
+// Mipmap generation
+//
+// Each pass renders one mip level from the level above it.  Every destination
+// texel is the average of the 2x2 source texels beneath it.  The source
+// texels are clamped to the tile that the destination texel lies in, so
+// colours never leak from one tile of an atlas into its neighbours.
+//
+
+struct MipmapParams {
+    // Size (in texels) of a tile in the source level.
+    tile_size: vec2<u32>,
+}
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> params: MipmapParams;
+
+// Draws a single triangle that covers the whole target.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let dest = vec2<u32>(position.xy);
+    let top_left = dest * 2u;
+
+    // Find the bounds of the tile in the source level.
+    let tile_min = (top_left / params.tile_size) * params.tile_size;
+    let tile_max = tile_min + params.tile_size - 1u;
+
+    var colour = vec4<f32>(0.0);
+    for (var y = 0u; y < 2u; y += 1u) {
+        for (var x = 0u; x < 2u; x += 1u) {
+            let texel = min(top_left + vec2<u32>(x, y), tile_max);
+            colour += textureLoad(source, vec2<i32>(texel), 0);
+        }
+    }
+    return colour * 0.25;
+}
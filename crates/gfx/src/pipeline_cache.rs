@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+
+use tracing::debug;
+use wgpu::{Device, TextureFormat, TextureViewDimension, VertexBufferLayout};
+
+use super::{
+    BlendMode, BufferKind, CullMode, DepthMode, GfxError, Material, PipelineState, RenderPipeline,
+    TargetFormat,
+};
+
+/// A description of a render pipeline that is looked up in the screen's cache.
+///
+/// # Notes
+///
+/// This names a material registered with [Screen::register_material] and the
+/// fixed-function state to draw it with.  Asking the screen for the same
+/// description twice returns the same pipeline.
+///
+/// # Examples
+///
+/// ```
+/// # use gfx::{BlendMode, DepthMode, PipelineDesc};
+/// let translucent = PipelineDesc::new("water")
+///     .blend(BlendMode::Alpha)
+///     .depth(DepthMode::Test);
+/// ```
+///
+/// [Screen::register_material]: struct.Screen.html#method.register_material
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineDesc {
+    /// The name of the material.
+    material: String,
+
+    /// The fixed-function state of the pipeline.
+    state: PipelineState,
+}
+
+/// A handle to a render pipeline in the screen's cache.
+///
+/// # Notes
+///
+/// The handle stays valid when the pipeline is rebuilt, for example after the
+/// surface format or sample count changes.  Use [Screen::render_pipeline] to
+/// get the pipeline itself when rendering.
+///
+/// [Screen::render_pipeline]: struct.Screen.html#method.render_pipeline
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineHandle(usize);
+
+/// Everything that a built pipeline depends on.
+///
+/// # Notes
+///
+/// When any of this changes, the pipeline has to be built again.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct PipelineKey {
+    /// The description of the pipeline, including the material name.
+    desc: PipelineDesc,
+
+    /// The vertex layouts of the material at the time it was built.
+    buffer_layouts: Vec<VertexBufferLayout<'static>>,
+
+    /// The texture bindings of the material at the time it was built.
+    textures: Vec<TextureViewDimension>,
+
+    /// The buffer bindings of the material at the time it was built.
+    buffers: Vec<BufferKind>,
+
+    /// The resolved formats of the colour targets.
+    formats: Vec<TextureFormat>,
+
+    /// The number of samples per pixel.
+    sample_count: u32,
+}
+
+/// A cache of render pipelines built from registered materials.
+#[derive(Default)]
+pub(crate) struct PipelineCache {
+    /// The pipelines, indexed by their handles, with the key they were built
+    /// with.
+    pipelines: Vec<(PipelineKey, RenderPipeline)>,
+
+    /// The handle of the pipeline for each description.
+    handles: HashMap<PipelineDesc, PipelineHandle>,
+}
+
+impl PipelineDesc {
+    /// Creates a new pipeline description with the default state.
+    ///
+    /// # Parameters
+    ///
+    /// * `material` - The name of a registered material.
+    ///
+    pub fn new(material: &str) -> Self {
+        Self {
+            material: material.to_string(),
+            state: PipelineState::default(),
+        }
+    }
+
+    /// Returns the name of the material.
+    pub fn material(&self) -> &str {
+        &self.material
+    }
+
+    /// Sets the whole fixed-function state of the pipeline.
+    pub fn state(mut self, state: PipelineState) -> Self {
+        self.state = state;
+        self
+    }
+
+    /// Sets how colours are blended with the colour targets.
+    pub fn blend(mut self, blend: BlendMode) -> Self {
+        self.state.blend = blend;
+        self
+    }
+
+    /// Sets which faces are culled.
+    pub fn cull(mut self, cull: CullMode) -> Self {
+        self.state.cull = cull;
+        self
+    }
+
+    /// Sets how the depth buffer is used.
+    pub fn depth(mut self, depth: DepthMode) -> Self {
+        self.state.depth = depth;
+        self
+    }
+
+    /// Adds a colour target.  With no colour targets, the pipeline draws to a
+    /// single target using the surface format.
+    pub fn colour_target(mut self, format: TargetFormat) -> Self {
+        self.state.colour_targets.push(format);
+        self
+    }
+}
+
+impl PipelineCache {
+    /// Returns the handle of the pipeline for a description, building the
+    /// pipeline if it is not in the cache yet.
+    ///
+    /// # Parameters
+    ///
+    /// * `device` - The WGPU device.
+    /// * `materials` - The registered materials.
+    /// * `desc` - The description of the pipeline.
+    /// * `surface_format` - The current format of the screen's surface.
+    /// * `sample_count` - The current number of samples per pixel.
+    ///
+    /// # Errors
+    ///
+    /// * `GfxError::UnknownMaterial` - If the material is not registered.
+    ///
+    pub(crate) fn get(
+        &mut self,
+        device: &Device,
+        materials: &HashMap<String, Material<'static>>,
+        desc: &PipelineDesc,
+        surface_format: TextureFormat,
+        sample_count: u32,
+    ) -> Result<PipelineHandle, GfxError> {
+        if let Some(&handle) = self.handles.get(desc) {
+            return Ok(handle);
+        }
+
+        let material = materials
+            .get(&desc.material)
+            .ok_or_else(|| GfxError::UnknownMaterial(desc.material.clone()))?;
+        let key = PipelineKey::new(desc, material, surface_format, sample_count);
+        let pipeline = build(device, &key, material);
+
+        let handle = PipelineHandle(self.pipelines.len());
+        self.pipelines.push((key, pipeline));
+        self.handles.insert(desc.clone(), handle);
+        Ok(handle)
+    }
+
+    /// Returns the pipeline for a handle.
+    pub(crate) fn pipeline(&self, handle: PipelineHandle) -> &RenderPipeline {
+        &self.pipelines[handle.0].1
+    }
+
+    /// Rebuilds every pipeline whose key has changed.
+    ///
+    /// # Parameters
+    ///
+    /// * `device` - The WGPU device.
+    /// * `materials` - The registered materials.
+    /// * `surface_format` - The current format of the screen's surface.
+    /// * `sample_count` - The current number of samples per pixel.
+    /// * `replaced_material` - The name of a material that was registered
+    ///   again.  Its pipelines are rebuilt even if their key is unchanged,
+    ///   since its shaders may have changed.
+    ///
+    /// # Notes
+    ///
+    /// This is called when a material is registered again, or when the
+    /// surface format or sample count changes.
+    ///
+    pub(crate) fn refresh(
+        &mut self,
+        device: &Device,
+        materials: &HashMap<String, Material<'static>>,
+        surface_format: TextureFormat,
+        sample_count: u32,
+        replaced_material: Option<&str>,
+    ) {
+        for (key, pipeline) in &mut self.pipelines {
+            let Some(material) = materials.get(&key.desc.material) else {
+                continue;
+            };
+            let new_key = PipelineKey::new(&key.desc, material, surface_format, sample_count);
+            if new_key != *key || replaced_material == Some(key.desc.material.as_str()) {
+                *pipeline = build(device, &new_key, material);
+                *key = new_key;
+            }
+        }
+    }
+}
+
+impl PipelineKey {
+    /// Creates the key of a pipeline from its description and the current
+    /// state of the screen.
+    fn new(
+        desc: &PipelineDesc,
+        material: &Material<'static>,
+        surface_format: TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        Self {
+            desc: desc.clone(),
+            buffer_layouts: material.buffer_layouts().to_vec(),
+            textures: material.textures().to_vec(),
+            buffers: material.buffers().to_vec(),
+            formats: desc.state.target_formats(surface_format),
+            sample_count,
+        }
+    }
+}
+
+/// Builds the pipeline for a key.
+fn build(device: &Device, key: &PipelineKey, material: &Material) -> RenderPipeline {
+    debug!(
+        "Building pipeline: {:?} ({:?}, {:?}, {} samples)",
+        key.desc.material, key.desc.state, key.formats, key.sample_count
+    );
+    RenderPipeline::new(
+        device,
+        &key.desc.material,
+        material,
+        &key.desc.state,
+        &key.formats,
+        key.sample_count,
+    )
+}
@@ -2,10 +2,213 @@ use std::ops::RangeBounds;
 
 use wgpu::{
     BufferAddress, Color, CommandEncoder, IndexFormat, LoadOp, Operations,
-    RenderPassColorAttachment, RenderPassDescriptor, TextureView,
+    RenderPassColorAttachment, RenderPassDepthStencilAttachment, RenderPassDescriptor, TextureView,
 };
 
-use super::{render_pipeline::RenderPipeline, Buffer};
+use super::{render_pipeline::RenderPipeline, BindGroup, Buffer, RenderTarget};
+
+/// The texture that a colour attachment of a render pass draws into.
+#[derive(Clone, Copy, Debug)]
+pub enum ColourTarget<'target> {
+    /// The surface texture of the frame, i.e. the screen.
+    Surface,
+
+    /// An off-screen render target.
+    Target(&'target RenderTarget),
+
+    /// Any other WGPU texture view.
+    View(&'target TextureView),
+}
+
+/// A colour attachment of a render pass.
+///
+/// # Notes
+///
+/// This describes where a render pass draws to and what happens to the
+/// previous contents of that texture when the pass starts and ends.  By default
+/// the previous contents are kept (`LoadOp::Load`) and the results are stored.
+///
+/// # Examples
+///
+/// ```
+/// # use gfx::ColourAttachment;
+/// // Draw a UI overlay on top of what is already on the screen.
+/// let overlay = ColourAttachment::surface();
+///
+/// // Clear the screen to black before drawing the world.
+/// let world = ColourAttachment::surface().clear(wgpu::Color::BLACK);
+/// ```
+///
+#[derive(Clone, Copy, Debug)]
+pub struct ColourAttachment<'target> {
+    /// The texture that is drawn into.
+    target: ColourTarget<'target>,
+
+    /// What to do with the previous contents of the texture.
+    load: LoadOp<Color>,
+
+    /// Whether the results of the render pass are written to the texture.
+    store: bool,
+}
+
+impl<'target> ColourAttachment<'target> {
+    /// Creates a colour attachment that draws into the frame's surface.
+    pub fn surface() -> Self {
+        Self::new(ColourTarget::Surface)
+    }
+
+    /// Creates a colour attachment that draws into an off-screen render target.
+    ///
+    /// # Parameters
+    ///
+    /// * `target` - The render target.
+    ///
+    pub fn target(target: &'target RenderTarget) -> Self {
+        Self::new(ColourTarget::Target(target))
+    }
+
+    /// Creates a colour attachment that draws into a WGPU texture view.
+    ///
+    /// # Parameters
+    ///
+    /// * `view` - The texture view.
+    ///
+    pub fn view(view: &'target TextureView) -> Self {
+        Self::new(ColourTarget::View(view))
+    }
+
+    /// Creates a colour attachment that draws into the given target.
+    ///
+    /// # Parameters
+    ///
+    /// * `target` - Where the attachment draws to.
+    ///
+    /// # Returns
+    ///
+    /// A colour attachment that loads and stores the contents of the target.
+    ///
+    pub fn new(target: ColourTarget<'target>) -> Self {
+        Self {
+            target,
+            load: LoadOp::Load,
+            store: true,
+        }
+    }
+
+    /// Clears the target to the given colour when the render pass starts.
+    ///
+    /// # Parameters
+    ///
+    /// * `colour` - The background colour.
+    ///
+    pub fn clear(mut self, colour: Color) -> Self {
+        self.load = LoadOp::Clear(colour);
+        self
+    }
+
+    /// Keeps the previous contents of the target when the render pass starts.
+    ///
+    /// # Notes
+    ///
+    /// This is the default and is used to draw on top of an earlier pass.
+    ///
+    pub fn load(mut self) -> Self {
+        self.load = LoadOp::Load;
+        self
+    }
+
+    /// Throws away the results of the render pass instead of storing them.
+    pub fn discard(mut self) -> Self {
+        self.store = false;
+        self
+    }
+}
+
+/// A description of a render pass.
+///
+/// # Notes
+///
+/// A render pass description lists the colour attachments of the pass in
+/// order.  The order matches the `@location(n)` outputs of the fragment
+/// shader and the colour targets of the render pipeline.  It can also have a
+/// depth attachment, which pipelines that use the depth buffer need.
+///
+/// You can start a render pass with a description using the
+/// [Frame::begin_render_pass] method.
+///
+/// # Examples
+///
+/// ```
+/// # use gfx::{ColourAttachment, RenderPassDesc};
+/// # fn example(albedo: &gfx::RenderTarget, normals: &gfx::RenderTarget) {
+/// let desc = RenderPassDesc::new("G-buffer pass")
+///     .colour_attachment(ColourAttachment::target(albedo).clear(wgpu::Color::BLACK))
+///     .colour_attachment(ColourAttachment::target(normals).clear(wgpu::Color::BLACK));
+/// # }
+/// ```
+///
+/// [Frame::begin_render_pass]: struct.Frame.html#method.begin_render_pass
+///
+#[derive(Clone, Debug)]
+pub struct RenderPassDesc<'target> {
+    /// The description of the render pass for debugging purposes.
+    desc: String,
+
+    /// The colour attachments in location order.
+    colour_attachments: Vec<ColourAttachment<'target>>,
+
+    /// The depth buffer, if the pass has one.
+    depth_attachment: Option<&'target RenderTarget>,
+}
+
+impl<'target> RenderPassDesc<'target> {
+    /// Creates a new render pass description with no colour attachments.
+    ///
+    /// # Parameters
+    ///
+    /// * `desc` - The render pass description for debugging purposes.
+    ///
+    pub fn new(desc: &str) -> Self {
+        Self {
+            desc: desc.to_string(),
+            colour_attachments: Vec::new(),
+            depth_attachment: None,
+        }
+    }
+
+    /// Adds a colour attachment to the render pass.
+    ///
+    /// # Parameters
+    ///
+    /// * `attachment` - The colour attachment.
+    ///
+    /// # Returns
+    ///
+    /// The render pass description with the attachment added at the next
+    /// location.
+    ///
+    pub fn colour_attachment(mut self, attachment: ColourAttachment<'target>) -> Self {
+        self.colour_attachments.push(attachment);
+        self
+    }
+
+    /// Sets the depth attachment of the render pass.
+    ///
+    /// # Parameters
+    ///
+    /// * `target` - The depth buffer.  It must have been created with the
+    ///   format [DEPTH_FORMAT](crate::DEPTH_FORMAT).
+    ///
+    /// # Notes
+    ///
+    /// The depth buffer is cleared to the far plane when the render pass
+    /// starts.
+    ///
+    pub fn depth_attachment(mut self, target: &'target RenderTarget) -> Self {
+        self.depth_attachment = Some(target);
+        self
+    }
+}
 
 /// A render pass.
 ///
@@ -24,9 +227,8 @@ impl<'encoder> RenderPass<'encoder> {
     /// # Parameters
     ///
     /// * `encoder` - The command encoder.
-    /// * `view` - The texture view.
-    /// * `desc` - The description for debugging purposes.
-    /// * `back_colour` - The background colour.
+    /// * `surface_view` - The texture view of the frame's surface.
+    /// * `desc` - The description of the render pass.
     ///
     /// # Returns
     ///
@@ -34,21 +236,42 @@ impl<'encoder> RenderPass<'encoder> {
     ///
     pub(crate) fn new(
         encoder: &'encoder mut CommandEncoder,
-        view: &'encoder TextureView,
-        desc: &str,
-        back_colour: Color,
+        surface_view: &'encoder TextureView,
+        desc: &RenderPassDesc<'encoder>,
     ) -> Self {
+        let color_attachments = desc
+            .colour_attachments
+            .iter()
+            .map(|attachment| {
+                let view = match attachment.target {
+                    ColourTarget::Surface => surface_view,
+                    ColourTarget::Target(target) => target.view(),
+                    ColourTarget::View(view) => view,
+                };
+                Some(RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: attachment.load,
+                        store: attachment.store,
+                    },
+                })
+            })
+            .collect::<Vec<_>>();
+
         let render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-            label: Some(desc),
-            color_attachments: &[Some(RenderPassColorAttachment {
-                view,
-                resolve_target: None,
-                ops: Operations {
-                    load: LoadOp::Clear(back_colour),
-                    store: true,
-                },
-            })],
-            depth_stencil_attachment: None,
+            label: Some(&desc.desc),
+            color_attachments: &color_attachments,
+            depth_stencil_attachment: desc.depth_attachment.map(|target| {
+                RenderPassDepthStencilAttachment {
+                    view: target.view(),
+                    depth_ops: Some(Operations {
+                        load: LoadOp::Clear(1.0),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }
+            }),
         });
 
         Self { render_pass }
@@ -65,6 +288,23 @@ impl<'encoder> RenderPass<'encoder> {
             .set_pipeline(pipeline.get_render_pipeline());
     }
 
+    /// Sets the bind group for the render pass at the given index.
+    ///
+    /// # Parameters
+    ///
+    /// * `index` - The index of the bind group, matching `@group(n)` in the
+    ///   shader.  Material textures are in group 0.
+    /// * `bind_group` - The bind group.
+    ///
+    /// # Notes
+    ///
+    /// This is a wrapper around `wgpu::RenderPass::set_bind_group`.
+    ///
+    pub fn set_bind_group(&mut self, index: u32, bind_group: &'encoder BindGroup) {
+        self.render_pass
+            .set_bind_group(index, bind_group.wgpu_bind_group(), &[]);
+    }
+
     /// Sets the vertex buffer for the render pass for the given slot.
     ///
     /// # Parameters
@@ -1,11 +1,99 @@
 use wgpu::{
-    BlendState, ColorTargetState, ColorWrites, Face, FrontFace, MultisampleState,
+    BlendComponent, BlendFactor, BlendOperation, BlendState, ColorTargetState, ColorWrites,
+    CompareFunction, DepthStencilState, Device, Face, FrontFace, MultisampleState,
     PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology,
-    RenderPipelineDescriptor,
+    RenderPipelineDescriptor, TextureFormat,
 };
 
 use super::{GfxError, Material, Screen};
 
+/// The format of the depth buffer used by pipelines that test depth.
+pub const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth32Float;
+
+/// How the colours written by a pipeline are combined with the target.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    /// The new colour replaces the old one.  Used for opaque geometry.
+    #[default]
+    Replace,
+
+    /// The new colour is blended with the old one using its alpha.  Used for
+    /// translucent geometry.
+    Alpha,
+
+    /// The new colour is added to the old one.
+    Additive,
+}
+
+/// Which faces of the triangles a pipeline throws away.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CullMode {
+    /// Draw both sides of every triangle.
+    None,
+
+    /// Throw away triangles facing away from the camera.
+    #[default]
+    Back,
+
+    /// Throw away triangles facing the camera.
+    Front,
+}
+
+/// How a pipeline uses the depth buffer.
+///
+/// # Notes
+///
+/// Pipelines that use the depth buffer expect a depth attachment with the
+/// format [DEPTH_FORMAT].
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DepthMode {
+    /// The depth buffer is not used.
+    #[default]
+    None,
+
+    /// Fragments behind the depth buffer are thrown away, but the depth buffer
+    /// is not updated.  Used for translucent geometry.
+    Test,
+
+    /// Fragments behind the depth buffer are thrown away and the depth buffer
+    /// is updated with the fragments that pass.
+    TestAndWrite,
+}
+
+/// The format of a colour target of a pipeline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TargetFormat {
+    /// The format of the screen's surface, whatever it currently is.
+    Surface,
+
+    /// A fixed texture format, for example of a render target.
+    Format(TextureFormat),
+}
+
+/// The fixed-function state of a render pipeline.
+///
+/// # Notes
+///
+/// This is everything about a pipeline apart from its shaders and vertex
+/// layouts, which come from its material.  If no colour targets are given,
+/// the pipeline has a single colour target using the surface format.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PipelineState {
+    /// How colours are blended with the colour targets.
+    pub blend: BlendMode,
+
+    /// Which faces are culled.
+    pub cull: CullMode,
+
+    /// How the depth buffer is used.
+    pub depth: DepthMode,
+
+    /// The formats of the colour targets in location order.
+    pub colour_targets: Vec<TargetFormat>,
+}
+
 /// A render pipeline builder.
 ///
 /// # Notes
@@ -14,10 +102,15 @@ use super::{GfxError, Material, Screen};
 /// from a material.
 ///
 /// You can create the render pipeline using the [RenderPipelineBuilder::build] method.
+/// Each call creates a new pipeline, even for identical state.  Use
+/// [Screen::pipeline] instead to share pipelines through the screen's cache.
+///
+/// [Screen::pipeline]: struct.Screen.html#method.pipeline
 ///
 pub struct RenderPipelineBuilder<'material> {
     desc: &'static str,
     shader: Option<&'material Material<'material>>,
+    state: PipelineState,
 }
 
 /// A render pipeline.
@@ -52,7 +145,11 @@ impl<'material> RenderPipelineBuilder<'material> {
     /// The new render pipeline builder.
     ///
     pub(crate) fn new(desc: &'static str) -> Self {
-        Self { desc, shader: None }
+        Self {
+            desc,
+            shader: None,
+            state: PipelineState::default(),
+        }
     }
 
     /// Sets the material for the render pipeline.
@@ -82,6 +179,73 @@ impl<'material> RenderPipelineBuilder<'material> {
         self
     }
 
+    /// Adds a colour target to the render pipeline.
+    ///
+    /// # Parameters
+    ///
+    /// * `format` - The texture format of the colour attachment that the
+    ///   fragment shader writes to at the next `@location`.
+    ///
+    /// # Returns
+    ///
+    /// The render pipeline builder with the colour target added.
+    ///
+    /// # Notes
+    ///
+    /// If no colour targets are added, the pipeline will have a single colour
+    /// target using the surface format of the screen.  Add one target for each
+    /// colour attachment of the render passes this pipeline is used in, for
+    /// example when rendering into a G-buffer.
+    ///
+    pub fn colour_target(mut self, format: TextureFormat) -> Self {
+        self.state.colour_targets.push(TargetFormat::Format(format));
+        self
+    }
+
+    /// Adds a colour target using the surface format of the screen.
+    ///
+    /// # Returns
+    ///
+    /// The render pipeline builder with the colour target added.
+    ///
+    pub fn surface_target(mut self) -> Self {
+        self.state.colour_targets.push(TargetFormat::Surface);
+        self
+    }
+
+    /// Sets how colours are blended with the colour targets.
+    ///
+    /// # Parameters
+    ///
+    /// * `blend` - The blend mode.  The default is [BlendMode::Replace].
+    ///
+    pub fn blend(mut self, blend: BlendMode) -> Self {
+        self.state.blend = blend;
+        self
+    }
+
+    /// Sets which faces are culled.
+    ///
+    /// # Parameters
+    ///
+    /// * `cull` - The cull mode.  The default is [CullMode::Back].
+    ///
+    pub fn cull(mut self, cull: CullMode) -> Self {
+        self.state.cull = cull;
+        self
+    }
+
+    /// Sets how the depth buffer is used.
+    ///
+    /// # Parameters
+    ///
+    /// * `depth` - The depth mode.  The default is [DepthMode::None].
+    ///
+    pub fn depth(mut self, depth: DepthMode) -> Self {
+        self.state.depth = depth;
+        self
+    }
+
     /// Builds the render pipeline.
     ///
     /// # Parameters
@@ -111,15 +275,75 @@ impl<'material> RenderPipelineBuilder<'material> {
     ///
     pub fn build(self, screen: &Screen) -> Result<RenderPipeline, GfxError> {
         let shader = self.shader.ok_or(GfxError::BadMaterialMissingShaders)?;
+        let formats = self.state.target_formats(screen.surface_format());
 
-        let render_pipeline_layout =
-            screen
-                .get_device()
-                .create_pipeline_layout(&PipelineLayoutDescriptor {
-                    label: Some("Render pipeline layout"),
-                    bind_group_layouts: &[],
-                    push_constant_ranges: &[],
-                });
+        Ok(RenderPipeline::new(
+            screen.get_device(),
+            self.desc,
+            shader,
+            &self.state,
+            &formats,
+            screen.sample_count(),
+        ))
+    }
+}
+
+impl PipelineState {
+    /// Resolves the formats of the colour targets.
+    ///
+    /// # Parameters
+    ///
+    /// * `surface_format` - The current format of the screen's surface.
+    ///
+    /// # Returns
+    ///
+    /// The texture format of each colour target in location order.
+    ///
+    pub(crate) fn target_formats(&self, surface_format: TextureFormat) -> Vec<TextureFormat> {
+        if self.colour_targets.is_empty() {
+            return vec![surface_format];
+        }
+
+        self.colour_targets
+            .iter()
+            .map(|target| match target {
+                TargetFormat::Surface => surface_format,
+                TargetFormat::Format(format) => *format,
+            })
+            .collect()
+    }
+}
+
+impl RenderPipeline {
+    /// Creates a new render pipeline.
+    ///
+    /// # Parameters
+    ///
+    /// * `device` - The WGPU device.
+    /// * `desc` - The description of the render pipeline for debugging purposes.
+    /// * `material` - The material providing the shaders and vertex layouts.
+    /// * `state` - The fixed-function state of the pipeline.
+    /// * `formats` - The resolved formats of the colour targets.
+    /// * `sample_count` - The number of samples per pixel of the targets.
+    ///
+    /// # Returns
+    ///
+    /// The new render pipeline.
+    ///
+    pub(crate) fn new(
+        device: &Device,
+        desc: &str,
+        material: &Material,
+        state: &PipelineState,
+        formats: &[TextureFormat],
+        sample_count: u32,
+    ) -> Self {
+        let bind_group_layouts = material.bind_group_layouts(device);
+        let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Render pipeline layout"),
+            bind_group_layouts: &bind_group_layouts.iter().collect::<Vec<_>>(),
+            push_constant_ranges: &[],
+        });
 
         // Create the render pipeline.
         //
@@ -127,43 +351,72 @@ impl<'material> RenderPipelineBuilder<'material> {
         // specifies the shader that will be used, the layout of the vertex
         // buffers, and the layout of the render targets.
 
-        let targets = &[Some(ColorTargetState {
-            format: screen.get_surface_format(),
-            blend: Some(BlendState::REPLACE),
-            write_mask: ColorWrites::ALL,
-        })];
-
-        let render_pipeline =
-            screen
-                .get_device()
-                .create_render_pipeline(&RenderPipelineDescriptor {
-                    label: Some(self.desc),
-                    layout: Some(&render_pipeline_layout),
-                    vertex: shader.vertex_state(),
-                    fragment: Some(shader.fragment_state(targets)),
-                    primitive: PrimitiveState {
-                        topology: PrimitiveTopology::TriangleList,
-                        strip_index_format: None,
-                        front_face: FrontFace::Ccw,
-                        cull_mode: Some(Face::Back),
-                        polygon_mode: PolygonMode::Fill,
-                        unclipped_depth: false,
-                        conservative: false,
-                    },
-                    depth_stencil: None,
-                    multisample: MultisampleState {
-                        count: 1,
-                        mask: !0,
-                        alpha_to_coverage_enabled: false,
-                    },
-                    multiview: None,
-                });
-
-        Ok(RenderPipeline { render_pipeline })
+        let blend = match state.blend {
+            BlendMode::Replace => BlendState::REPLACE,
+            BlendMode::Alpha => BlendState::ALPHA_BLENDING,
+            BlendMode::Additive => BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::SrcAlpha,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent::OVER,
+            },
+        };
+        let targets = formats
+            .iter()
+            .map(|&format| {
+                Some(ColorTargetState {
+                    format,
+                    blend: Some(blend),
+                    write_mask: ColorWrites::ALL,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let cull_mode = match state.cull {
+            CullMode::None => None,
+            CullMode::Back => Some(Face::Back),
+            CullMode::Front => Some(Face::Front),
+        };
+
+        let depth_stencil = match state.depth {
+            DepthMode::None => None,
+            DepthMode::Test | DepthMode::TestAndWrite => Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: state.depth == DepthMode::TestAndWrite,
+                depth_compare: CompareFunction::Less,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+        };
+
+        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some(desc),
+            layout: Some(&render_pipeline_layout),
+            vertex: material.vertex_state(),
+            fragment: Some(material.fragment_state(&targets)),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode,
+                polygon_mode: PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil,
+            multisample: MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        Self { render_pipeline }
     }
-}
 
-impl RenderPipeline {
     /// Gets the render pipeline.
     ///
     /// # Returns
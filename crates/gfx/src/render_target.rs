@@ -0,0 +1,103 @@
+use wgpu::{
+    Device, Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+    TextureView, TextureViewDescriptor,
+};
+
+/// An off-screen texture that can be rendered to.
+///
+/// # Notes
+///
+/// This is a wrapper around a WGPU texture and a view of that texture.  A
+/// render target can be used as a colour attachment of a render pass instead
+/// of the frame's surface, for example for G-buffer style rendering.
+///
+/// The texture is also created with the `TEXTURE_BINDING` usage so that a
+/// later pass can sample from it.
+///
+/// You can create a render target using the [Screen::create_render_target]
+/// method.
+///
+/// [Screen::create_render_target]: struct.Screen.html#method.create_render_target
+///
+#[derive(Debug)]
+pub struct RenderTarget {
+    /// The view of the whole texture that is rendered to.
+    ///
+    /// # Notes
+    ///
+    /// The view keeps the underlying WGPU texture alive.
+    ///
+    view: TextureView,
+
+    /// The format of the texture.
+    format: TextureFormat,
+
+    /// The size (in pixels) of the texture.
+    size: (u32, u32),
+}
+
+impl RenderTarget {
+    /// Creates a new render target.
+    ///
+    /// # Parameters
+    ///
+    /// * `desc` - The name of the render target for debugging purposes.
+    /// * `device` - The WGPU device.
+    /// * `format` - The texture format.
+    /// * `width` - The width of the texture (in pixels).
+    /// * `height` - The height of the texture (in pixels).
+    ///
+    /// # Returns
+    ///
+    /// The new render target.
+    ///
+    pub(crate) fn new(
+        desc: &str,
+        device: &Device,
+        format: TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some(desc),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
+        Self {
+            view,
+            format,
+            size: (width, height),
+        }
+    }
+
+    /// Returns the format of the render target.
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    /// Returns the size (in pixels) of the render target.
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Returns the view of the render target.
+    ///
+    /// # Notes
+    ///
+    /// This is useful for creating bind groups that sample from the target.
+    ///
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+}
@@ -1,4 +1,4 @@
-use std::marker::PhantomData;
+use std::{collections::HashMap, marker::PhantomData};
 
 use bytemuck::{Pod, Zeroable};
 use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
@@ -8,7 +8,11 @@ use wgpu::{
     Queue, ShaderModuleDescriptor, Surface, SurfaceConfiguration, TextureFormat, TextureUsages,
 };
 
-use super::{render_pipeline::RenderPipelineBuilder, Buffer, Frame, GfxError, Material};
+use super::{
+    pipeline_cache::PipelineCache, render_pipeline::RenderPipelineBuilder, BindGroup, Buffer,
+    BufferKind, Frame, GfxError, Material, PipelineDesc, PipelineHandle, RenderPipeline,
+    RenderTarget, Texture,
+};
 
 /// The main interface to the gfx library.
 ///
@@ -43,6 +47,18 @@ pub struct Screen<'window> {
     /// The WGPU queue.
     queue: Queue,
 
+    /// The surface formats supported by the adapter.
+    supported_formats: Vec<TextureFormat>,
+
+    /// The number of samples per pixel that pipelines are built for.
+    sample_count: u32,
+
+    /// The materials registered by name.
+    materials: HashMap<String, Material<'static>>,
+
+    /// The render pipelines built from the registered materials.
+    pipelines: PipelineCache,
+
     /// Used to tie the lifetime of the screen object to the lifetime of the
     /// window.
     ///
@@ -161,6 +177,10 @@ impl<'window> Screen<'window> {
             surface_size: (width, height),
             device,
             queue,
+            supported_formats: surface_caps.formats,
+            sample_count: 1,
+            materials: HashMap::new(),
+            pipelines: PipelineCache::default(),
         })
     }
 
@@ -186,12 +206,12 @@ impl<'window> Screen<'window> {
     /// [`Material::new`]: struct.Material.html#method.new
     /// [`include_wgsl!`]: https://docs.rs/wgpu/latest/wgpu/macro.include_wgsl.html
     ///
-    pub fn create_material(
+    pub fn create_material<'layout>(
         &self,
         shader: ShaderModuleDescriptor,
-        vertex_entry_point: &'static str,
-        fragment_entry_point: &'static str,
-    ) -> Material {
+        vertex_entry_point: &str,
+        fragment_entry_point: &str,
+    ) -> Material<'layout> {
         Material::new(
             &self.device,
             shader,
@@ -200,6 +220,69 @@ impl<'window> Screen<'window> {
         )
     }
 
+    /// Registers a material by name.
+    ///
+    /// # Parameters
+    ///
+    /// * `name` - The name of the material, used by [PipelineDesc].
+    /// * `material` - The material, usually created by [Screen::create_material].
+    ///
+    /// # Notes
+    ///
+    /// If a material with the same name was already registered, it is
+    /// replaced and every cached pipeline that uses it is rebuilt.
+    ///
+    /// [PipelineDesc]: struct.PipelineDesc.html
+    /// [Screen::create_material]: struct.Screen.html#method.create_material
+    ///
+    pub fn register_material(&mut self, name: &str, material: Material<'static>) {
+        let replaced = self.materials.insert(name.to_string(), material).is_some();
+        if replaced {
+            self.refresh_pipelines(Some(name));
+        }
+    }
+
+    /// Returns the material registered under the given name.
+    pub fn material(&self, name: &str) -> Option<&Material<'static>> {
+        self.materials.get(name)
+    }
+
+    /// Returns a handle to the render pipeline for a description.
+    ///
+    /// # Parameters
+    ///
+    /// * `desc` - The description of the pipeline.
+    ///
+    /// # Returns
+    ///
+    /// The handle of the pipeline.  The pipeline is only built the first time
+    /// a description is asked for.  After that the cached pipeline is used.
+    ///
+    /// # Errors
+    ///
+    /// * `GfxError::UnknownMaterial` - If the material is not registered.
+    ///
+    pub fn pipeline(&mut self, desc: &PipelineDesc) -> Result<PipelineHandle, GfxError> {
+        self.pipelines.get(
+            &self.device,
+            &self.materials,
+            desc,
+            self.surface_config.format,
+            self.sample_count,
+        )
+    }
+
+    /// Returns the render pipeline for a handle.
+    ///
+    /// # Notes
+    ///
+    /// Do not hold on to the returned pipeline across changes to the screen,
+    /// since it may be rebuilt.  Keep the handle instead.
+    ///
+    pub fn render_pipeline(&self, handle: PipelineHandle) -> &RenderPipeline {
+        self.pipelines.pipeline(handle)
+    }
+
     /// Creates a new render pipeline builder.
     ///
     /// # Parameters
@@ -219,7 +302,10 @@ impl<'window> Screen<'window> {
     /// [`RenderPipelineBuilder::new`]: struct.RenderPipelineBuilder.html#method.new
     /// [`RenderPipelineBuilder::build`]: struct.RenderPipelineBuilder.html#method.build
     ///
-    pub fn create_render_pipeline(&self, pipeline_desc: &'static str) -> RenderPipelineBuilder {
+    pub fn create_render_pipeline<'material>(
+        &self,
+        pipeline_desc: &'static str,
+    ) -> RenderPipelineBuilder<'material> {
         RenderPipelineBuilder::new(pipeline_desc)
     }
 
@@ -268,6 +354,319 @@ impl<'window> Screen<'window> {
         Buffer::new_index_buffer(desc, &self.device, data)
     }
 
+    /// Creates a new uniform buffer.
+    ///
+    /// # Parameters
+    ///
+    /// * `desc` - The name of the uniform buffer for debugging purposes.
+    /// * `data` - The initial contents of the buffer.
+    ///
+    /// # Returns
+    ///
+    /// The new uniform buffer.
+    ///
+    /// # Notes
+    ///
+    /// This will call [`Buffer::new_binding_buffer`] to create the buffer.
+    ///
+    /// [`Buffer::new_binding_buffer`]: struct.Buffer.html#method.new_binding_buffer
+    ///
+    pub fn create_uniform_buffer<T>(&self, desc: &'static str, data: &T) -> Buffer
+    where
+        T: Pod + Zeroable,
+    {
+        Buffer::new_binding_buffer(
+            desc,
+            &self.device,
+            BufferKind::Uniform,
+            std::slice::from_ref(data),
+        )
+    }
+
+    /// Creates a new storage buffer.
+    ///
+    /// # Parameters
+    ///
+    /// * `desc` - The name of the storage buffer for debugging purposes.
+    /// * `data` - The initial contents of the buffer.
+    ///
+    /// # Returns
+    ///
+    /// The new storage buffer.
+    ///
+    /// # Notes
+    ///
+    /// This will call [`Buffer::new_binding_buffer`] to create the buffer.
+    ///
+    /// [`Buffer::new_binding_buffer`]: struct.Buffer.html#method.new_binding_buffer
+    ///
+    pub fn create_storage_buffer<T>(&self, desc: &'static str, data: &[T]) -> Buffer
+    where
+        T: Pod + Zeroable,
+    {
+        Buffer::new_binding_buffer(desc, &self.device, BufferKind::Storage, data)
+    }
+
+    /// Writes new contents to the start of a uniform or storage buffer.
+    ///
+    /// # Parameters
+    ///
+    /// * `buffer` - The buffer to write to.
+    /// * `data` - The new contents.  This must fit in the buffer.
+    ///
+    /// # Notes
+    ///
+    /// The write happens before the next frame is submitted.
+    ///
+    pub fn write_buffer<T>(&self, buffer: &Buffer, data: &[T])
+    where
+        T: Pod + Zeroable,
+    {
+        self.queue
+            .write_buffer(buffer.wgpu_buffer(), 0, bytemuck::cast_slice(data));
+    }
+
+    /// Creates a new texture atlas and generates its mipmaps.
+    ///
+    /// # Parameters
+    ///
+    /// * `desc` - The name of the texture for debugging purposes.
+    /// * `pixels` - The RGBA image data, 4 bytes per pixel, row by row.
+    /// * `width` - The width of the image (in pixels).
+    /// * `height` - The height of the image (in pixels).
+    /// * `tile_size` - The size (in pixels) of each tile in the atlas.
+    ///
+    /// # Returns
+    ///
+    /// The new texture, with a sampler that matches its mip chain.
+    ///
+    /// # Notes
+    ///
+    /// This will call [`Texture::new_atlas`] to create the texture.  The
+    /// mipmaps are generated on the GPU, one tile at a time, so that colours
+    /// do not leak between neighbouring tiles.
+    ///
+    /// [`Texture::new_atlas`]: struct.Texture.html#method.new_atlas
+    ///
+    pub fn create_atlas_texture(
+        &self,
+        desc: &str,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+        tile_size: (u32, u32),
+    ) -> Result<Texture, GfxError> {
+        Texture::new_atlas(
+            desc,
+            &self.device,
+            &self.queue,
+            pixels,
+            width,
+            height,
+            tile_size,
+        )
+    }
+
+    /// Creates a new texture array with one tile per layer and generates its
+    /// mipmaps.
+    ///
+    /// # Parameters
+    ///
+    /// * `desc` - The name of the texture for debugging purposes.
+    /// * `pixels` - The RGBA image data of every layer, one after the other.
+    /// * `tile_size` - The size (in pixels) of each tile.
+    /// * `layer_count` - The number of tiles, and therefore layers.
+    ///
+    /// # Returns
+    ///
+    /// The new texture, with a sampler that repeats each layer.
+    ///
+    /// # Notes
+    ///
+    /// This will call [`Texture::new_array`] to create the texture.  This is
+    /// the alternative to a texture atlas: each tile is addressed by its layer
+    /// index, so UVs can wrap and mipmaps never mix tiles together.
+    ///
+    /// [`Texture::new_array`]: struct.Texture.html#method.new_array
+    ///
+    pub fn create_array_texture(
+        &self,
+        desc: &str,
+        pixels: &[u8],
+        tile_size: (u32, u32),
+        layer_count: u32,
+    ) -> Result<Texture, GfxError> {
+        Texture::new_array(
+            desc,
+            &self.device,
+            &self.queue,
+            pixels,
+            tile_size,
+            layer_count,
+        )
+    }
+
+    /// Creates a bind group holding the textures of a material.
+    ///
+    /// # Parameters
+    ///
+    /// * `desc` - The name of the bind group for debugging purposes.
+    /// * `material` - The material that declares the texture bindings.
+    /// * `textures` - The textures, in the order they were added to the
+    ///   material.
+    ///
+    /// # Returns
+    ///
+    /// The new bind group, to be bound at index 0 of a render pass.
+    ///
+    /// # Errors
+    ///
+    /// * `GfxError::BadTextureBindings` - If the number of textures does not
+    ///   match the material.
+    ///
+    pub fn create_texture_bind_group(
+        &self,
+        desc: &str,
+        material: &Material,
+        textures: &[&Texture],
+    ) -> Result<BindGroup, GfxError> {
+        BindGroup::new_textures(desc, &self.device, material, textures)
+    }
+
+    /// Creates a new bind group holding the buffers that a material declares.
+    ///
+    /// # Parameters
+    ///
+    /// * `desc` - The name of the bind group for debugging purposes.
+    /// * `material` - The material that declares the buffer bindings.
+    /// * `buffers` - The buffers, in the order they were added to the
+    ///   material.
+    ///
+    /// # Returns
+    ///
+    /// The new bind group, to be bound at index 1 of a render pass.
+    ///
+    /// # Errors
+    ///
+    /// * `GfxError::BadBufferBindings` - If the buffers do not match the
+    ///   material.
+    ///
+    pub fn create_buffer_bind_group(
+        &self,
+        desc: &str,
+        material: &Material,
+        buffers: &[&Buffer],
+    ) -> Result<BindGroup, GfxError> {
+        BindGroup::new_buffers(desc, &self.device, material, buffers)
+    }
+
+    /// Creates a new off-screen render target the same size as the surface.
+    ///
+    /// # Parameters
+    ///
+    /// * `desc` - The name of the render target for debugging purposes.
+    /// * `format` - The texture format of the render target.
+    ///
+    /// # Returns
+    ///
+    /// The new render target.
+    ///
+    /// # Notes
+    ///
+    /// The render target does not follow the size of the surface.  It should
+    /// be created again when the window is resized.
+    ///
+    pub fn create_render_target(&self, desc: &str, format: TextureFormat) -> RenderTarget {
+        let (width, height) = self.surface_size;
+        RenderTarget::new(desc, &self.device, format, width, height)
+    }
+
+    /// Returns the size (in pixels) of the surface.
+    pub fn size(&self) -> (u32, u32) {
+        self.surface_size
+    }
+
+    /// Returns the texture format of the surface.
+    ///
+    /// # Notes
+    ///
+    /// This is the format that render pipelines drawing to the screen use.
+    ///
+    pub fn surface_format(&self) -> TextureFormat {
+        self.surface_config.format
+    }
+
+    /// Changes the texture format of the surface.
+    ///
+    /// # Parameters
+    ///
+    /// * `format` - The new format.  It must be one of the formats supported
+    ///   by the adapter.
+    ///
+    /// # Errors
+    ///
+    /// * `GfxError::UnsupportedSurfaceFormat` - If the format is not supported.
+    ///
+    /// # Notes
+    ///
+    /// Every cached pipeline that draws to the surface is rebuilt.
+    ///
+    pub fn set_surface_format(&mut self, format: TextureFormat) -> Result<(), GfxError> {
+        if !self.supported_formats.contains(&format) {
+            return Err(GfxError::UnsupportedSurfaceFormat(format));
+        }
+        if format != self.surface_config.format {
+            info!("Surface format: {:?}", format);
+            self.surface_config.format = format;
+            self.recreate();
+            self.refresh_pipelines(None);
+        }
+        Ok(())
+    }
+
+    /// Returns the number of samples per pixel that pipelines are built for.
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// Changes the number of samples per pixel that pipelines are built for.
+    ///
+    /// # Parameters
+    ///
+    /// * `sample_count` - The number of samples.  This must be 1 or 4.
+    ///
+    /// # Errors
+    ///
+    /// * `GfxError::UnsupportedSampleCount` - If the count is not 1 or 4.
+    ///
+    /// # Notes
+    ///
+    /// Every cached pipeline is rebuilt.  The colour and depth attachments
+    /// of the render passes the pipelines are used in must have the same
+    /// number of samples.
+    ///
+    pub fn set_sample_count(&mut self, sample_count: u32) -> Result<(), GfxError> {
+        if sample_count != 1 && sample_count != 4 {
+            return Err(GfxError::UnsupportedSampleCount(sample_count));
+        }
+        if sample_count != self.sample_count {
+            self.sample_count = sample_count;
+            self.refresh_pipelines(None);
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the cached pipelines that are out of date.
+    fn refresh_pipelines(&mut self, replaced_material: Option<&str>) {
+        self.pipelines.refresh(
+            &self.device,
+            &self.materials,
+            self.surface_config.format,
+            self.sample_count,
+            replaced_material,
+        );
+    }
+
     /// Creates a new [Frame] that can be used to render to the screen.
     ///
     /// # Parameters
@@ -286,7 +685,7 @@ impl<'window> Screen<'window> {
     ///
     /// [`Frame::new`]: struct.Frame.html#method.new
     ///
-    pub fn start_frame(&self, frame_desc: &'static str) -> Result<Frame, GfxError> {
+    pub fn start_frame(&self, frame_desc: &'static str) -> Result<Frame<'_>, GfxError> {
         Frame::new(&self.device, &self.queue, &self.surface, frame_desc).map_err(GfxError::from)
     }
 
@@ -294,10 +693,6 @@ impl<'window> Screen<'window> {
         &self.device
     }
 
-    pub(crate) fn get_surface_format(&self) -> TextureFormat {
-        self.surface_config.format
-    }
-
     /// Resizes the surface.
     ///
     /// # Parameters
@@ -0,0 +1,323 @@
+use std::num::NonZeroU32;
+
+use wgpu::{
+    AddressMode, CommandEncoderDescriptor, Device, Extent3d, FilterMode, ImageCopyTexture,
+    ImageDataLayout, Origin3d, Queue, Sampler, SamplerDescriptor, TextureAspect, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureUsages, TextureView, TextureViewDescriptor,
+    TextureViewDimension,
+};
+
+use super::{
+    mipmap::{mip_level_count, MipmapGenerator},
+    GfxError,
+};
+
+/// The format of all textures created from RGBA images.
+const TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+/// A texture that can be sampled by shaders.
+///
+/// # Notes
+///
+/// This is a wrapper around a WGPU texture, a view of all its mip levels and
+/// layers, and a sampler that matches the way the texture was built.
+///
+/// The texture is always viewed as a texture array, even an atlas which only
+/// has a single layer.  This lets the same shader sample from either.
+///
+/// You can create a texture atlas with mipmaps using the
+/// [Screen::create_atlas_texture] method, or a texture array with one tile in
+/// each layer using the [Screen::create_array_texture] method.
+///
+/// [Screen::create_atlas_texture]: struct.Screen.html#method.create_atlas_texture
+/// [Screen::create_array_texture]: struct.Screen.html#method.create_array_texture
+///
+#[derive(Debug)]
+pub struct Texture {
+    /// The view of the whole texture, including all mip levels and layers.
+    view: TextureView,
+
+    /// The sampler to use with this texture.
+    sampler: Sampler,
+
+    /// The size (in pixels) of level 0 of each layer of the texture.
+    size: (u32, u32),
+
+    /// The number of array layers in the texture.
+    layer_count: u32,
+
+    /// The number of mip levels in the texture.
+    mip_level_count: u32,
+}
+
+impl Texture {
+    /// Creates a new texture atlas with a full mip chain.
+    ///
+    /// # Parameters
+    ///
+    /// * `desc` - The name of the texture for debugging purposes.
+    /// * `device` - The WGPU device.
+    /// * `queue` - The WGPU queue used to upload the image and generate the
+    ///   mipmaps.
+    /// * `pixels` - The RGBA image data, 4 bytes per pixel, row by row.
+    /// * `width` - The width of the image (in pixels).
+    /// * `height` - The height of the image (in pixels).
+    /// * `tile_size` - The size (in pixels) of each tile in the atlas.
+    ///
+    /// # Returns
+    ///
+    /// The new texture.
+    ///
+    /// # Errors
+    ///
+    /// * `GfxError::BadTextureData` - If the pixel data does not match the size.
+    /// * `GfxError::BadTileSize` - If the image is not made of whole tiles.
+    ///
+    /// # Notes
+    ///
+    /// The mip chain stops when a tile is a single pixel wide or high, and
+    /// each level is generated per tile.  Together with the sampler created
+    /// here, this stops colours bleeding between tiles.
+    ///
+    pub(crate) fn new_atlas(
+        desc: &str,
+        device: &Device,
+        queue: &Queue,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+        tile_size: (u32, u32),
+    ) -> Result<Self, GfxError> {
+        if tile_size.0 == 0
+            || tile_size.1 == 0
+            || !width.is_multiple_of(tile_size.0)
+            || !height.is_multiple_of(tile_size.1)
+        {
+            return Err(GfxError::BadTileSize);
+        }
+
+        Self::new(
+            desc,
+            device,
+            queue,
+            pixels,
+            (width, height),
+            1,
+            tile_size,
+            AddressMode::ClampToEdge,
+        )
+    }
+
+    /// Creates a new texture array with one tile in each layer and a full mip
+    /// chain.
+    ///
+    /// # Parameters
+    ///
+    /// * `desc` - The name of the texture for debugging purposes.
+    /// * `device` - The WGPU device.
+    /// * `queue` - The WGPU queue used to upload the image and generate the
+    ///   mipmaps.
+    /// * `pixels` - The RGBA image data of all the layers, one after the
+    ///   other.  Each layer is 4 bytes per pixel, row by row.
+    /// * `tile_size` - The size (in pixels) of each layer.
+    /// * `layer_count` - The number of layers.
+    ///
+    /// # Returns
+    ///
+    /// The new texture.
+    ///
+    /// # Errors
+    ///
+    /// * `GfxError::BadTextureData` - If the pixel data does not match the size.
+    /// * `GfxError::BadTileSize` - If the tile size is zero.
+    /// * `GfxError::BadLayerCount` - If there are no layers or the device does
+    ///   not support that many.
+    ///
+    /// # Notes
+    ///
+    /// Because every tile has a layer of its own, the sampler repeats the
+    /// texture so that a tile can be stretched across a larger quad, and each
+    /// mip level is generated from its own layer alone.
+    ///
+    pub(crate) fn new_array(
+        desc: &str,
+        device: &Device,
+        queue: &Queue,
+        pixels: &[u8],
+        tile_size: (u32, u32),
+        layer_count: u32,
+    ) -> Result<Self, GfxError> {
+        if tile_size.0 == 0 || tile_size.1 == 0 {
+            return Err(GfxError::BadTileSize);
+        }
+        if layer_count == 0 || layer_count > device.limits().max_texture_array_layers {
+            return Err(GfxError::BadLayerCount(layer_count));
+        }
+
+        Self::new(
+            desc,
+            device,
+            queue,
+            pixels,
+            tile_size,
+            layer_count,
+            tile_size,
+            AddressMode::Repeat,
+        )
+    }
+
+    /// Creates a new texture, uploads its pixels and generates its mipmaps.
+    ///
+    /// # Parameters
+    ///
+    /// * `desc` - The name of the texture for debugging purposes.
+    /// * `device` - The WGPU device.
+    /// * `queue` - The WGPU queue.
+    /// * `pixels` - The RGBA image data of all the layers, one after the other.
+    /// * `size` - The size (in pixels) of each layer.
+    /// * `layer_count` - The number of layers.
+    /// * `tile_size` - The size (in pixels) of each tile within a layer.
+    /// * `address_mode` - How the sampler deals with UVs outside 0..1.
+    ///
+    /// # Returns
+    ///
+    /// The new texture.
+    ///
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        desc: &str,
+        device: &Device,
+        queue: &Queue,
+        pixels: &[u8],
+        (width, height): (u32, u32),
+        layer_count: u32,
+        tile_size: (u32, u32),
+        address_mode: AddressMode,
+    ) -> Result<Self, GfxError> {
+        if pixels.len() != (width * height * layer_count * 4) as usize {
+            return Err(GfxError::BadTextureData);
+        }
+
+        let mip_level_count = mip_level_count(tile_size);
+        let size = Extent3d {
+            width,
+            height,
+            depth_or_array_layers: layer_count,
+        };
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some(desc),
+            size,
+            mip_level_count,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TEXTURE_FORMAT,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            pixels,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(width * 4),
+                rows_per_image: NonZeroU32::new(height),
+            },
+            size,
+        );
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Mipmap encoder"),
+        });
+        MipmapGenerator::new(device, TEXTURE_FORMAT).generate(
+            device,
+            &mut encoder,
+            &texture,
+            mip_level_count,
+            layer_count,
+            tile_size,
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let view = texture.create_view(&TextureViewDescriptor {
+            label: Some(desc),
+            dimension: Some(TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let sampler = create_tile_sampler(device, desc, mip_level_count, address_mode);
+
+        Ok(Self {
+            view,
+            sampler,
+            size: (width, height),
+            layer_count,
+            mip_level_count,
+        })
+    }
+
+    /// Returns the view of the texture, including all mip levels and layers.
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+
+    /// Returns the sampler that matches the texture.
+    pub fn sampler(&self) -> &Sampler {
+        &self.sampler
+    }
+
+    /// Returns the size (in pixels) of level 0 of each layer of the texture.
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Returns the number of array layers in the texture.
+    ///
+    /// # Notes
+    ///
+    /// This is 1 for a texture atlas.
+    ///
+    pub fn layer_count(&self) -> u32 {
+        self.layer_count
+    }
+
+    /// Returns the number of mip levels in the texture.
+    pub fn mip_level_count(&self) -> u32 {
+        self.mip_level_count
+    }
+}
+
+/// Creates the sampler used for textures made of tiles.
+///
+/// # Notes
+///
+/// Magnification and minification use the nearest texel so that pixel art
+/// stays crisp and a sample never straddles two tiles.  Blending between mip
+/// levels is linear to hide the switch from one level to the next, and the
+/// level of detail is clamped to the levels that were generated.
+///
+fn create_tile_sampler(
+    device: &Device,
+    desc: &str,
+    mip_level_count: u32,
+    address_mode: AddressMode,
+) -> Sampler {
+    device.create_sampler(&SamplerDescriptor {
+        label: Some(desc),
+        address_mode_u: address_mode,
+        address_mode_v: address_mode,
+        address_mode_w: address_mode,
+        mag_filter: FilterMode::Nearest,
+        min_filter: FilterMode::Nearest,
+        mipmap_filter: FilterMode::Linear,
+        lod_min_clamp: 0.0,
+        lod_max_clamp: (mip_level_count - 1) as f32,
+        ..Default::default()
+    })
+}
@@ -0,0 +1,137 @@
+//
+// Vertex shader
+//
+
+// A terrain vertex packed into two words (see `TerrainVertex` in the game).
+//
+// position: x (bits 0-7), y (8-15), z (16-23), light (24-31)
+// texture:  u (bits 0-7), v (8-15), tile (16-26), face (27-29),
+//           ambient occlusion (30-31)
+//
+// Positions and texture coordinates are in eighths of a block or tile.
+//
+// The position of the chunk's first block comes from the instance buffer.
+struct VertexInput {
+    @location(0) position: u32,
+    @location(1) texture: u32,
+    @location(2) chunk: vec3<i32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) tile: u32,
+    @location(2) shade: f32,
+};
+
+// Where a tile is found in the tile textures.
+struct TileInfo {
+    rect: vec4<f32>,
+    layer: u32,
+}
+
+@group(1) @binding(0)
+var<storage, read> tile_table: array<TileInfo>;
+
+// The camera (see `CameraUniform` in the game).  Positions are measured from
+// the block the camera is in before they are transformed, so that they stay
+// small enough for f32 anywhere in the world.
+struct Camera {
+    view_projection: mat4x4<f32>,
+    origin: vec4<i32>,
+}
+
+@group(1) @binding(1)
+var<uniform> camera: Camera;
+
+// Returns the normal of a face, in the same order as `Face::ALL`.
+fn face_normal(face: u32) -> vec3<f32> {
+    var normals = array<vec3<f32>, 6>(
+        vec3(0.0, 0.0, 1.0),
+        vec3(0.0, 0.0, -1.0),
+        vec3(-1.0, 0.0, 0.0),
+        vec3(1.0, 0.0, 0.0),
+        vec3(0.0, 1.0, 0.0),
+        vec3(0.0, -1.0, 0.0),
+    );
+    return normals[face];
+}
+
+// Darkens each side of a block by a fixed amount so that neighbouring faces
+// can be told apart: tops are brightest and bottoms darkest.
+fn face_shade(normal: vec3<f32>) -> f32 {
+    return 0.8 + 0.2 * normal.y - 0.1 * abs(normal.x);
+}
+
+// Darkens the corners of faces that are tucked in next to other blocks.  The
+// ambient occlusion level runs from 0 (both sides blocked) to 3 (open).  The
+// steps are uneven so that a single neighbouring block is only a hint.
+fn ao_shade(ao: u32) -> f32 {
+    var levels = array<f32, 4>(0.45, 0.6, 0.8, 1.0);
+    return levels[ao];
+}
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    let position = vec3(
+        f32(model.position & 255u),
+        f32((model.position >> 8u) & 255u),
+        f32((model.position >> 16u) & 255u),
+    ) / 8.0;
+    let light = model.position >> 24u;
+    let sky = f32(light >> 4u) / 15.0;
+    let block = f32(light & 15u) / 15.0;
+    let face = (model.texture >> 27u) & 7u;
+    let ao = model.texture >> 30u;
+
+    var output: VertexOutput;
+    output.uv = vec2(
+        f32(model.texture & 255u),
+        f32((model.texture >> 8u) & 255u),
+    ) / 8.0;
+    output.tile = (model.texture >> 16u) & 2047u;
+    output.shade = face_shade(face_normal(face)) * ao_shade(ao) * max(sky, block);
+
+    let relative = vec3<f32>(model.chunk - camera.origin.xyz) + position;
+    output.clip_position = camera.view_projection * vec4(relative, 1.0);
+    return output;
+}
+
+
+//
+// Fragment shaders
+//
+
+@group(0) @binding(0)
+var tiles: texture_2d_array<f32>;
+@group(0) @binding(1)
+var tiles_sampler: sampler;
+
+// Samples the tile of a face.  UVs are measured in tiles, so they are wrapped
+// into the tile's rectangle to repeat it across merged faces.  The gradients
+// come from the unwrapped UVs so that the mip level does not jump at the
+// seams.
+fn sample_tile(in: VertexOutput) -> vec4<f32> {
+    let tile = tile_table[in.tile];
+    let uv = tile.rect.xy + fract(in.uv) * tile.rect.zw;
+    let ddx = dpdx(in.uv) * tile.rect.zw;
+    let ddy = dpdy(in.uv) * tile.rect.zw;
+    return textureSampleGrad(tiles, tiles_sampler, uv, i32(tile.layer), ddx, ddy);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let colour = sample_tile(in);
+    return vec4(colour.rgb * in.shade, colour.a);
+}
+
+// Drops any texels that are mostly transparent, for leaves, grass and the
+// like that are drawn without blending.
+@fragment
+fn fs_cutout(in: VertexOutput) -> @location(0) vec4<f32> {
+    let colour = sample_tile(in);
+    if colour.a < 0.5 {
+        discard;
+    }
+    return vec4(colour.rgb * in.shade, colour.a);
+}
@@ -0,0 +1,331 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bytemuck::{Pod, Zeroable};
+use glam::{DVec3, Mat4, Vec3};
+
+use crate::input::{Action, Input};
+
+/// The closest the camera can look straight up or down, in radians.  Looking
+/// exactly along the up axis would leave the view with no sideways direction.
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.001;
+
+/// A first-person camera with a perspective projection.
+///
+/// # Notes
+///
+/// The world is right-handed with y up.  With a yaw and pitch of zero the
+/// camera looks along -z.  Turning right increases the yaw, and looking up
+/// increases the pitch.
+///
+/// Depth runs from 0 at the near plane to 1 at the far plane, as wgpu
+/// expects.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Camera {
+    /// The position of the eye in the world, in blocks.
+    pub position: DVec3,
+
+    /// The angle (in radians) the camera is turned right from -z.
+    pub yaw: f32,
+
+    /// The angle (in radians) the camera looks up from the horizon, kept
+    /// just short of straight up or down.
+    pub pitch: f32,
+
+    /// The vertical field of view, in radians.
+    pub fov: f32,
+
+    /// The distance (in blocks) to the near clipping plane.
+    pub near: f32,
+
+    /// The distance (in blocks) to the far clipping plane.
+    pub far: f32,
+
+    /// The width of the view divided by its height.
+    pub aspect: f32,
+}
+
+/// The camera as the shader sees it (see `Camera` in `block.wgsl`).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Zeroable, Pod)]
+pub struct CameraUniform {
+    /// The projection and view, measured from [CameraUniform::origin].
+    pub view_projection: [[f32; 4]; 4],
+
+    /// The block the camera is in.  The last element is padding.
+    pub origin: [i32; 4],
+}
+
+/// Moves a camera with the movement actions and turns it with the mouse.
+///
+/// # Notes
+///
+/// The forward, back, left and right actions move the camera along the
+/// ground, whichever way it is looking up or down.  The up and down actions
+/// move it straight up and down.
+///
+#[derive(Clone, Debug)]
+pub struct CameraControls {
+    /// How fast the camera moves, in blocks per second.
+    pub speed: f32,
+
+    /// How far the camera turns for each pixel the mouse moves, in radians.
+    pub sensitivity: f32,
+
+    /// How far the mouse has moved since the camera was last turned, in
+    /// pixels.
+    look: (f64, f64),
+}
+
+impl Camera {
+    /// Creates a camera at a position looking along -z, with a 70° field of
+    /// view and a square view.
+    pub fn new(position: DVec3) -> Camera {
+        Camera {
+            position,
+            yaw: 0.0,
+            pitch: 0.0,
+            fov: 70f32.to_radians(),
+            near: 0.1,
+            far: 1000.0,
+            aspect: 1.0,
+        }
+    }
+
+    /// Sets the aspect ratio from the size (in pixels) of the view.  A view
+    /// with no area leaves it unchanged.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.aspect = width as f32 / height as f32;
+        }
+    }
+
+    /// Turns the camera, keeping it from looking past straight up or down.
+    ///
+    /// # Parameters
+    ///
+    /// * `yaw` - The angle (in radians) to turn right.
+    /// * `pitch` - The angle (in radians) to look up.
+    ///
+    pub fn turn(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = (self.yaw + yaw).rem_euclid(std::f32::consts::TAU);
+        self.pitch = (self.pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Returns the direction the camera is looking in.
+    pub fn forward(&self) -> Vec3 {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        Vec3::new(sin_yaw * cos_pitch, sin_pitch, -cos_yaw * cos_pitch)
+    }
+
+    /// Returns the direction to the right of the camera, along the ground.
+    pub fn right(&self) -> Vec3 {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        Vec3::new(cos_yaw, 0.0, sin_yaw)
+    }
+
+    /// Returns the block the camera is in, which the view is measured from.
+    pub fn origin(&self) -> [i64; 3] {
+        let floor = self.position.floor();
+        [floor.x as i64, floor.y as i64, floor.z as i64]
+    }
+
+    /// Returns the view matrix, which moves the world so the camera is at the
+    /// origin looking along -z.  Positions are measured from
+    /// [Camera::origin].
+    pub fn view(&self) -> Mat4 {
+        let eye = (self.position - self.position.floor()).as_vec3();
+        Mat4::look_to_rh(eye, self.forward(), Vec3::Y)
+    }
+
+    /// Returns the projection matrix.
+    pub fn projection(&self) -> Mat4 {
+        Mat4::perspective_rh(self.fov, self.aspect, self.near, self.far)
+    }
+
+    /// Returns the camera as the shader sees it.
+    pub fn uniform(&self) -> CameraUniform {
+        let [x, y, z] = self.origin();
+        CameraUniform {
+            view_projection: (self.projection() * self.view()).to_cols_array_2d(),
+            origin: [x as i32, y as i32, z as i32, 0],
+        }
+    }
+}
+
+impl CameraControls {
+    /// Creates controls that move at 10 blocks per second.
+    pub fn new() -> CameraControls {
+        CameraControls {
+            speed: 10.0,
+            sensitivity: 0.003,
+            look: (0.0, 0.0),
+        }
+    }
+
+    /// Records the mouse moving, in pixels.
+    pub fn mouse_moved(&mut self, dx: f64, dy: f64) {
+        self.look.0 += dx;
+        self.look.1 += dy;
+    }
+
+    /// Forgets the mouse movement since the camera was last turned, such as
+    /// when the cursor is let go.
+    pub fn release(&mut self) {
+        self.look = (0.0, 0.0);
+    }
+
+    /// Turns the camera by the mouse movement since it was last turned.  This
+    /// is done every frame, so looking around stays smooth between ticks.
+    pub fn look(&mut self, camera: &mut Camera) {
+        let (dx, dy) = std::mem::take(&mut self.look);
+        camera.turn(dx as f32 * self.sensitivity, -dy as f32 * self.sensitivity);
+    }
+
+    /// Returns the way along the ground that the forward, back, left and
+    /// right actions held point, for a camera looking any way.  This has a
+    /// length of 1, or 0 if no movement action is held or they cancel out.
+    pub fn heading(camera: &Camera, input: &Input) -> Vec3 {
+        let right = camera.right();
+        let ahead = Vec3::Y.cross(right);
+        let direction = ahead * axis(input, Action::MoveForward, Action::MoveBack)
+            + right * axis(input, Action::MoveRight, Action::MoveLeft);
+        direction.normalize_or_zero()
+    }
+
+    /// Moves the camera by the movement actions held, flying freely.
+    ///
+    /// # Parameters
+    ///
+    /// * `camera` - The camera to move.
+    /// * `input` - The state of the actions.
+    /// * `dt` - The time (in seconds) to move it for.
+    ///
+    pub fn update(&self, camera: &mut Camera, input: &Input, dt: f32) {
+        let direction = CameraControls::heading(camera, input)
+            + Vec3::Y * axis(input, Action::MoveUp, Action::MoveDown);
+        let step = direction.normalize_or_zero() * self.speed * dt;
+        camera.position += step.as_dvec3();
+    }
+}
+
+/// Returns 1 if only the positive action is held, -1 if only the negative
+/// one is held, or 0.
+fn axis(input: &Input, positive: Action, negative: Action) -> f32 {
+    input.held(positive) as i32 as f32 - input.held(negative) as i32 as f32
+}
+
+impl Default for CameraControls {
+    fn default() -> Self {
+        CameraControls::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec4;
+    use winit::event::VirtualKeyCode;
+
+    use super::*;
+    use crate::input::Binding;
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-5, "{a} != {b}");
+    }
+
+    /// Returns where a point in the world ends up in normalised device
+    /// coordinates.
+    fn project(camera: &Camera, point: DVec3) -> Vec3 {
+        let uniform = camera.uniform();
+        let origin = DVec3::new(
+            uniform.origin[0] as f64,
+            uniform.origin[1] as f64,
+            uniform.origin[2] as f64,
+        );
+        let clip = Mat4::from_cols_array_2d(&uniform.view_projection)
+            * (point - origin).as_vec3().extend(1.0);
+        clip.truncate() / clip.w
+    }
+
+    #[test]
+    fn turning_changes_where_it_looks() {
+        let mut camera = Camera::new(DVec3::ZERO);
+        assert_near(camera.forward(), Vec3::NEG_Z);
+        assert_near(camera.right(), Vec3::X);
+
+        camera.turn(FRAC_PI_2, 0.0);
+        assert_near(camera.forward(), Vec3::X);
+        assert_near(camera.right(), Vec3::Z);
+
+        camera.turn(0.0, 10.0);
+        assert_eq!(camera.pitch, MAX_PITCH);
+        assert!(camera.forward().y > 0.99);
+        camera.turn(0.0, -20.0);
+        assert_eq!(camera.pitch, -MAX_PITCH);
+    }
+
+    #[test]
+    fn points_ahead_are_in_view() {
+        let mut camera = Camera::new(DVec3::new(1000.5, 64.25, -2000.75));
+        camera.resize(1600, 900);
+        assert_eq!(camera.aspect, 16.0 / 9.0);
+
+        let ahead = project(&camera, camera.position + DVec3::new(0.0, 0.0, -10.0));
+        assert_near(ahead * Vec3::new(1.0, 1.0, 0.0), Vec3::ZERO);
+        assert!(ahead.z > 0.0 && ahead.z < 1.0);
+
+        let near = project(&camera, camera.position + DVec3::new(0.0, 0.0, -0.1));
+        let far = project(&camera, camera.position + DVec3::new(0.0, 0.0, -1000.0));
+        assert!((near.z - 0.0).abs() < 1e-4 && (far.z - 1.0).abs() < 1e-4);
+
+        let right = project(&camera, camera.position + DVec3::new(1.0, 1.0, -10.0));
+        assert!(right.x > 0.0 && right.y > 0.0);
+
+        let behind = camera.uniform();
+        let clip =
+            Mat4::from_cols_array_2d(&behind.view_projection) * Vec4::new(0.5, 0.25, 10.0, 1.0);
+        assert!(clip.w < 0.0);
+    }
+
+    #[test]
+    fn actions_move_along_the_ground() {
+        let mut camera = Camera::new(DVec3::ZERO);
+        camera.turn(0.0, 1.0);
+        let controls = CameraControls::new();
+        let mut input = Input::new();
+        input.press(Binding::Key(VirtualKeyCode::W));
+        controls.update(&mut camera, &input, 0.5);
+        assert_near(camera.position.as_vec3(), Vec3::new(0.0, 0.0, -5.0));
+
+        input.release(Binding::Key(VirtualKeyCode::W));
+        input.press(Binding::Key(VirtualKeyCode::D));
+        input.press(Binding::Key(VirtualKeyCode::Space));
+        controls.update(&mut camera, &input, 1.0);
+        let diagonal = 10.0 / 2f32.sqrt();
+        assert_near(
+            camera.position.as_vec3(),
+            Vec3::new(diagonal, diagonal, -5.0),
+        );
+
+        input.release_all();
+        controls.update(&mut camera, &input, 1.0);
+        assert_near(
+            camera.position.as_vec3(),
+            Vec3::new(diagonal, diagonal, -5.0),
+        );
+    }
+
+    #[test]
+    fn mouse_turns_the_camera() {
+        let mut camera = Camera::new(DVec3::ZERO);
+        let mut controls = CameraControls::new();
+        controls.mouse_moved(100.0, 50.0);
+        controls.mouse_moved(100.0, 50.0);
+        controls.look(&mut camera);
+        assert!((camera.yaw - 0.6).abs() < 1e-6);
+        assert!((camera.pitch + 0.3).abs() < 1e-6);
+        controls.look(&mut camera);
+        assert!((camera.yaw - 0.6).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,216 @@
+use std::time::{Duration, Instant};
+
+use tracing::warn;
+
+/// Runs the simulation in ticks of a fixed length, however fast frames are
+/// drawn.
+///
+/// # Notes
+///
+/// The time between frames is added to an accumulator, and a tick is run for
+/// each tick length in it.  What's left over is how far the next tick is
+/// along, which rendering uses to blend between the last two ticks (see
+/// [Ticks::alpha]).
+///
+/// If the ticks fall too far behind, such as after a long pause in a
+/// debugger or when ticks take longer to run than they cover, only
+/// [TickClock::max_ticks] are run in a frame and the rest of the time is
+/// dropped.  Otherwise each frame would have more ticks to catch up on than
+/// the last.
+///
+/// While paused, time doesn't build up, and ticks are only run one at a
+/// time with [TickClock::step].
+///
+#[derive(Clone, Debug)]
+pub struct TickClock {
+    /// The length of a tick.
+    tick_length: Duration,
+
+    /// The most ticks run in one frame.
+    max_ticks: u32,
+
+    /// The time not yet run in ticks.
+    accumulator: Duration,
+
+    /// When the clock was last updated, if it has been.
+    last_update: Option<Instant>,
+
+    /// Whether time is stopped.
+    paused: bool,
+
+    /// The ticks asked for with [TickClock::step] but not yet run.
+    steps: u32,
+
+    /// The number of ticks run so far.
+    tick: u64,
+}
+
+/// The ticks to run in a frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ticks {
+    /// How many ticks to run.
+    pub count: u32,
+
+    /// How far (from 0 to 1) the time is between the last tick and the next,
+    /// for blending what is drawn between the last two ticks.
+    pub alpha: f32,
+}
+
+impl TickClock {
+    /// Creates a clock with no time built up.
+    ///
+    /// # Parameters
+    ///
+    /// * `rate` - The number of ticks each second.
+    /// * `max_ticks` - The most ticks run in one frame.
+    ///
+    pub fn new(rate: u32, max_ticks: u32) -> TickClock {
+        TickClock {
+            tick_length: Duration::from_secs(1) / rate.max(1),
+            max_ticks: max_ticks.max(1),
+            accumulator: Duration::ZERO,
+            last_update: None,
+            paused: false,
+            steps: 0,
+            tick: 0,
+        }
+    }
+
+    /// Returns the length of a tick.
+    pub fn tick_length(&self) -> Duration {
+        self.tick_length
+    }
+
+    /// Returns the length of a tick, in seconds.
+    pub fn tick_seconds(&self) -> f32 {
+        self.tick_length.as_secs_f32()
+    }
+
+    /// Returns the most ticks run in one frame.
+    pub fn max_ticks(&self) -> u32 {
+        self.max_ticks
+    }
+
+    /// Returns the number of ticks run so far.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Returns true if time is stopped.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Stops or restarts time.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.steps = 0;
+    }
+
+    /// Runs one more tick while paused.  Does nothing while running.
+    pub fn step(&mut self) {
+        if self.paused {
+            self.steps += 1;
+        }
+    }
+
+    /// Works out the ticks to run in a frame from the time since the last
+    /// update.  The first update runs no ticks.
+    pub fn update(&mut self, now: Instant) -> Ticks {
+        let elapsed = self
+            .last_update
+            .map_or(Duration::ZERO, |last| now.saturating_duration_since(last));
+        self.last_update = Some(now);
+        self.advance(elapsed)
+    }
+
+    /// Works out the ticks to run in a frame after some time has passed.
+    pub fn advance(&mut self, elapsed: Duration) -> Ticks {
+        let count = if self.paused {
+            std::mem::take(&mut self.steps).min(self.max_ticks)
+        } else {
+            self.accumulator += elapsed;
+            let due = (self.accumulator.as_nanos() / self.tick_length.as_nanos()) as u32;
+            if due > self.max_ticks {
+                warn!(
+                    "Ticks are {} behind, skipping {}",
+                    due,
+                    due - self.max_ticks
+                );
+                self.accumulator = Duration::from_nanos(
+                    (self.accumulator.as_nanos() % self.tick_length.as_nanos()) as u64,
+                );
+                self.max_ticks
+            } else {
+                self.accumulator -= self.tick_length * due;
+                due
+            }
+        };
+        self.tick += count as u64;
+
+        Ticks {
+            count,
+            alpha: self.accumulator.as_secs_f32() / self.tick_length.as_secs_f32(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_builds_up_into_ticks() {
+        let mut clock = TickClock::new(20, 5);
+        assert_eq!(clock.tick_length(), Duration::from_millis(50));
+
+        let ticks = clock.advance(Duration::from_millis(30));
+        assert_eq!(ticks.count, 0);
+        assert!((ticks.alpha - 0.6).abs() < 1e-6);
+
+        let ticks = clock.advance(Duration::from_millis(95));
+        assert_eq!(ticks.count, 2);
+        assert!((ticks.alpha - 0.5).abs() < 1e-6);
+        assert_eq!(clock.tick(), 2);
+    }
+
+    #[test]
+    fn falling_behind_drops_time() {
+        let mut clock = TickClock::new(20, 5);
+        let ticks = clock.advance(Duration::from_millis(10_010));
+        assert_eq!(ticks.count, 5);
+        assert!((ticks.alpha - 0.2).abs() < 1e-6);
+
+        let ticks = clock.advance(Duration::from_millis(10));
+        assert_eq!(ticks.count, 0);
+        assert_eq!(clock.tick(), 5);
+    }
+
+    #[test]
+    fn paused_clocks_only_step() {
+        let mut clock = TickClock::new(20, 5);
+        clock.advance(Duration::from_millis(25));
+        clock.step();
+        clock.set_paused(true);
+        let ticks = clock.advance(Duration::from_secs(1));
+        assert_eq!(ticks.count, 0);
+        assert!((ticks.alpha - 0.5).abs() < 1e-6);
+
+        clock.step();
+        clock.step();
+        assert_eq!(clock.advance(Duration::ZERO).count, 2);
+        assert_eq!(clock.advance(Duration::ZERO).count, 0);
+
+        clock.set_paused(false);
+        assert_eq!(clock.advance(Duration::from_millis(25)).count, 1);
+        assert_eq!(clock.tick(), 3);
+    }
+
+    #[test]
+    fn first_update_runs_nothing() {
+        let mut clock = TickClock::new(60, 5);
+        let start = Instant::now();
+        assert_eq!(clock.update(start).count, 0);
+        assert_eq!(clock.update(start + Duration::from_millis(40)).count, 2);
+    }
+}
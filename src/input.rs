@@ -0,0 +1,732 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use knuffel::{parse, Decode};
+use tracing::{error, info, warn};
+use winit::event::{ElementState, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent};
+
+/// Something the player can do by pressing a key or mouse button.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    GrabCursor,
+    Quit,
+    ToggleFly,
+    PauseTicks,
+    StepTick,
+}
+
+/// A key or mouse button that an action is bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+}
+
+/// Keeps track of which actions are pressed, from the key and mouse button
+/// bindings of each action.
+///
+/// # Notes
+///
+/// Bindings are read from a settings file (see [Input::load]) like this:
+///
+/// ```kdl
+/// bindings {
+///     bind "move-forward" key="W"
+///     bind "move-forward" key="Up"
+///     bind "grab-cursor" mouse="Left"
+/// }
+/// ```
+///
+/// An action can have several bindings, and a `bind` entry with no `key` or
+/// `mouse` leaves it with none.  Actions missing from the file keep their
+/// default bindings (see [Action::default_bindings]), and an action listed in
+/// the file loses all of its default bindings.  Keys are named as
+/// in winit's `VirtualKeyCode`, and mouse buttons are `Left`, `Right`,
+/// `Middle` or a number.
+///
+/// The state of each key and mouse button is updated as window events come
+/// in (see [Input::handle_event]).  An action is pressed or released in a
+/// frame if one of its bindings went down or up since the last call to
+/// [Input::end_frame], and it is held while any of its bindings are down.
+///
+#[derive(Debug)]
+pub struct Input {
+    /// The bindings of each action.
+    bindings: HashMap<Action, Vec<Binding>>,
+
+    /// The file the bindings are saved to when they change, if any.
+    path: Option<PathBuf>,
+
+    /// The keys and mouse buttons that are down.
+    held: HashSet<Binding>,
+
+    /// The keys and mouse buttons that went down this frame.
+    pressed: HashSet<Binding>,
+
+    /// The keys and mouse buttons that went up this frame.
+    released: HashSet<Binding>,
+
+    /// The action that the next key or mouse button pressed is bound to.
+    capturing: Option<Action>,
+}
+
+/// The sections of a settings file.
+#[derive(Decode, Debug)]
+enum KdlSettings {
+    Bindings(KdlBindings),
+}
+
+/// The `bindings` section of a settings file.
+#[derive(Decode, Debug)]
+struct KdlBindings {
+    #[knuffel(children(name = "bind"))]
+    binds: Vec<KdlBind>,
+}
+
+/// A `bind` entry in the `bindings` section: `bind "<action>" key="<key>"`,
+/// `bind "<action>" mouse="<button>"` or `bind "<action>"` for no binding.
+#[derive(Decode, Debug)]
+struct KdlBind {
+    #[knuffel(argument)]
+    action: String,
+
+    #[knuffel(property)]
+    key: Option<String>,
+
+    #[knuffel(property)]
+    mouse: Option<String>,
+}
+
+/// Every key, in the order winit declares them, so each key's name can be
+/// found for the settings file.
+const KEYS: &[VirtualKeyCode] = {
+    use VirtualKeyCode::*;
+    &[
+        Key1,
+        Key2,
+        Key3,
+        Key4,
+        Key5,
+        Key6,
+        Key7,
+        Key8,
+        Key9,
+        Key0,
+        A,
+        B,
+        C,
+        D,
+        E,
+        F,
+        G,
+        H,
+        I,
+        J,
+        K,
+        L,
+        M,
+        N,
+        O,
+        P,
+        Q,
+        R,
+        S,
+        T,
+        U,
+        V,
+        W,
+        X,
+        Y,
+        Z,
+        Escape,
+        F1,
+        F2,
+        F3,
+        F4,
+        F5,
+        F6,
+        F7,
+        F8,
+        F9,
+        F10,
+        F11,
+        F12,
+        F13,
+        F14,
+        F15,
+        F16,
+        F17,
+        F18,
+        F19,
+        F20,
+        F21,
+        F22,
+        F23,
+        F24,
+        Snapshot,
+        Scroll,
+        Pause,
+        Insert,
+        Home,
+        Delete,
+        End,
+        PageDown,
+        PageUp,
+        Left,
+        Up,
+        Right,
+        Down,
+        Back,
+        Return,
+        Space,
+        Compose,
+        Caret,
+        Numlock,
+        Numpad0,
+        Numpad1,
+        Numpad2,
+        Numpad3,
+        Numpad4,
+        Numpad5,
+        Numpad6,
+        Numpad7,
+        Numpad8,
+        Numpad9,
+        NumpadAdd,
+        NumpadDivide,
+        NumpadDecimal,
+        NumpadComma,
+        NumpadEnter,
+        NumpadEquals,
+        NumpadMultiply,
+        NumpadSubtract,
+        AbntC1,
+        AbntC2,
+        Apostrophe,
+        Apps,
+        Asterisk,
+        At,
+        Ax,
+        Backslash,
+        Calculator,
+        Capital,
+        Colon,
+        Comma,
+        Convert,
+        Equals,
+        Grave,
+        Kana,
+        Kanji,
+        LAlt,
+        LBracket,
+        LControl,
+        LShift,
+        LWin,
+        Mail,
+        MediaSelect,
+        MediaStop,
+        Minus,
+        Mute,
+        MyComputer,
+        NavigateForward,
+        NavigateBackward,
+        NextTrack,
+        NoConvert,
+        OEM102,
+        Period,
+        PlayPause,
+        Plus,
+        Power,
+        PrevTrack,
+        RAlt,
+        RBracket,
+        RControl,
+        RShift,
+        RWin,
+        Semicolon,
+        Slash,
+        Sleep,
+        Stop,
+        Sysrq,
+        Tab,
+        Underline,
+        Unlabeled,
+        VolumeDown,
+        VolumeUp,
+        Wake,
+        WebBack,
+        WebFavorites,
+        WebForward,
+        WebHome,
+        WebRefresh,
+        WebSearch,
+        WebStop,
+        Yen,
+        Copy,
+        Paste,
+        Cut,
+    ]
+};
+
+impl Action {
+    /// Every action, in the order they are saved in.
+    pub const ALL: [Action; 11] = [
+        Action::MoveForward,
+        Action::MoveBack,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::GrabCursor,
+        Action::Quit,
+        Action::ToggleFly,
+        Action::PauseTicks,
+        Action::StepTick,
+    ];
+
+    /// Returns the name of the action in settings files.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveForward => "move-forward",
+            Action::MoveBack => "move-back",
+            Action::MoveLeft => "move-left",
+            Action::MoveRight => "move-right",
+            Action::MoveUp => "move-up",
+            Action::MoveDown => "move-down",
+            Action::GrabCursor => "grab-cursor",
+            Action::Quit => "quit",
+            Action::ToggleFly => "toggle-fly",
+            Action::PauseTicks => "pause-ticks",
+            Action::StepTick => "step-tick",
+        }
+    }
+
+    /// Returns the action with a name in settings files.
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+
+    /// Returns the bindings an action has when the settings file doesn't
+    /// list it.
+    pub fn default_bindings(self) -> &'static [Binding] {
+        use Binding::*;
+        match self {
+            Action::MoveForward => &[Key(VirtualKeyCode::W)],
+            Action::MoveBack => &[Key(VirtualKeyCode::S)],
+            Action::MoveLeft => &[Key(VirtualKeyCode::A)],
+            Action::MoveRight => &[Key(VirtualKeyCode::D)],
+            Action::MoveUp => &[Key(VirtualKeyCode::Space)],
+            Action::MoveDown => &[Key(VirtualKeyCode::LShift)],
+            Action::GrabCursor => &[Mouse(MouseButton::Left)],
+            Action::Quit => &[Key(VirtualKeyCode::Escape)],
+            Action::ToggleFly => &[Key(VirtualKeyCode::F)],
+            Action::PauseTicks => &[Key(VirtualKeyCode::F9)],
+            Action::StepTick => &[Key(VirtualKeyCode::F10)],
+        }
+    }
+}
+
+impl Binding {
+    /// Reads a binding from the `key` or `mouse` property of a `bind` entry.
+    fn parse(key: Option<&str>, mouse: Option<&str>) -> Option<Binding> {
+        match (key, mouse) {
+            (Some(key), None) => KEYS
+                .iter()
+                .find(|k| format!("{k:?}") == key)
+                .map(|&k| Binding::Key(k)),
+            (None, Some(mouse)) => match mouse {
+                "Left" => Some(Binding::Mouse(MouseButton::Left)),
+                "Right" => Some(Binding::Mouse(MouseButton::Right)),
+                "Middle" => Some(Binding::Mouse(MouseButton::Middle)),
+                other => other
+                    .parse()
+                    .ok()
+                    .map(|n| Binding::Mouse(MouseButton::Other(n))),
+            },
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Binding {
+    /// Writes the binding as the properties of a `bind` entry.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "key=\"{key:?}\""),
+            Binding::Mouse(MouseButton::Other(n)) => write!(f, "mouse=\"{n}\""),
+            Binding::Mouse(button) => write!(f, "mouse=\"{button:?}\""),
+        }
+    }
+}
+
+impl Input {
+    /// Creates the input state with every action at its default bindings.
+    pub fn new() -> Input {
+        Input {
+            bindings: Action::ALL
+                .into_iter()
+                .map(|action| (action, action.default_bindings().to_vec()))
+                .collect(),
+            path: None,
+            held: HashSet::new(),
+            pressed: HashSet::new(),
+            released: HashSet::new(),
+            capturing: None,
+        }
+    }
+
+    /// Reads the bindings from a settings file, which they are saved back to
+    /// when they are changed.
+    ///
+    /// # Notes
+    ///
+    /// If the file doesn't exist, every action keeps its default bindings.
+    /// If it can't be read, the errors are logged and the defaults are used.
+    /// Unknown actions, keys and mouse buttons are skipped with a warning.
+    ///
+    pub fn load<P>(path: P) -> Input
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let mut input = Input::new();
+        match fs::read_to_string(path) {
+            Ok(text) => {
+                info!("Reading bindings from {path:?}");
+                input.read(&path.to_string_lossy(), &text);
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => error!("Unable to read settings {path:?}: {err}"),
+        }
+        input.path = Some(path.to_path_buf());
+        input
+    }
+
+    /// Reads bindings from the text of a settings file, replacing the
+    /// bindings of the actions it lists.
+    fn read(&mut self, file_name: &str, text: &str) {
+        let settings = match parse::<Vec<KdlSettings>>(file_name, text) {
+            Ok(settings) => settings,
+            Err(err) => {
+                error!("Unable to parse settings {file_name:?}: {err}");
+                return;
+            }
+        };
+
+        let mut listed = HashSet::new();
+        for KdlSettings::Bindings(bindings) in settings {
+            for bind in bindings.binds {
+                let Some(action) = Action::from_name(&bind.action) else {
+                    warn!("Unknown action {:?} in {file_name:?}", bind.action);
+                    continue;
+                };
+                // An entry with no key or mouse button leaves the action
+                // unbound.
+                if bind.key.is_none() && bind.mouse.is_none() {
+                    if listed.insert(action) {
+                        self.bindings.entry(action).or_default().clear();
+                    }
+                    continue;
+                }
+                let Some(binding) = Binding::parse(bind.key.as_deref(), bind.mouse.as_deref())
+                else {
+                    warn!(
+                        "Bad binding for {:?} in {file_name:?}: key={:?} mouse={:?}",
+                        bind.action, bind.key, bind.mouse
+                    );
+                    continue;
+                };
+                let bindings = self.bindings.entry(action).or_default();
+                if listed.insert(action) {
+                    bindings.clear();
+                }
+                bindings.push(binding);
+            }
+        }
+    }
+
+    /// Writes every action's bindings to the settings file they were loaded
+    /// from.  Does nothing if they weren't loaded from a file.
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut text = String::from(
+            "// Key and mouse bindings.  Keys are named as in winit's VirtualKeyCode, and mouse\n\
+             // buttons are Left, Right, Middle or a number.\n\
+             bindings {\n",
+        );
+        for action in Action::ALL {
+            let bindings = self.bindings(action);
+            if bindings.is_empty() {
+                text += &format!("    bind \"{}\"\n", action.name());
+            }
+            for binding in bindings {
+                text += &format!("    bind \"{}\" {binding}\n", action.name());
+            }
+        }
+        text += "}\n";
+        fs::write(path, text)
+    }
+
+    /// Returns the bindings of an action.
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Replaces the bindings of an action and saves them.
+    pub fn rebind(&mut self, action: Action, bindings: Vec<Binding>) -> io::Result<()> {
+        self.bindings.insert(action, bindings);
+        self.save()
+    }
+
+    /// Binds an action to the next key or mouse button that is pressed, in
+    /// place of its other bindings.  That press doesn't count towards any
+    /// action.
+    pub fn capture(&mut self, action: Action) {
+        self.capturing = Some(action);
+    }
+
+    /// Returns the action waiting for a key or mouse button to be bound to.
+    pub fn capturing(&self) -> Option<Action> {
+        self.capturing
+    }
+
+    /// Updates the state of the keys and mouse buttons from a window event.
+    ///
+    /// # Returns
+    ///
+    /// True if the event was a key or mouse button going up or down.
+    ///
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        let (binding, state) = match *event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => (Binding::Key(key), state),
+            WindowEvent::MouseInput { state, button, .. } => (Binding::Mouse(button), state),
+            _ => return false,
+        };
+
+        match state {
+            ElementState::Pressed => self.press(binding),
+            ElementState::Released => self.release(binding),
+        }
+        true
+    }
+
+    /// Records a key or mouse button going down, or binds it to the action
+    /// being captured.
+    pub fn press(&mut self, binding: Binding) {
+        if let Some(action) = self.capturing.take() {
+            info!("Binding {} to {binding}", action.name());
+            if let Err(err) = self.rebind(action, vec![binding]) {
+                error!("Unable to save bindings: {err}");
+            }
+        } else if self.held.insert(binding) {
+            self.pressed.insert(binding);
+        }
+    }
+
+    /// Records a key or mouse button going up.
+    pub fn release(&mut self, binding: Binding) {
+        if self.held.remove(&binding) {
+            self.released.insert(binding);
+        }
+    }
+
+    /// Returns true if any binding of an action went down this frame.
+    pub fn pressed(&self, action: Action) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|binding| self.pressed.contains(binding))
+    }
+
+    /// Returns true if any binding of an action is down.
+    pub fn held(&self, action: Action) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|binding| self.held.contains(binding))
+    }
+
+    /// Returns true if any binding of an action went up this frame.
+    pub fn released(&self, action: Action) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|binding| self.released.contains(binding))
+    }
+
+    /// Starts a new frame, forgetting what was pressed and released.
+    pub fn end_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+    }
+
+    /// Lets go of every key and mouse button, such as when the window loses
+    /// focus and the releases would be missed.
+    pub fn release_all(&mut self) {
+        self.released.extend(self.held.drain());
+    }
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Input::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::event::{DeviceId, ModifiersState};
+
+    use super::*;
+    use crate::world::TestDir;
+
+    #[allow(deprecated)]
+    fn key(key: VirtualKeyCode, state: ElementState) -> WindowEvent<'static> {
+        WindowEvent::KeyboardInput {
+            device_id: unsafe { DeviceId::dummy() },
+            input: KeyboardInput {
+                scancode: 0,
+                state,
+                virtual_keycode: Some(key),
+                modifiers: ModifiersState::empty(),
+            },
+            is_synthetic: false,
+        }
+    }
+
+    #[allow(deprecated)]
+    fn mouse(button: MouseButton, state: ElementState) -> WindowEvent<'static> {
+        WindowEvent::MouseInput {
+            device_id: unsafe { DeviceId::dummy() },
+            state,
+            button,
+            modifiers: ModifiersState::empty(),
+        }
+    }
+
+    #[test]
+    fn actions_are_named() {
+        for action in Action::ALL {
+            assert_eq!(Action::from_name(action.name()), Some(action));
+        }
+        assert_eq!(Action::from_name("fly"), None);
+    }
+
+    #[test]
+    fn settings_replace_default_bindings() {
+        let mut input = Input::new();
+        input.read(
+            "settings.kdl",
+            r#"
+            bindings {
+                bind "move-forward" key="Up"
+                bind "move-forward" mouse="4"
+                bind "quit" key="Q"
+                bind "fly" key="F"
+                bind "move-back" key="NotAKey"
+            }
+            "#,
+        );
+        assert_eq!(
+            input.bindings(Action::MoveForward),
+            [
+                Binding::Key(VirtualKeyCode::Up),
+                Binding::Mouse(MouseButton::Other(4))
+            ]
+        );
+        assert_eq!(
+            input.bindings(Action::Quit),
+            [Binding::Key(VirtualKeyCode::Q)]
+        );
+        assert_eq!(
+            input.bindings(Action::MoveBack),
+            Action::MoveBack.default_bindings()
+        );
+        assert_eq!(
+            input.bindings(Action::GrabCursor),
+            [Binding::Mouse(MouseButton::Left)]
+        );
+    }
+
+    #[test]
+    fn pressed_held_and_released_last_a_frame() {
+        let mut input = Input::new();
+        input.handle_event(&key(VirtualKeyCode::W, ElementState::Pressed));
+        assert!(input.pressed(Action::MoveForward) && input.held(Action::MoveForward));
+        assert!(!input.held(Action::MoveBack));
+        input.end_frame();
+
+        // Key repeats don't press the action again.
+        input.handle_event(&key(VirtualKeyCode::W, ElementState::Pressed));
+        assert!(!input.pressed(Action::MoveForward) && input.held(Action::MoveForward));
+        input.end_frame();
+
+        input.handle_event(&key(VirtualKeyCode::W, ElementState::Released));
+        assert!(input.released(Action::MoveForward) && !input.held(Action::MoveForward));
+        input.end_frame();
+        assert!(!input.released(Action::MoveForward));
+
+        input.handle_event(&mouse(MouseButton::Left, ElementState::Pressed));
+        assert!(input.pressed(Action::GrabCursor));
+        input.release_all();
+        assert!(input.released(Action::GrabCursor) && !input.held(Action::GrabCursor));
+    }
+
+    #[test]
+    fn captured_bindings_are_saved_and_loaded() {
+        let dir = TestDir::new("input");
+        fs::create_dir_all(dir.path()).unwrap();
+        let path = dir.path().join("input.kdl");
+
+        let mut input = Input::load(&path);
+        input.capture(Action::Quit);
+        input.handle_event(&mouse(MouseButton::Right, ElementState::Pressed));
+        assert_eq!(input.capturing(), None);
+        assert!(!input.pressed(Action::Quit));
+        assert_eq!(
+            input.bindings(Action::Quit),
+            [Binding::Mouse(MouseButton::Right)]
+        );
+
+        // Every key can be saved, and so can an action with no bindings.
+        input.capture(Action::MoveUp);
+        input.handle_event(&key(VirtualKeyCode::Capital, ElementState::Pressed));
+        input.rebind(Action::ToggleFly, vec![]).unwrap();
+
+        let loaded = Input::load(&path);
+        assert_eq!(
+            loaded.bindings(Action::MoveUp),
+            [Binding::Key(VirtualKeyCode::Capital)]
+        );
+        assert!(loaded.bindings(Action::ToggleFly).is_empty());
+        for action in Action::ALL {
+            assert_eq!(loaded.bindings(action), input.bindings(action));
+        }
+    }
+
+    #[test]
+    fn every_key_has_a_name() {
+        for (index, &key) in KEYS.iter().enumerate() {
+            assert_eq!(key as usize, index);
+            let name = format!("{key:?}");
+            assert_eq!(Binding::parse(Some(&name), None), Some(Binding::Key(key)));
+        }
+        assert_eq!(KEYS.last(), Some(&VirtualKeyCode::Cut));
+    }
+}
@@ -0,0 +1,5 @@
+pub mod camera;
+pub mod clock;
+pub mod input;
+pub mod resources;
+pub mod world;
@@ -1,24 +1,58 @@
-mod resources;
+use std::{collections::HashMap, env::set_var, path::Path, sync::Arc, time::Instant};
 
-use std::env::set_var;
-
-use bytemuck::{Pod, Zeroable};
 use color_eyre::{
     eyre::{Context, ContextCompat},
     Report,
 };
-use gfx::{Buffer, GfxError, RenderPipeline, Screen};
-use tracing::{error, info};
+use delve::{
+    camera::{Camera, CameraControls},
+    clock::TickClock,
+    input::{Action, Input},
+    resources::{self, RenderMaterial, TileBackend},
+    world::{
+        BlockPos, ChunkOrigin, ChunkPos, ChunkStreamer, FeaturePlacer, GpuChunkMesh, Player,
+        RegionStore, TerrainGenerator, TerrainVertex,
+    },
+};
+use gfx::{ColourAttachment, GfxError, RenderPassDesc, RenderTarget, Screen, DEPTH_FORMAT};
+use glam::DVec3;
+use tracing::{debug, error, info};
 use tracing_subscriber::EnvFilter;
-use wgpu::{include_wgsl, Color, SurfaceError};
-use wgpu_macros::VertexLayout;
+use wgpu::{Color, SurfaceError};
 use winit::{
     dpi::PhysicalSize,
-    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event::{DeviceEvent, Event, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
-    window::WindowBuilder,
+    window::{CursorGrabMode, Window, WindowBuilder},
 };
 
+/// The seed of the world when none is given on the command line.
+const DEFAULT_SEED: u64 = 1;
+
+/// The directory that worlds are saved in, one directory for each seed.
+const SAVE_DIR: &str = "saves";
+
+/// The file holding the player's settings, such as key bindings.
+const SETTINGS_FILE: &str = "settings.kdl";
+
+/// The distance (in chunks) around the player within which chunks are loaded.
+const RENDER_DISTANCE: u32 = 4;
+
+/// The height (in blocks) of the camera above the player's feet.
+const EYE_HEIGHT: f64 = 1.6;
+
+/// The number of ticks each second.  Movement and other simulation happen in
+/// ticks, and frames blend between the last two.
+const TICK_RATE: u32 = 20;
+
+/// The most ticks run in one frame.  If the ticks fall further behind than
+/// this, the time is dropped rather than caught up on.
+const MAX_TICKS_PER_FRAME: u32 = 10;
+
+/// The most chunk meshes uploaded to the GPU each frame.  The rest wait for
+/// later frames, so a burst of finished chunks doesn't make a frame stutter.
+const MESH_UPLOADS_PER_FRAME: usize = 4;
+
 #[tokio::main]
 async fn main() -> Result<(), Report> {
     //
@@ -42,7 +76,7 @@ async fn main() -> Result<(), Report> {
     // Load start up resources
     //
 
-    let _database = resources::read_kdl("core.kdl").context("loading core resources")?;
+    let database = Arc::new(resources::read_kdl("core.kdl").context("loading core resources")?);
 
     //
     // Set up window
@@ -62,27 +96,83 @@ async fn main() -> Result<(), Report> {
 
     let mut screen = Screen::new(&window, window_size.width, window_size.height).await?;
 
-    // Load the shader module for our render pipeline.
+    // Upload the tiles to the GPU.
     //
-    // This is the shader that will be used to render our frames. It is
-    // written in the WebGPU Shader Language (WGSL), which is a new language
-    // that is designed to be easy to read and write.
+    // By default, each texture atlas is uploaded as it is.  Passing
+    // `--texture-array` on the command line copies every tile into a layer of
+    // a texture array instead.  Either way, the mipmaps are generated tile by
+    // tile so the colours of neighbouring tiles do not bleed into each other.
+    let tile_backend = if std::env::args().any(|arg| arg == "--texture-array") {
+        TileBackend::Array
+    } else {
+        TileBackend::Atlas
+    };
+    let tile_textures = database.create_tile_textures(&screen, tile_backend)?;
+
+    // Create the tile table, which tells the shader where each tile is found
+    // in the textures.  Terrain vertices only carry the tile number.
+    let tile_table = database.create_tile_table(&screen, tile_backend);
+
+    // Create the camera buffer, which holds the view and projection of the
+    // camera and is written again every frame.  The depth buffer is the size
+    // of the window, so it is created again whenever the window is resized.
+    let mut camera = Camera::new(DVec3::ZERO);
+    camera.resize(window_size.width, window_size.height);
+    let camera_buffer = screen.create_uniform_buffer("Camera", &camera.uniform());
+    let mut depth_buffer = screen.create_render_target("Depth buffer", DEPTH_FORMAT);
+
+    // Register the materials declared in the KDL files.
     //
-    // The shader is loaded from a file using the `include_wgsl!` macro.
-    // This macro will compile the shader at compile time, and embed the
-    // compiled shader into the binary. This means that we don't need to
-    // worry about loading the shader at runtime.
-    let quad_material = screen
-        .create_material(include_wgsl!("shader.wgsl"), "vs_main", "fs_main")
-        .add_buffer_layout(Vertex::LAYOUT);
-
-    let render_pipeline = screen
-        .create_render_pipeline("triangle render")
-        .shader(&quad_material)
-        .build(&screen)?;
-
-    let quad_vertices = screen.create_vertex_buffer("Quad vertices", QUAD_VERTICES);
-    let quad_indices = screen.create_index_buffer("Quad indices", QUAD_INDICES);
+    // Each material names a WGSL shader in the data directory, its entry
+    // points, how it is blended and culled, and the textures and buffers it
+    // uses.  The screen keeps the pipelines in a cache so that they can be
+    // rebuilt when the surface changes.
+    let materials = database.register_materials(
+        &mut screen,
+        &tile_textures,
+        &[("tiles", &tile_table), ("camera", &camera_buffer)],
+        &[TerrainVertex::LAYOUT, ChunkOrigin::LAYOUT],
+    )?;
+
+    // Stream in the world around the camera, which starts on the ground at
+    // the origin.  Chunks are loaded, generated if they haven't been saved,
+    // lit and meshed on worker threads, and the meshes are uploaded a few at a
+    // time as they are finished.
+    //
+    // The seed of the world can be given on the command line with `--seed
+    // <number>`.  The same seed always makes the same world.
+    let seed = seed_argument().unwrap_or(DEFAULT_SEED);
+    info!("World seed: {seed}");
+    let generator =
+        TerrainGenerator::new(seed, &database).context("creating the terrain generator")?;
+    let save_dir = Path::new(SAVE_DIR).join(format!("seed-{seed}"));
+    let regions = RegionStore::open(&save_dir).context("opening the world save")?;
+    let features =
+        FeaturePlacer::load(&save_dir, &database).context("reading the saved features")?;
+    let mut player = Player::new(DVec3::new(0.5, generator.height(0, 0) as f64 + 1.0, 0.5));
+    camera.position = player.position + DVec3::Y * EYE_HEIGHT;
+    let mut streamer = ChunkStreamer::new(
+        database.clone(),
+        generator,
+        regions,
+        features,
+        RENDER_DISTANCE,
+    );
+    let mut chunk_meshes = HashMap::<ChunkPos, GpuChunkMesh>::new();
+
+    // The camera is turned with the mouse while the cursor is grabbed, which
+    // happens when the window is clicked.  Each tick the player walks, jumps
+    // and falls with the movement actions and the camera follows their eyes,
+    // or while flying the camera moves freely and the player follows it.
+    // Frames draw the camera between where it was at the last two ticks, so
+    // movement is smooth however fast frames are drawn.
+    let mut input = Input::load(SETTINGS_FILE);
+    let mut controls = CameraControls::new();
+    let mut clock = TickClock::new(TICK_RATE, MAX_TICKS_PER_FRAME);
+    let mut last_position = camera.position;
+    let mut flying = false;
+    let mut grabbed = false;
+    let mut closing = false;
 
     //
     // Main loop
@@ -92,18 +182,7 @@ async fn main() -> Result<(), Report> {
         *control_flow = ControlFlow::Poll;
         match event {
             Event::WindowEvent { window_id, event } if window_id == window.id() => match event {
-                WindowEvent::CloseRequested
-                | WindowEvent::KeyboardInput {
-                    input:
-                        KeyboardInput {
-                            state: ElementState::Pressed,
-                            virtual_keycode: Some(VirtualKeyCode::Escape),
-                            ..
-                        },
-                    ..
-                } => {
-                    *control_flow = ControlFlow::Exit;
-                }
+                WindowEvent::CloseRequested => closing = true,
 
                 WindowEvent::Resized(PhysicalSize { width, height })
                 | WindowEvent::ScaleFactorChanged {
@@ -111,13 +190,101 @@ async fn main() -> Result<(), Report> {
                     ..
                 } => {
                     screen.resize(width, height);
+                    camera.resize(width, height);
+                    depth_buffer = screen.create_render_target("Depth buffer", DEPTH_FORMAT);
+                }
+
+                WindowEvent::Focused(false) => {
+                    grabbed = grab_cursor(&window, false);
+                    input.release_all();
+                    controls.release();
                 }
 
-                _ => {}
+                event => {
+                    input.handle_event(&event);
+                }
             },
 
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta: (dx, dy) },
+                ..
+            } if grabbed => controls.mouse_moved(dx, dy),
+
+            Event::MainEventsCleared => {
+                if closing || input.pressed(Action::Quit) {
+                    if let Err(err) = streamer.save(&save_dir) {
+                        error!("Error saving the world: {err}");
+                    }
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
+                if !grabbed && input.pressed(Action::GrabCursor) {
+                    grabbed = grab_cursor(&window, true);
+                }
+
+                if input.pressed(Action::PauseTicks) {
+                    clock.set_paused(!clock.is_paused());
+                    info!(
+                        "Ticks {}",
+                        if clock.is_paused() {
+                            "paused"
+                        } else {
+                            "resumed"
+                        }
+                    );
+                }
+                if input.pressed(Action::StepTick) {
+                    clock.step();
+                }
+
+                if input.pressed(Action::ToggleFly) {
+                    flying = !flying;
+                    player.velocity = DVec3::ZERO;
+                    info!("Flying {}", if flying { "on" } else { "off" });
+                }
+
+                let ticks = clock.update(Instant::now());
+                let dt = clock.tick_seconds();
+                // The world is only locked while the player moves through it,
+                // so frames without ticks never wait on the workers.
+                let world = (ticks.count > 0 && !flying).then(|| streamer.world());
+                for _ in 0..ticks.count {
+                    last_position = camera.position;
+                    match &world {
+                        Some(world) => {
+                            let walk = CameraControls::heading(&camera, &input).as_dvec3();
+                            let jump = input.held(Action::MoveUp);
+                            player.update(world, &database, walk, jump, dt as f64);
+                            camera.position = player.position + DVec3::Y * EYE_HEIGHT;
+                        }
+                        None => {
+                            controls.update(&mut camera, &input, dt);
+                            player.position = camera.position - DVec3::Y * EYE_HEIGHT;
+                        }
+                    }
+                }
+                drop(world);
+                controls.look(&mut camera);
+                let mut view = camera.clone();
+                view.position = last_position.lerp(camera.position, ticks.alpha as f64);
+                screen.write_buffer(&camera_buffer, &[view.uniform()]);
+
+                let position = camera.position;
+                let centre = BlockPos::containing(position.x, position.y, position.z).chunk();
+                let streamed = streamer.update(centre, MESH_UPLOADS_PER_FRAME);
+                for pos in streamed.unloaded {
+                    chunk_meshes.remove(&pos);
+                }
+                for (pos, mesh) in &streamed.meshes {
+                    debug!("Chunk {:?}: {} vertices", pos, mesh.vertex_count());
+                    chunk_meshes.insert(*pos, mesh.upload(&screen, *pos));
+                }
+                input.end_frame();
+                window.request_redraw();
+            }
+
             Event::RedrawRequested(_) => {
-                match render(&screen, &render_pipeline, &quad_vertices, &quad_indices) {
+                match render(&screen, &materials, &depth_buffer, &chunk_meshes) {
                     Ok(_) => {}
                     Err(GfxError::BadRender(SurfaceError::Lost)) => screen.recreate(),
                     Err(GfxError::BadRender(SurfaceError::OutOfMemory)) => {
@@ -132,63 +299,77 @@ async fn main() -> Result<(), Report> {
     });
 }
 
-// TODO: Possible to use a macro to generate this?
-// vertex! Vertex {
-//     0 => position: Float32x3,
-//     1 => colour: Float32x3,
-// }
-
-#[repr(C)]
-#[derive(Copy, Clone, Zeroable, Pod, VertexLayout)]
-struct Vertex {
-    position: [f32; 3],
-    colour: [f32; 3],
+/// Returns the world seed given on the command line with `--seed <number>`.
+fn seed_argument() -> Option<u64> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--seed").skip(1);
+    args.next()?.parse().ok()
 }
 
-const QUAD_VERTICES: &[Vertex] = &[
-    Vertex {
-        position: [-0.8, -0.8, 0.0],
-        colour: [1.0, 0.0, 0.0],
-    },
-    Vertex {
-        position: [0.8, -0.8, 0.0],
-        colour: [1.0, 1.0, 0.0],
-    },
-    Vertex {
-        position: [0.8, 0.8, 0.0],
-        colour: [1.0, 0.0, 1.0],
-    },
-    Vertex {
-        position: [-0.8, 0.8, 0.0],
-        colour: [0.0, 1.0, 0.0],
-    },
-];
-
-const QUAD_INDICES: &[u16] = &[0, 1, 2, 2, 3, 0];
+/// Grabs or lets go of the mouse cursor.  While it is grabbed it is hidden
+/// and kept in the window, so moving the mouse only turns the camera.
+///
+/// # Returns
+///
+/// True if the cursor is now grabbed.
+///
+fn grab_cursor(window: &Window, grab: bool) -> bool {
+    let result = if grab {
+        window
+            .set_cursor_grab(CursorGrabMode::Locked)
+            .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined))
+    } else {
+        window.set_cursor_grab(CursorGrabMode::None)
+    };
+    match result {
+        Ok(()) => {
+            window.set_cursor_visible(!grab);
+            grab
+        }
+        Err(err) => {
+            error!("Error grabbing the cursor: {err}");
+            false
+        }
+    }
+}
 
+/// Draws the chunks, with every solid part before the translucent ones.
 fn render(
     screen: &Screen,
-    pipeline: &RenderPipeline,
-    quad_buffer: &Buffer,
-    quad_indices: &Buffer,
+    materials: &[RenderMaterial],
+    depth_buffer: &RenderTarget,
+    chunks: &HashMap<ChunkPos, GpuChunkMesh>,
 ) -> Result<(), GfxError> {
     let mut frame = screen.start_frame("Main frame")?;
 
     {
-        let mut render_pass = frame.create_render_pass(
-            "Main render pass",
-            Color {
+        let desc = RenderPassDesc::new("Main render pass")
+            .colour_attachment(ColourAttachment::surface().clear(Color {
                 r: 0.1,
                 g: 0.2,
                 b: 0.3,
                 a: 1.0,
-            },
-        );
+            }))
+            .depth_attachment(depth_buffer);
+        let mut render_pass = frame.begin_render_pass(&desc);
 
-        render_pass.set_pipeline(pipeline);
-        render_pass.set_vertex_buffer(0, quad_buffer, ..);
-        render_pass.set_index_buffer(quad_indices, ..);
-        render_pass.draw_indexed(quad_indices.all());
+        for translucent in [false, true] {
+            for chunk in chunks.values() {
+                render_pass.set_vertex_buffer(1, &chunk.origin, ..);
+                for part in chunk.parts.iter().filter(|p| p.translucent == translucent) {
+                    let material = &materials[part.material];
+                    render_pass.set_pipeline(screen.render_pipeline(material.pipeline));
+                    if let Some(bind_group) = &material.bind_group {
+                        render_pass.set_bind_group(0, bind_group);
+                    }
+                    if let Some(bind_group) = &material.buffer_bind_group {
+                        render_pass.set_bind_group(1, bind_group);
+                    }
+                    render_pass.set_vertex_buffer(0, &part.vertices, ..);
+                    render_pass.set_index_buffer(&part.indices, ..);
+                    render_pass.draw_indexed(part.indices.all());
+                }
+            }
+        }
     }
 
     frame.finish();
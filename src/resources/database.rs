@@ -1,7 +1,12 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, path::PathBuf};
 
+use gfx::PipelineState;
 use image::RgbaImage;
 
+use crate::world::{BlockId, Face, BLOCK_STEPS};
+
+use super::{BlockType, Placement};
+
 /// The database of all the resources used in the game.
 pub struct Database {
     /// All the images used in the game and associated tile size.
@@ -16,11 +21,40 @@ pub struct Database {
     /// A map of tile names to their index in the `tiles` vector.
     pub(crate) tile_names: HashMap<String, usize>,
 
-    /// All the block types used in the game.
+    /// All the materials used in the game.
+    pub(crate) materials: Vec<Material>,
+
+    /// A map of material names to their index in the `materials` vector.
+    pub(crate) material_names: HashMap<String, usize>,
+
+    /// Every state of every block type used in the game.  A block ID refers
+    /// to one of these.
     pub(crate) blocks: Vec<Block>,
 
-    /// A map of block names to their index in the `blocks` vector.
+    /// All the block types used in the game, which group the states in the
+    /// `blocks` vector.
+    pub(crate) block_types: Vec<BlockType>,
+
+    /// A map of block type names to their index in the `block_types` vector.
     pub(crate) block_names: HashMap<String, usize>,
+
+    /// All the block models used in the game.
+    pub(crate) models: Vec<Model>,
+
+    /// A map of model names to their index in the `models` vector.
+    pub(crate) model_names: HashMap<String, usize>,
+
+    /// The ores scattered through the ground when terrain is generated.
+    pub(crate) ores: Vec<Ore>,
+
+    /// The biomes that the world is split into when terrain is generated.
+    pub(crate) biomes: Vec<Biome>,
+
+    /// A map of biome names to their index in the `biomes` vector.
+    pub(crate) biome_names: HashMap<String, usize>,
+
+    /// The trees, boulders and plants placed on top of generated terrain.
+    pub(crate) features: Vec<Feature>,
 }
 
 /// A tile is a rectangular region of an image.
@@ -28,16 +62,80 @@ pub struct Database {
 /// The tile is specified by the image index and the UV coordinates of the tile
 /// in the image. The image index is the index of the image in the `images`
 /// vector of the `Database` which contains all the texture atlases.
+///
+/// When the tiles are uploaded as a texture array instead of atlases (see
+/// `TileBackend`), the tile is specified by its layer in that array.
 pub struct Tile {
     /// Image index where tile is found.
     pub(crate) image_index: usize,
 
+    /// Position (in pixels) of the top-left corner of the tile in the image.
+    pub(crate) origin: (u32, u32),
+
     /// UV coordinates of the tile in the image.
     pub(crate) uv0: (f32, f32),
     pub(crate) uv1: (f32, f32),
+
+    /// The layer of the tile in the tile texture array.  This is the same as
+    /// the index of the tile in the `tiles` vector.
+    pub(crate) layer: u32,
 }
 
-/// A block is a cube that is rendered in the game.
+/// A material is a shader and the pipeline state used to draw with it.
+///
+/// The material is specified by the source of its WGSL shader and the image
+/// indices of the textures it binds.  The image indices are the indices of the
+/// images in the `images` vector of the `Database`.  The material is turned
+/// into a GPU pipeline by `Database::register_materials`.
+pub struct Material {
+    /// Path of the shader file, relative to the data directory.
+    pub(crate) shader: PathBuf,
+
+    /// WGSL source of the shader.
+    pub(crate) source: String,
+
+    /// The name of the vertex entry point in the shader.
+    pub(crate) vertex_entry: String,
+
+    /// The name of the fragment entry point in the shader.
+    pub(crate) fragment_entry: String,
+
+    /// The blending, culling and depth state of the pipeline.
+    pub(crate) state: PipelineState,
+
+    /// The image indices of the textures bound to the shader, in order.
+    pub(crate) textures: Vec<usize>,
+
+    /// The names of the buffers bound to the shader, in order.
+    pub(crate) buffers: Vec<String>,
+
+    /// Whether blocks drawn with this material can be seen through, unless
+    /// a block says otherwise.
+    pub(crate) transparent: bool,
+}
+
+impl Tile {
+    /// Returns the UV coordinates of the top-left corner of the tile in its
+    /// image.
+    pub fn uv0(&self) -> (f32, f32) {
+        self.uv0
+    }
+
+    /// Returns the UV coordinates of the bottom-right corner of the tile in
+    /// its image.
+    pub fn uv1(&self) -> (f32, f32) {
+        self.uv1
+    }
+
+    /// Returns the layer of the tile in the tile texture array.
+    pub fn layer(&self) -> u32 {
+        self.layer
+    }
+}
+
+/// A block is a cube that is rendered in the game, unless it is drawn with a
+/// model.  Each state of a block type (see [BlockType]) is a block of its
+/// own.
 ///
 /// The block is specified by the tile indices of the front, back, top, bottom,
 /// left and right sides of the block. The tile indices are the indices of the
@@ -61,6 +159,603 @@ pub struct Block {
 
     /// The index of the tile that is used to render the right side of the block.
     pub(crate) right: usize,
+
+    /// The index of the material that is used to render the block.
+    pub(crate) material: usize,
+
+    /// How the block behaves in the world.
+    pub(crate) properties: BlockProperties,
+
+    /// The index of the model the block is drawn with, or `None` for a full
+    /// cube.
+    pub(crate) model: Option<usize>,
+
+    /// The index of the block's type in the `block_types` vector.
+    pub(crate) block_type: usize,
+}
+
+/// A kind of ore that is scattered through the ground in veins when terrain
+/// is generated.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ore {
+    /// The block the ore is made of.
+    pub(crate) block: BlockId,
+
+    /// The block the ore replaces, such as stone.
+    pub(crate) replaces: BlockId,
+
+    /// The number of blocks in each vein.
+    pub(crate) size: u32,
+
+    /// The lowest height that the ore is found at.
+    pub(crate) min_height: i64,
+
+    /// The highest height that the ore is found at.
+    pub(crate) max_height: i64,
+
+    /// The average number of veins started in each chunk within the heights.
+    pub(crate) frequency: f32,
+}
+
+/// A region of the world with its own climate, ground and plants.
+///
+/// Each column of the world is in the biome whose temperature and humidity
+/// ranges hold the climate there.  Climates run from 0 (cold or dry) to 1
+/// (hot or wet).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Biome {
+    /// The name of the biome.
+    pub(crate) name: String,
+
+    /// The lowest and highest temperatures of the biome.
+    pub(crate) temperature: (f32, f32),
+
+    /// The lowest and highest humidities of the biome.
+    pub(crate) humidity: (f32, f32),
+
+    /// The block on top of the ground.
+    pub(crate) surface: BlockId,
+
+    /// The block under the surface block, above the stone.
+    pub(crate) filler: BlockId,
+
+    /// The chance of a tree growing on each column.
+    pub(crate) tree_density: f32,
+
+    /// The colour that grass and leaves are tinted with.
+    pub(crate) grass_tint: [f32; 3],
+
+    /// How far (in blocks) the ground is raised above the lie of the land.
+    pub(crate) height: f32,
+
+    /// How tall the hills are, compared to the usual height of hills.
+    pub(crate) hilliness: f32,
+}
+
+/// Something placed on top of generated terrain, such as a tree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Feature {
+    /// The name of the feature.
+    pub(crate) name: String,
+
+    /// The shape of the feature and the blocks it is made of.
+    pub(crate) shape: FeatureShape,
+
+    /// The chance of the feature on each column, or `None` to use the
+    /// biome's tree density.
+    pub(crate) density: Option<f32>,
+
+    /// The indices of the biomes the feature is found in, or empty if it is
+    /// found in all of them.
+    pub(crate) biomes: Vec<usize>,
+}
+
+/// The shapes that features can have.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FeatureShape {
+    /// A trunk with a rounded clump of leaves at the top.
+    Tree {
+        /// The block the trunk is made of.
+        trunk: BlockId,
+
+        /// The block the leaves are made of.
+        leaves: BlockId,
+
+        /// The least and most blocks in the trunk.
+        height: (u32, u32),
+
+        /// How far the leaves reach from the trunk.
+        radius: u32,
+    },
+
+    /// A ball of blocks half sunk into the ground.
+    Boulder {
+        /// The block the boulder is made of.
+        block: BlockId,
+
+        /// The radius of the ball.
+        radius: u32,
+    },
+
+    /// A single block on top of the ground.
+    Plant {
+        /// The block of the plant.
+        block: BlockId,
+    },
+}
+
+/// A shape that blocks can be drawn with instead of a full cube, such as a
+/// slab, stairs or a plant.
+///
+/// The shape is a list of quads, measured in steps of 1/[BLOCK_STEPS] of a
+/// block from the block's lowest corner.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Model {
+    /// The quads that make up the model.
+    pub(crate) quads: Vec<ModelQuad>,
+
+    /// Whether each side of the block, in the order of [Face::ALL], is fully
+    /// covered by the model, and so hides the faces of the block next to it.
+    pub(crate) full_sides: [bool; 6],
+
+    /// The boxes that things collide with, as the lowest and highest corners
+    /// in steps.
+    pub(crate) boxes: Vec<[[u32; 3]; 2]>,
+}
+
+/// A quad of a block model.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelQuad {
+    /// The direction the quad is shaded as facing.
+    pub(crate) face: Face,
+
+    /// The corners of the quad in steps, anti-clockwise as seen from the
+    /// front: bottom-left, bottom-right, top-right, top-left.
+    pub(crate) corners: [[u32; 3]; 4],
+
+    /// The texture coordinates of each corner, in steps.
+    pub(crate) uvs: [[u32; 2]; 4],
+
+    /// The index of the tile the quad is drawn with, or `None` to use the
+    /// block's tile for the quad's face.
+    pub(crate) tile: Option<usize>,
+
+    /// The side of the block that the quad lies on, if any.  The quad is
+    /// hidden when the block on that side covers it, and is lit by that
+    /// block rather than the model's own.
+    pub(crate) cull: Option<Face>,
+}
+
+/// How a block type behaves: how it is drawn, lit, collided with and mined.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockProperties {
+    /// Whether things collide with the block.
+    pub(crate) solid: bool,
+
+    /// Whether the blocks behind the block can be seen through it.
+    pub(crate) transparent: bool,
+
+    /// Whether the block is blended with what is behind it, such as water or
+    /// stained glass, rather than being either fully seen or fully hidden.
+    pub(crate) translucent: bool,
+
+    /// Whether the block darkens the corners of the faces next to it.
+    pub(crate) occludes: bool,
+
+    /// The level of light (0 to 15) that the block gives off.
+    pub(crate) light_emission: u8,
+
+    /// How many levels (0 to 15) light is dimmed by on top of the usual one
+    /// level per block when it spreads into the block.  15 stops light.
+    pub(crate) light_opacity: u8,
+
+    /// How long the block takes to mine, in seconds by hand.
+    pub(crate) hardness: f32,
+
+    /// How much the block slows down things sliding over it, from 0 (ice-like)
+    /// to 1.
+    pub(crate) friction: f32,
+
+    /// Whether placing a block in its place replaces it, as with tall grass.
+    pub(crate) replaceable: bool,
+}
+
+impl Block {
+    /// Returns the index of the tile used to render a face of the block.
+    pub fn tile(&self, face: Face) -> usize {
+        match face {
+            Face::Front => self.front,
+            Face::Back => self.back,
+            Face::Left => self.left,
+            Face::Right => self.right,
+            Face::Top => self.top,
+            Face::Bottom => self.bottom,
+        }
+    }
+
+    /// Returns the index of the material used to render the block.
+    pub fn material(&self) -> usize {
+        self.material
+    }
+
+    /// Returns how the block behaves in the world.
+    pub fn properties(&self) -> &BlockProperties {
+        &self.properties
+    }
+
+    /// Returns the index of the model the block is drawn with, or `None` for
+    /// a full cube.
+    pub fn model(&self) -> Option<usize> {
+        self.model
+    }
+
+    /// Returns the index of the block's type in the database.
+    pub fn block_type(&self) -> usize {
+        self.block_type
+    }
+}
+
+impl Ore {
+    /// Returns the block the ore is made of.
+    pub fn block(&self) -> BlockId {
+        self.block
+    }
+
+    /// Returns the block the ore replaces.
+    pub fn replaces(&self) -> BlockId {
+        self.replaces
+    }
+
+    /// Returns the number of blocks in each vein.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Returns the lowest and highest heights that the ore is found at.
+    pub fn heights(&self) -> (i64, i64) {
+        (self.min_height, self.max_height)
+    }
+
+    /// Returns the average number of veins started in each chunk.
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+}
+
+impl Biome {
+    /// Returns the name of the biome.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the lowest and highest temperatures of the biome.
+    pub fn temperature(&self) -> (f32, f32) {
+        self.temperature
+    }
+
+    /// Returns the lowest and highest humidities of the biome.
+    pub fn humidity(&self) -> (f32, f32) {
+        self.humidity
+    }
+
+    /// Returns the block on top of the ground.
+    pub fn surface(&self) -> BlockId {
+        self.surface
+    }
+
+    /// Returns the block under the surface block.
+    pub fn filler(&self) -> BlockId {
+        self.filler
+    }
+
+    /// Returns the chance of a tree growing on each column.
+    pub fn tree_density(&self) -> f32 {
+        self.tree_density
+    }
+
+    /// Returns the colour (red, green and blue, from 0 to 1) that grass and
+    /// leaves are tinted with.
+    pub fn grass_tint(&self) -> [f32; 3] {
+        self.grass_tint
+    }
+
+    /// Returns how far (in blocks) the ground is raised.
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+
+    /// Returns how tall the hills are, compared to the usual height.
+    pub fn hilliness(&self) -> f32 {
+        self.hilliness
+    }
+
+    /// Returns how far a climate is from the ranges of the biome, or 0 if it
+    /// is inside them.
+    pub fn climate_distance(&self, temperature: f32, humidity: f32) -> f32 {
+        let outside =
+            |value: f32, (low, high): (f32, f32)| (low - value).max(value - high).max(0.0);
+        outside(temperature, self.temperature).hypot(outside(humidity, self.humidity))
+    }
+}
+
+impl Feature {
+    /// Returns the name of the feature.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the shape of the feature.
+    pub fn shape(&self) -> FeatureShape {
+        self.shape
+    }
+
+    /// Returns the chance of the feature on each column, if it has one of
+    /// its own.
+    pub fn density(&self) -> Option<f32> {
+        self.density
+    }
+
+    /// Returns the chance of the feature on each column of a biome.  Trees
+    /// with no density of their own use the biome's tree density.
+    pub fn density_in(&self, biome: &Biome) -> f32 {
+        match (self.density, self.shape) {
+            (Some(density), _) => density,
+            (None, FeatureShape::Tree { .. }) => biome.tree_density,
+            (None, _) => 0.0,
+        }
+    }
+
+    /// Returns true if the feature is found in a biome.
+    pub fn grows_in(&self, biome: usize) -> bool {
+        self.biomes.is_empty() || self.biomes.contains(&biome)
+    }
+}
+
+impl FeatureShape {
+    /// Returns how far (in blocks) the shape reaches sideways from where it
+    /// is placed.
+    pub fn radius(&self) -> u32 {
+        match *self {
+            FeatureShape::Tree { radius, .. } | FeatureShape::Boulder { radius, .. } => radius,
+            FeatureShape::Plant { .. } => 0,
+        }
+    }
+}
+
+impl Model {
+    /// Creates a model from its quads, working out which sides of the block
+    /// it fully covers.
+    ///
+    /// # Notes
+    ///
+    /// A side is fully covered if the quads that lie on it and face out of
+    /// the block cover every step of it.
+    ///
+    pub fn new(quads: Vec<ModelQuad>) -> Model {
+        let steps = BLOCK_STEPS as usize;
+        let full_sides = Face::ALL.map(|side| {
+            let (_, right, up) = side.axes();
+            let mut covered = vec![false; steps * steps];
+            for quad in quads.iter().filter(|quad| quad.cull == Some(side)) {
+                let range = |axis: usize| {
+                    let values = quad.corners.map(|corner| corner[axis] as usize);
+                    let min = values.into_iter().min().unwrap_or(0);
+                    let max = values.into_iter().max().unwrap_or(0);
+                    min..max.min(steps)
+                };
+                for b in range(up) {
+                    for a in range(right) {
+                        covered[b * steps + a] = true;
+                    }
+                }
+            }
+            covered.into_iter().all(|step| step)
+        });
+
+        Model {
+            quads,
+            full_sides,
+            boxes: Vec::new(),
+        }
+    }
+
+    /// Returns the model with boxes that things collide with, given as the
+    /// lowest and highest corners in steps.  A model has none until this is
+    /// called, so things pass through it.
+    pub fn with_boxes(mut self, boxes: Vec<[[u32; 3]; 2]>) -> Model {
+        self.boxes = boxes;
+        self
+    }
+
+    /// Returns the quads that make up the model.
+    pub fn quads(&self) -> &[ModelQuad] {
+        &self.quads
+    }
+
+    /// Returns the boxes that things collide with, as the lowest and highest
+    /// corners in steps.
+    pub fn boxes(&self) -> &[[[u32; 3]; 2]] {
+        &self.boxes
+    }
+
+    /// Returns true if the model fully covers a side of the block.
+    pub fn covers_side(&self, side: Face) -> bool {
+        self.full_sides[side.index()]
+    }
+}
+
+impl ModelQuad {
+    /// Works out the quad for a face of a cuboid, or `None` if the cuboid is
+    /// flat in that direction so the face has no area.
+    ///
+    /// # Parameters
+    ///
+    /// * `from` - The lowest corner of the cuboid, in steps.
+    /// * `to` - The highest corner of the cuboid, in steps.
+    /// * `face` - The face of the cuboid.
+    /// * `uvs` - The texture coordinates of the corners, or `None` for the part
+    ///   of the tile a full cube would show there.
+    /// * `tile` - The tile of the face, or `None` for the block's tile.
+    ///
+    pub fn cuboid_face(
+        from: [u32; 3],
+        to: [u32; 3],
+        face: Face,
+        uvs: Option<[[u32; 2]; 4]>,
+        tile: Option<usize>,
+    ) -> Option<ModelQuad> {
+        let (normal, right, up) = face.axes();
+        if from[right] == to[right] || from[up] == to[up] {
+            return None;
+        }
+
+        let unit = face.corners();
+        let corners =
+            unit.map(|corner| [0, 1, 2].map(|axis| [from, to][corner[axis] as usize][axis]));
+
+        // The texture runs along the face's right and up axes, which may point
+        // down their axes of the block, with v growing downwards.
+        let right_grows = unit[1][right] > unit[0][right];
+        let up_grows = unit[3][up] > unit[0][up];
+        let uvs = uvs.unwrap_or_else(|| {
+            corners.map(|corner| {
+                let u = if right_grows {
+                    corner[right]
+                } else {
+                    BLOCK_STEPS - corner[right]
+                };
+                let v = if up_grows {
+                    BLOCK_STEPS - corner[up]
+                } else {
+                    corner[up]
+                };
+                [u, v]
+            })
+        });
+
+        let on_side = if unit[0][normal] == 1 {
+            to[normal] == BLOCK_STEPS
+        } else {
+            from[normal] == 0
+        };
+
+        Some(ModelQuad {
+            face,
+            corners,
+            uvs,
+            tile,
+            cull: on_side.then_some(face),
+        })
+    }
+
+    /// Works out the quads of a cross: two quads running diagonally through the
+    /// block, each seen from both sides.  They are shaded as if facing up.
+    pub fn cross(tile: Option<usize>) -> [ModelQuad; 4] {
+        let s = BLOCK_STEPS;
+        let uvs = [[0, s], [s, s], [s, 0], [0, 0]];
+        let quad = |corners| ModelQuad {
+            face: Face::Top,
+            corners,
+            uvs,
+            tile,
+            cull: None,
+        };
+        [
+            quad([[0, 0, 0], [s, 0, s], [s, s, s], [0, s, 0]]),
+            quad([[s, 0, s], [0, 0, 0], [0, s, 0], [s, s, s]]),
+            quad([[0, 0, s], [s, 0, 0], [s, s, 0], [0, s, s]]),
+            quad([[s, 0, 0], [0, 0, s], [0, s, s], [s, s, 0]]),
+        ]
+    }
+
+    /// Returns the direction the quad is shaded as facing.
+    pub fn face(&self) -> Face {
+        self.face
+    }
+
+    /// Returns the corners of the quad in steps.
+    pub fn corners(&self) -> [[u32; 3]; 4] {
+        self.corners
+    }
+
+    /// Returns the texture coordinates of each corner, in steps.
+    pub fn uvs(&self) -> [[u32; 2]; 4] {
+        self.uvs
+    }
+
+    /// Returns the side of the block that the quad lies on, if any.
+    pub fn cull(&self) -> Option<Face> {
+        self.cull
+    }
+
+    /// Returns the index of the tile the quad is drawn with for a block.
+    pub fn tile(&self, block: &Block) -> usize {
+        self.tile.unwrap_or_else(|| block.tile(self.face))
+    }
+}
+
+impl BlockProperties {
+    /// Returns true if things collide with the block.
+    pub fn solid(&self) -> bool {
+        self.solid
+    }
+
+    /// Returns true if the blocks behind the block can be seen through it.
+    pub fn transparent(&self) -> bool {
+        self.transparent
+    }
+
+    /// Returns true if the block is blended with what is behind it.
+    pub fn translucent(&self) -> bool {
+        self.translucent
+    }
+
+    /// Returns true if the block darkens the corners of the faces next to it.
+    pub fn occludes(&self) -> bool {
+        self.occludes
+    }
+
+    /// Returns the level of light (0 to 15) that the block gives off.
+    pub fn light_emission(&self) -> u8 {
+        self.light_emission
+    }
+
+    /// Returns how many extra levels light is dimmed by in the block.
+    pub fn light_opacity(&self) -> u8 {
+        self.light_opacity
+    }
+
+    /// Returns how long the block takes to mine, in seconds by hand.
+    pub fn hardness(&self) -> f32 {
+        self.hardness
+    }
+
+    /// Returns how much the block slows down things sliding over it.
+    pub fn friction(&self) -> f32 {
+        self.friction
+    }
+
+    /// Returns true if placing a block in its place replaces it.
+    pub fn replaceable(&self) -> bool {
+        self.replaceable
+    }
+}
+
+impl Default for BlockProperties {
+    /// The properties of a plain solid block, such as stone.
+    fn default() -> Self {
+        BlockProperties {
+            solid: true,
+            transparent: false,
+            translucent: false,
+            occludes: true,
+            light_emission: 0,
+            light_opacity: 15,
+            hardness: 1.0,
+            friction: 0.6,
+            replaceable: false,
+        }
+    }
 }
 
 impl Database {
@@ -70,8 +765,352 @@ impl Database {
             image_names: HashMap::new(),
             tiles: Vec::new(),
             tile_names: HashMap::new(),
+            materials: Vec::new(),
+            material_names: HashMap::new(),
             blocks: Vec::new(),
+            block_types: Vec::new(),
             block_names: HashMap::new(),
+            models: Vec::new(),
+            model_names: HashMap::new(),
+            ores: Vec::new(),
+            biomes: Vec::new(),
+            biome_names: HashMap::new(),
+            features: Vec::new(),
         }
     }
+
+    /// Returns the tile with the given name.
+    pub fn tile(&self, name: &str) -> Option<&Tile> {
+        self.tile_names.get(name).map(|&index| &self.tiles[index])
+    }
+
+    /// Returns the index of the material with the given name.
+    pub fn material_index(&self, name: &str) -> Option<usize> {
+        self.material_names.get(name).copied()
+    }
+
+    /// Returns the ID of the default state of the block type with the given
+    /// name.
+    pub fn block_id(&self, name: &str) -> Option<BlockId> {
+        self.block_names
+            .get(name)
+            .map(|&index| BlockId::from_index(self.block_types[index].first))
+    }
+
+    /// Returns the type of a block, or `None` for air.
+    pub fn block_type(&self, id: BlockId) -> Option<&BlockType> {
+        self.block(id)
+            .and_then(|block| self.block_types.get(block.block_type))
+    }
+
+    /// Returns the value of a state property of a block, or `None` if the
+    /// block has no such property.
+    pub fn state_value(&self, id: BlockId, property: &str) -> Option<&str> {
+        let block_type = self.block_type(id)?;
+        let index = block_type.property_index(property)?;
+        let values = block_type.state_values(id.index()? - block_type.first);
+        Some(&block_type.properties[index].values[values[index]])
+    }
+
+    /// Returns the ID of a block with one of its state properties changed,
+    /// such as a door with `open` set to `true`.
+    ///
+    /// # Returns
+    ///
+    /// The ID of the new state, or `None` if the block has no such property
+    /// or it cannot have that value.
+    ///
+    pub fn with_state(&self, id: BlockId, property: &str, value: &str) -> Option<BlockId> {
+        let block_type = self.block_type(id)?;
+        let index = block_type.property_index(property)?;
+        let value = block_type.properties[index]
+            .values
+            .iter()
+            .position(|v| v == value)?;
+
+        let mut values = block_type.state_values(id.index()? - block_type.first);
+        values[index] = value;
+        Some(BlockId::from_index(
+            block_type.first + block_type.state_index(&values),
+        ))
+    }
+
+    /// Works out the state of a block being placed by a player, following
+    /// the placement rule of its type (see [Placement]).
+    ///
+    /// # Parameters
+    ///
+    /// * `id` - Any state of the block being placed.
+    /// * `look` - The direction the player is looking in.
+    ///
+    /// # Returns
+    ///
+    /// The ID of the state to place.  This is `id` itself if the rule does
+    /// not apply, such as a furnace that cannot face the way needed.
+    ///
+    pub fn placement_state(&self, id: BlockId, look: [f32; 3]) -> BlockId {
+        let Some(block_type) = self.block_type(id) else {
+            return id;
+        };
+
+        // The axes in order of how much the player is looking along them.
+        let mut axes = [0, 1, 2];
+        axes.sort_by(|&a, &b| look[b].abs().total_cmp(&look[a].abs()));
+
+        let state = match block_type.placement {
+            Placement::Default => None,
+            Placement::Axis => self.with_state(id, "axis", ["x", "y", "z"][axes[0]]),
+            Placement::Facing => {
+                // The front faces back towards the player, turned to the
+                // nearest way the block can face.
+                axes.into_iter().find_map(|axis| {
+                    let towards = if look[axis] > 0.0 {
+                        ["west", "down", "north"]
+                    } else {
+                        ["east", "up", "south"]
+                    };
+                    self.with_state(id, "facing", towards[axis])
+                })
+            }
+        };
+        state.unwrap_or(id)
+    }
+
+    /// Returns the block type for an ID, or `None` for air.
+    pub fn block(&self, id: BlockId) -> Option<&Block> {
+        id.index().and_then(|index| self.blocks.get(index))
+    }
+
+    /// Returns the name that a block is saved under: the name of its type,
+    /// followed by its state values if it has any, such as
+    /// `stone-stairs[facing=east]`.  Air has no name.
+    pub fn block_key(&self, id: BlockId) -> Option<String> {
+        let block_type = self.block_type(id)?;
+        if block_type.properties.is_empty() {
+            return Some(block_type.name.clone());
+        }
+        let values = block_type.state_values(id.index()? - block_type.first);
+        let states = block_type
+            .properties
+            .iter()
+            .zip(values)
+            .map(|(property, value)| format!("{}={}", property.name, property.values[value]))
+            .collect::<Vec<_>>();
+        Some(format!("{}[{}]", block_type.name, states.join(",")))
+    }
+
+    /// Returns the block saved under a name (see [Database::block_key]).
+    /// States that no longer exist are left at their default values.
+    pub fn block_from_key(&self, key: &str) -> Option<BlockId> {
+        let (name, states) = match key.strip_suffix(']').and_then(|key| key.split_once('[')) {
+            Some((name, states)) => (name, states),
+            None => (key, ""),
+        };
+        let mut id = self.block_id(name)?;
+        for state in states.split(',').filter(|state| !state.is_empty()) {
+            if let Some(state) = state
+                .split_once('=')
+                .and_then(|(property, value)| self.with_state(id, property, value))
+            {
+                id = state;
+            }
+        }
+        Some(id)
+    }
+
+    /// Returns the ores scattered through the ground.
+    pub fn ores(&self) -> &[Ore] {
+        &self.ores
+    }
+
+    /// Returns the biomes that the world is split into.
+    pub fn biomes(&self) -> &[Biome] {
+        &self.biomes
+    }
+
+    /// Returns the biome at an index.
+    pub fn biome(&self, index: usize) -> Option<&Biome> {
+        self.biomes.get(index)
+    }
+
+    /// Returns the index of a biome from its name.
+    pub fn biome_index(&self, name: &str) -> Option<usize> {
+        self.biome_names.get(name).copied()
+    }
+
+    /// Returns the features placed on top of generated terrain.
+    pub fn features(&self) -> &[Feature] {
+        &self.features
+    }
+
+    /// Returns the model a block is drawn with, or `None` for air and full
+    /// cubes.
+    pub fn model(&self, id: BlockId) -> Option<&Model> {
+        self.block(id)
+            .and_then(|block| block.model)
+            .map(|index| &self.models[index])
+    }
+
+    /// Returns true if a block fully covers one of its sides, hiding the
+    /// faces of the block next to it on that side.  Full cubes cover all
+    /// their sides and air covers none.
+    pub fn covers_side(&self, id: BlockId, side: Face) -> bool {
+        match self.block(id) {
+            Some(block) => block
+                .model
+                .is_none_or(|index| self.models[index].covers_side(side)),
+            None => false,
+        }
+    }
+
+    /// Returns the properties of a block, or `None` for air.
+    pub fn properties(&self, id: BlockId) -> Option<&BlockProperties> {
+        self.block(id).map(|block| &block.properties)
+    }
+
+    /// Returns true if things collide with a block.  Air is never solid.
+    pub fn is_solid(&self, id: BlockId) -> bool {
+        self.properties(id).is_some_and(|p| p.solid)
+    }
+
+    /// Returns the boxes that things collide with in a block, as the lowest
+    /// and highest corners in steps.  Full cubes have one box filling the
+    /// block, and air and blocks that aren't solid have none.
+    pub fn collision_boxes(&self, id: BlockId) -> &[[[u32; 3]; 2]] {
+        const FULL: [[[u32; 3]; 2]; 1] = [[[0; 3], [BLOCK_STEPS; 3]]];
+        match self.block(id) {
+            Some(block) if block.properties.solid => match block.model {
+                Some(index) => self.models[index].boxes(),
+                None => &FULL,
+            },
+            _ => &[],
+        }
+    }
+
+    /// Returns how much a block slows down things sliding over it, or `None`
+    /// for air.
+    pub fn friction(&self, id: BlockId) -> Option<f32> {
+        self.properties(id).map(|p| p.friction)
+    }
+
+    /// Returns true if the blocks behind a block can be seen through it.
+    /// Air is always transparent.
+    pub fn is_transparent(&self, id: BlockId) -> bool {
+        self.properties(id).is_none_or(|p| p.transparent)
+    }
+
+    /// Returns true if a block is blended with what is behind it.  Air is
+    /// not.
+    pub fn is_translucent(&self, id: BlockId) -> bool {
+        self.properties(id).is_some_and(|p| p.translucent)
+    }
+
+    /// Returns true if a block darkens the corners of the faces next to it.
+    /// Air never does.
+    pub fn occludes(&self, id: BlockId) -> bool {
+        self.properties(id).is_some_and(|p| p.occludes)
+    }
+
+    /// Returns the level of light (0 to 15) that a block gives off.  Air
+    /// gives off none.
+    pub fn light_emission(&self, id: BlockId) -> u8 {
+        self.properties(id).map_or(0, |p| p.light_emission)
+    }
+
+    /// Returns how many extra levels light is dimmed by in a block.  Air
+    /// does not dim light.
+    pub fn light_opacity(&self, id: BlockId) -> u8 {
+        self.properties(id).map_or(0, |p| p.light_opacity)
+    }
+
+    /// Returns true if placing a block in the place of another replaces it.
+    /// Air can always be replaced.
+    pub fn is_replaceable(&self, id: BlockId) -> bool {
+        self.properties(id).is_none_or(|p| p.replaceable)
+    }
+}
+
+#[cfg(test)]
+impl Database {
+    /// Creates a database for tests with a plain solid block for each name,
+    /// in order, so that the ID of each block is its index in `names`.
+    pub(crate) fn test_blocks(names: &[&str]) -> Database {
+        names.iter().fold(Database::new(), |db, name| {
+            db.with_block(name, 0, BlockProperties::default(), None)
+        })
+    }
+
+    /// Adds a block type with a single state that uses the first tile on
+    /// every face.
+    ///
+    /// # Parameters
+    ///
+    /// * `name` - The name of the block type.
+    /// * `material` - The index of the material of the block.
+    /// * `properties` - The properties of the block.
+    /// * `model` - The index of the model of the block, or `None` for a cube.
+    ///
+    pub(crate) fn with_block(
+        self,
+        name: &str,
+        material: usize,
+        properties: BlockProperties,
+        model: Option<usize>,
+    ) -> Database {
+        let mut db = self.with_block_type(BlockType {
+            name: name.into(),
+            first: 0,
+            properties: vec![],
+            placement: Placement::Default,
+        });
+        let block = db.blocks.last_mut().unwrap();
+        block.material = material;
+        block.properties = properties;
+        block.model = model;
+        db
+    }
+
+    /// Adds a block type with a plain solid block for each of its states.
+    pub(crate) fn with_block_type(mut self, block_type: BlockType) -> Database {
+        let index = self.block_types.len();
+        let block_type = BlockType {
+            first: self.blocks.len(),
+            ..block_type
+        };
+        for _ in 0..block_type.state_count() {
+            self.blocks.push(Block {
+                front: 0,
+                back: 0,
+                left: 0,
+                right: 0,
+                top: 0,
+                bottom: 0,
+                material: 0,
+                properties: BlockProperties::default(),
+                model: None,
+                block_type: index,
+            });
+        }
+        self.block_names.insert(block_type.name.clone(), index);
+        self.block_types.push(block_type);
+        self
+    }
+
+    /// Adds a biome that covers every climate, with no trees and the usual
+    /// lie of the land.
+    pub(crate) fn with_biome(mut self, name: &str, surface: BlockId, filler: BlockId) -> Database {
+        self.biome_names.insert(name.into(), self.biomes.len());
+        self.biomes.push(Biome {
+            name: name.into(),
+            temperature: (0.0, 1.0),
+            humidity: (0.0, 1.0),
+            surface,
+            filler,
+            tree_density: 0.0,
+            grass_tint: [1.0; 3],
+            height: 0.0,
+            hilliness: 1.0,
+        });
+        self
+    }
 }
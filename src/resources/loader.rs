@@ -1,13 +1,23 @@
 use std::{
+    collections::HashMap,
     fs::{self, File},
     io::BufReader,
     path::{Path, PathBuf},
 };
 
-use knuffel::{parse, Decode};
+use gfx::{BlendMode, CullMode, DepthMode, PipelineState};
+use knuffel::{parse, Decode, DecodeScalar};
 use tracing::{error, info};
 
-use crate::resources::{Block, Tile};
+use crate::{
+    resources::{
+        compass_face, Biome, Block, BlockProperties, BlockType, Feature, FeatureShape, Material,
+        Model, ModelQuad, Ore, Placement, Rotation, StateProperty, Tile,
+    },
+    world::{
+        Face, BLOCK_STEPS, MAX_BIOMES, MAX_FEATURE_RADIUS, MAX_LIGHT, MAX_TILES, MAX_VEIN_SIZE,
+    },
+};
 
 use super::Database;
 
@@ -32,18 +42,62 @@ use super::Database;
 ///     ..
 /// }
 ///
+/// // Lists all the materials that are used in this file.  They will reference
+/// // textures from the textures section and provides names for themselves.
+/// materials {
+///     material ..
+///     ..
+/// }
+///
+/// // Lists all the block models that are used in this file.  They may
+/// // reference tiles from the tiles section and provides names for
+/// // themselves.
+/// models {
+///     model ..
+///     ..
+/// }
+///
 /// // Lists all the game block types that are used in this file.  They will
-/// // reference tiles from the tiles section and provides names for themselves.
+/// // reference tiles from the tiles section, materials from the materials
+/// // section and models from the models section and provides names for
+/// // themselves.
 /// blocks {
 ///     block ..
 ///     ..
 /// }
+///
+/// // Lists the ores that are scattered through the ground.  They will
+/// // reference blocks from the blocks section.
+/// ores {
+///     ore ..
+///     ..
+/// }
+///
+/// // Lists the biomes that the world is split into.  They will reference
+/// // blocks from the blocks section and provides names for themselves.
+/// biomes {
+///     biome ..
+///     ..
+/// }
+///
+/// // Lists the trees, boulders and plants placed on generated terrain.
+/// // They will reference blocks from the blocks section and biomes from the
+/// // biomes section.
+/// features {
+///     feature ..
+///     ..
+/// }
 /// ```
 #[derive(Decode, Debug)]
 pub enum KdlTopLevel {
     Textures(KdlTextures),
     Tiles(KdlTiles),
+    Materials(KdlMaterials),
+    Models(KdlModels),
     Blocks(KdlBlocks),
+    Ores(KdlOres),
+    Biomes(KdlBiomes),
+    Features(KdlFeatures),
 }
 
 /// Represents a `textures` section in the KDL file.
@@ -126,6 +180,235 @@ pub struct KdlTile {
     y: usize,
 }
 
+/// Represents a `materials` section in the KDL file.
+///
+/// This provides a list of materials that are used in this file.  A material
+/// is a shader and the state of the pipeline that draws it.
+///
+/// Each entry in this section is a `material` entry of the format:
+///
+/// ```kdl
+/// material "<name>" "<shader path>" <option>="<value>" .. {
+///     texture "<texture name>"
+///     ..
+/// }
+/// ```
+///
+/// The options are all optional and can be:
+///
+/// * `vertex` - The vertex entry point of the shader (default `vs_main`).
+/// * `fragment` - The fragment entry point of the shader (default `fs_main`).
+/// * `blend` - One of `replace`, `alpha` or `additive` (default `replace`).
+/// * `cull` - One of `none`, `back` or `front` (default `back`).
+/// * `depth` - One of `none`, `test` or `test-and-write` (default `none`).
+/// * `transparent` - Whether blocks drawn with this material can be seen
+///   through (default `false`).  Each block can override this.
+///
+/// Each `texture` child names a texture from the `textures` section.  They are
+/// bound to the shader in order: texture `n` is at binding `2n` of group 0 and
+/// its sampler at binding `2n + 1`.
+///
+/// Each `buffer` child names a buffer created by the game, such as `tiles`
+/// for the tile table.  Buffer `n` is at binding `n` of group 1.
+///
+#[derive(Decode, Debug)]
+pub struct KdlMaterials {
+    /// The list of materials that are declared in a `materials` section.
+    #[knuffel(children(name = "material"))]
+    materials: Vec<KdlMaterial>,
+}
+
+/// Represents a `material` entry in the KDL file within the `materials`
+/// section.
+#[derive(Decode, Debug)]
+pub struct KdlMaterial {
+    /// The name of the material.
+    #[knuffel(argument)]
+    name: String,
+
+    /// The path to the WGSL shader file relative to the KDL file.
+    #[knuffel(argument)]
+    shader: PathBuf,
+
+    /// The name of the vertex entry point in the shader.
+    #[knuffel(property, default = "vs_main".into())]
+    vertex: String,
+
+    /// The name of the fragment entry point in the shader.
+    #[knuffel(property, default = "fs_main".into())]
+    fragment: String,
+
+    /// How the material's colours are blended with the frame.
+    #[knuffel(property, default)]
+    blend: KdlBlend,
+
+    /// Which faces are culled.
+    #[knuffel(property, default)]
+    cull: KdlCull,
+
+    /// How the depth buffer is used.
+    #[knuffel(property, default)]
+    depth: KdlDepth,
+
+    /// Whether blocks behind this material can be seen through it.
+    #[knuffel(property, default)]
+    transparent: bool,
+
+    /// The names of the textures bound to the shader.
+    #[knuffel(children(name = "texture"), unwrap(argument))]
+    textures: Vec<String>,
+
+    /// The names of the buffers bound to the shader.
+    #[knuffel(children(name = "buffer"), unwrap(argument))]
+    buffers: Vec<String>,
+}
+
+/// The values of the `blend` property of a material.
+#[derive(DecodeScalar, Debug, Default)]
+pub enum KdlBlend {
+    #[default]
+    Replace,
+    Alpha,
+    Additive,
+}
+
+/// The values of the `cull` property of a material.
+#[derive(DecodeScalar, Debug, Default)]
+pub enum KdlCull {
+    None,
+    #[default]
+    Back,
+    Front,
+}
+
+/// The values of the `depth` property of a material.
+#[derive(DecodeScalar, Debug, Default)]
+pub enum KdlDepth {
+    #[default]
+    None,
+    Test,
+    TestAndWrite,
+}
+
+/// Represents a `models` section in the KDL file.
+///
+/// This provides a list of shapes that blocks can be drawn with instead of a
+/// full cube.  All coordinates are in eighths of a block, from 0 to 8.
+///
+/// Each entry in this section is a `model` entry of the format:
+///
+/// ```kdl
+/// model "<name>" {
+///     cuboid <x0> <y0> <z0> <x1> <y1> <z1> {
+///         face "<side>" <u0> <v0> <u1> <v1> tile="<tile name>"
+///         ..
+///     }
+///     cross tile="<tile name>"
+///     ..
+/// }
+/// ```
+///
+/// A `cuboid` is a box from one corner to the other.  Without any `face`
+/// children it has all six faces, otherwise only the ones listed.  The `side`
+/// is one of `front`, `back`, `left`, `right`, `top` or `bottom`.  Each face
+/// is drawn with the block's tile for that side unless it names a `tile`.
+/// Its texture coordinates are the rectangle from `u0 v0` (top-left) to `u1
+/// v1` (bottom-right) of the tile, and default to the part of the tile that a
+/// full cube would show there.  Things collide with the cuboids of solid
+/// blocks.
+///
+/// A `cross` is two crossed quads running diagonally through the block, seen
+/// from both sides, as used for plants.  It is drawn with the `tile` given or
+/// the block's top tile, and nothing collides with it.
+///
+/// Faces that lie on a side of the block are hidden by a block next to it
+/// that covers that side.  A model covers a side if its faces on that side
+/// fill it.
+///
+#[derive(Decode, Debug)]
+pub struct KdlModels {
+    /// The list of models that are declared in a `models` section.
+    #[knuffel(children(name = "model"))]
+    models: Vec<KdlModel>,
+}
+
+/// Represents a `model` entry in the KDL file within the `models` section.
+#[derive(Decode, Debug)]
+pub struct KdlModel {
+    /// The name of the model.
+    #[knuffel(argument)]
+    name: String,
+
+    /// The boxes that make up the model.
+    #[knuffel(children(name = "cuboid"))]
+    cuboids: Vec<KdlCuboid>,
+
+    /// The crossed quads that make up the model.
+    #[knuffel(children(name = "cross"))]
+    crosses: Vec<KdlCross>,
+}
+
+/// Represents a `cuboid` entry in the KDL file within a `model` entry.
+#[derive(Decode, Debug)]
+pub struct KdlCuboid {
+    #[knuffel(argument)]
+    x0: u32,
+
+    #[knuffel(argument)]
+    y0: u32,
+
+    #[knuffel(argument)]
+    z0: u32,
+
+    #[knuffel(argument)]
+    x1: u32,
+
+    #[knuffel(argument)]
+    y1: u32,
+
+    #[knuffel(argument)]
+    z1: u32,
+
+    /// The faces of the box that are drawn, or all of them if empty.
+    #[knuffel(children(name = "face"))]
+    faces: Vec<KdlModelFace>,
+}
+
+/// Represents a `face` entry in the KDL file within a `cuboid` entry.
+#[derive(Decode, Debug)]
+pub struct KdlModelFace {
+    /// The side of the box.
+    #[knuffel(argument)]
+    side: KdlFace,
+
+    /// The texture rectangle: `u0 v0 u1 v1`, or nothing for the default.
+    #[knuffel(arguments)]
+    uv: Vec<u32>,
+
+    /// The name of the tile, if not the block's tile for the side.
+    #[knuffel(property)]
+    tile: Option<String>,
+}
+
+/// Represents a `cross` entry in the KDL file within a `model` entry.
+#[derive(Decode, Debug)]
+pub struct KdlCross {
+    /// The name of the tile, if not the block's top tile.
+    #[knuffel(property)]
+    tile: Option<String>,
+}
+
+/// The sides of a block, as named in the KDL file.
+#[derive(DecodeScalar, Debug, Clone, Copy)]
+pub enum KdlFace {
+    Front,
+    Back,
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
 /// Represents a `blocks` section in the KDL file.
 ///
 /// This provides a list of blocks that are used in this file.
@@ -133,7 +416,7 @@ pub struct KdlTile {
 /// Each entry in this section is a `block` entry in this format:
 ///
 /// ```kdl
-/// block "<name>" <area>="<tile name>"
+/// block "<name>" <area>="<tile name>" material="<material name>"
 /// ```
 ///
 /// The `area` can be one of `tiles`, `sides`, `top`, `bottom`, `front`, `back`,
@@ -146,6 +429,66 @@ pub struct KdlTile {
 /// separately.  The `top`, `bottom`, `front`, `back`, `left`, `right` areas are
 /// used for blocks that have different tiles for each side.
 ///
+/// The `material` is the name of a material from the `materials` section that
+/// the block is drawn with.  It defaults to `opaque`.
+///
+/// The `model` is the name of a model from the `models` section that the
+/// block is drawn with instead of a full cube.  The tiles still have to be
+/// given, as the model's faces use them unless they name their own.
+///
+/// The other properties describe how the block behaves, and are all optional:
+///
+/// * `solid` - Whether things collide with the block (default `true`).
+/// * `transparent` - Whether the blocks behind can be seen through it
+///   (defaults to the material's `transparent`).
+/// * `translucent` - Whether it is blended with what is behind it, like water
+///   (defaults to `true` if the material uses alpha blending).  Translucent
+///   blocks are always transparent.
+/// * `occludes` - Whether it darkens the corners of the faces next to it
+///   (defaults to `true` unless the block is transparent or has a model).
+/// * `light-emission` - The level of light, from 0 to 15, that it gives off,
+///   such as for a torch or lava (default 0).
+/// * `light-opacity` - How many levels, from 0 to 15, light is dimmed by as
+///   it passes through, on top of the usual one level per block.  15 stops
+///   light altogether (defaults to 15, 1 if translucent, 0 if transparent or
+///   if it has a model).
+/// * `hardness` - How long it takes to mine, in seconds by hand (default
+///   1.0).
+/// * `friction` - How much it slows down things sliding over it, from 0 to 1
+///   (default 0.6).
+/// * `replaceable` - Whether placing a block in its place replaces it, like
+///   tall grass (default `false`).
+///
+/// A block can also have states, such as which way it faces or whether it
+/// is open, declared as children:
+///
+/// ```kdl
+/// block "<name>" .. placement="<rule>" {
+///     state "<property>" "<value>" ..
+///     ..
+///     variant "<property>=<value>,.." <area>="<tile name>" model="<model name>"
+///     ..
+/// }
+/// ```
+///
+/// Each `state` lists the values a property can have, the first being the
+/// default.  Every combination of values is a block of its own.  Two
+/// properties turn the block:
+///
+/// * `axis` - One of `x`, `y` or `z`: the axis the block's top points along,
+///   like a log.
+/// * `facing` - One of `north` (-z), `south` (+z), `east` (+x), `west`
+///   (-x), `up` or `down`: the way the block's front faces, like a furnace.
+///   Unturned, the front faces south.
+///
+/// Each `variant` changes the tiles or model of the states that match all
+/// of its `property=value` conditions, before they are turned.  Later
+/// variants win.
+///
+/// The `placement` is how the state of a placed block is chosen: `default`
+/// (the default state), `axis` (along the axis the player looks along) or
+/// `facing` (with its front facing the player).
+///
 #[derive(Decode, Debug)]
 pub struct KdlBlocks {
     /// The list of blocks that are declared in a `blocks` section.
@@ -161,31 +504,344 @@ pub struct KdlBlock {
     #[knuffel(argument)]
     name: String,
 
-    #[knuffel(argument)]
+    #[knuffel(flatten(property))]
+    areas: KdlAreas,
+
+    #[knuffel(property, default = "opaque".into())]
+    material: String,
+
+    #[knuffel(property)]
+    model: Option<String>,
+
+    #[knuffel(property, default = true)]
+    solid: bool,
+
+    #[knuffel(property)]
+    transparent: Option<bool>,
+
+    #[knuffel(property)]
+    translucent: Option<bool>,
+
+    #[knuffel(property)]
+    occludes: Option<bool>,
+
+    #[knuffel(property, default)]
+    light_emission: u8,
+
+    #[knuffel(property)]
+    light_opacity: Option<u8>,
+
+    #[knuffel(property, default = 1.0)]
+    hardness: f32,
+
+    #[knuffel(property, default = 0.6)]
+    friction: f32,
+
+    #[knuffel(property, default)]
+    replaceable: bool,
+
+    #[knuffel(property, default)]
+    placement: KdlPlacement,
+
+    #[knuffel(children(name = "state"))]
+    states: Vec<KdlState>,
+
+    #[knuffel(children(name = "variant"))]
+    variants: Vec<KdlVariant>,
+}
+
+/// The tiles of each area of a block, as given in a `block` or `variant`
+/// entry.
+#[derive(Decode, Debug, Default)]
+pub struct KdlAreas {
+    #[knuffel(property)]
     tiles: Option<String>,
 
-    #[knuffel(argument)]
+    #[knuffel(property)]
     sides: Option<String>,
 
-    #[knuffel(argument)]
+    #[knuffel(property)]
     top: Option<String>,
 
-    #[knuffel(argument)]
+    #[knuffel(property)]
     bottom: Option<String>,
 
-    #[knuffel(argument)]
+    #[knuffel(property)]
     front: Option<String>,
 
-    #[knuffel(argument)]
+    #[knuffel(property)]
     back: Option<String>,
 
-    #[knuffel(argument)]
+    #[knuffel(property)]
     left: Option<String>,
 
-    #[knuffel(argument)]
+    #[knuffel(property)]
     right: Option<String>,
 }
 
+/// Represents a `state` entry in the KDL file within a `block` entry.
+#[derive(Decode, Debug)]
+pub struct KdlState {
+    /// The name of the state property.
+    #[knuffel(argument)]
+    name: String,
+
+    /// The values the property can have, the first being the default.
+    #[knuffel(arguments)]
+    values: Vec<String>,
+}
+
+/// Represents a `variant` entry in the KDL file within a `block` entry.
+#[derive(Decode, Debug)]
+pub struct KdlVariant {
+    /// The states the variant applies to, as `property=value` conditions
+    /// separated by commas.
+    #[knuffel(argument)]
+    conditions: String,
+
+    /// The tiles that the variant changes.
+    #[knuffel(flatten(property))]
+    areas: KdlAreas,
+
+    /// The model that the variant is drawn with instead.
+    #[knuffel(property)]
+    model: Option<String>,
+}
+
+/// The values of the `placement` property of a block.
+#[derive(DecodeScalar, Debug, Default)]
+pub enum KdlPlacement {
+    #[default]
+    Default,
+    Axis,
+    Facing,
+}
+
+/// Represents an `ores` section in the KDL file.
+///
+/// This provides a list of ores that are scattered through the ground in
+/// veins when terrain is generated.
+///
+/// Each entry in this section is an `ore` entry of the format:
+///
+/// ```kdl
+/// ore "<block name>" size=<blocks> min-height=<y> max-height=<y> \
+///     frequency=<veins> replaces="<block name>"
+/// ```
+///
+/// * `size` - The number of blocks in each vein, from 1 to 16.
+/// * `min-height` and `max-height` - The heights that the ore is found
+///   between.
+/// * `frequency` - The average number of veins started in each chunk between
+///   those heights.
+/// * `replaces` - The block the ore replaces (default `stone`).
+///
+#[derive(Decode, Debug)]
+pub struct KdlOres {
+    /// The list of ores that are declared in an `ores` section.
+    #[knuffel(children(name = "ore"))]
+    ores: Vec<KdlOre>,
+}
+
+/// Represents an `ore` entry in the KDL file within the `ores` section.
+#[derive(Decode, Debug)]
+pub struct KdlOre {
+    /// The name of the block the ore is made of.
+    #[knuffel(argument)]
+    block: String,
+
+    /// The number of blocks in each vein.
+    #[knuffel(property)]
+    size: u32,
+
+    /// The lowest height that the ore is found at.
+    #[knuffel(property)]
+    min_height: i64,
+
+    /// The highest height that the ore is found at.
+    #[knuffel(property)]
+    max_height: i64,
+
+    /// The average number of veins started in each chunk.
+    #[knuffel(property)]
+    frequency: f32,
+
+    /// The name of the block the ore replaces.
+    #[knuffel(property, default = "stone".into())]
+    replaces: String,
+}
+
+/// Represents a `biomes` section in the KDL file.
+///
+/// This provides a list of biomes that the world is split into when terrain
+/// is generated.  Each column of the world is in the first biome whose
+/// temperature and humidity ranges hold its climate, and the height of the
+/// ground is blended between biomes near their borders.
+///
+/// Each entry in this section is a `biome` entry of the format:
+///
+/// ```kdl
+/// biome "<name>" <properties>
+/// ```
+///
+/// Properties can be:
+///
+/// * `min-temperature` and `max-temperature` - The range of temperatures of
+///   the biome, from 0 (cold) to 1 (hot).  Defaults to 0 and 1.
+/// * `min-humidity` and `max-humidity` - The range of humidities of the
+///   biome, from 0 (dry) to 1 (wet).  Defaults to 0 and 1.
+/// * `surface` - The block on top of the ground (default `grass`).
+/// * `filler` - The block under the surface, above the stone (default
+///   `dirt`).
+/// * `tree-density` - The chance, from 0 to 1, of a tree growing on each
+///   column (default 0).
+/// * `grass-tint` - The colour grass and leaves are tinted with, as
+///   `"#rrggbb"` (default white).
+/// * `height` - How many blocks the ground is raised by (default 0).
+/// * `hilliness` - How tall the hills are compared to usual (default 1).
+///
+#[derive(Decode, Debug)]
+pub struct KdlBiomes {
+    /// The list of biomes that are declared in a `biomes` section.
+    #[knuffel(children(name = "biome"))]
+    biomes: Vec<KdlBiome>,
+}
+
+/// Represents a `biome` entry in the KDL file within the `biomes` section.
+#[derive(Decode, Debug)]
+pub struct KdlBiome {
+    /// The name of the biome.
+    #[knuffel(argument)]
+    name: String,
+
+    /// The lowest temperature of the biome.
+    #[knuffel(property, default = 0.0)]
+    min_temperature: f32,
+
+    /// The highest temperature of the biome.
+    #[knuffel(property, default = 1.0)]
+    max_temperature: f32,
+
+    /// The lowest humidity of the biome.
+    #[knuffel(property, default = 0.0)]
+    min_humidity: f32,
+
+    /// The highest humidity of the biome.
+    #[knuffel(property, default = 1.0)]
+    max_humidity: f32,
+
+    /// The name of the block on top of the ground.
+    #[knuffel(property, default = "grass".into())]
+    surface: String,
+
+    /// The name of the block under the surface.
+    #[knuffel(property, default = "dirt".into())]
+    filler: String,
+
+    /// The chance of a tree growing on each column.
+    #[knuffel(property, default = 0.0)]
+    tree_density: f32,
+
+    /// The colour grass and leaves are tinted with.
+    #[knuffel(property, default = "#ffffff".into())]
+    grass_tint: String,
+
+    /// How many blocks the ground is raised by.
+    #[knuffel(property, default = 0.0)]
+    height: f32,
+
+    /// How tall the hills are compared to usual.
+    #[knuffel(property, default = 1.0)]
+    hilliness: f32,
+}
+
+/// Represents a `features` section in the KDL file.
+///
+/// This provides a list of the trees, boulders and plants that are placed on
+/// top of generated terrain.
+///
+/// Each entry in this section is a `feature` entry of the format:
+///
+/// ```kdl
+/// feature "<name>" kind=<kind> <properties>
+/// ```
+///
+/// The kind can be:
+///
+/// * `tree` - A trunk of `trunk` blocks, `min-trunk` to `max-trunk` blocks
+///   tall, topped with `leaves` blocks reaching `radius` blocks from it.
+/// * `boulder` - A ball of `block` blocks with a radius of `radius`, half
+///   sunk into the ground.
+/// * `plant` - A single `block` on top of the ground.
+///
+/// Other properties can be:
+///
+/// * `density` - The chance, from 0 to 1, of the feature on each column.
+///   Trees default to the tree density of the biome and everything else to
+///   0.
+/// * `biomes` - The names of the biomes the feature is found in, separated
+///   by commas (default all of them).
+///
+#[derive(Decode, Debug)]
+pub struct KdlFeatures {
+    /// The list of features that are declared in a `features` section.
+    #[knuffel(children(name = "feature"))]
+    features: Vec<KdlFeature>,
+}
+
+/// Represents a `feature` entry in the KDL file within the `features`
+/// section.
+#[derive(Decode, Debug)]
+pub struct KdlFeature {
+    /// The name of the feature.
+    #[knuffel(argument)]
+    name: String,
+
+    /// The kind of feature.
+    #[knuffel(property)]
+    kind: KdlFeatureKind,
+
+    /// The name of the block of a boulder or plant.
+    #[knuffel(property)]
+    block: Option<String>,
+
+    /// The name of the block of a tree's trunk.
+    #[knuffel(property)]
+    trunk: Option<String>,
+
+    /// The name of the block of a tree's leaves.
+    #[knuffel(property)]
+    leaves: Option<String>,
+
+    /// The least number of blocks in a tree's trunk.
+    #[knuffel(property, default = 4)]
+    min_trunk: u32,
+
+    /// The most blocks in a tree's trunk.
+    #[knuffel(property, default = 6)]
+    max_trunk: u32,
+
+    /// How far a tree's leaves reach, or the radius of a boulder.
+    #[knuffel(property, default = 2)]
+    radius: u32,
+
+    /// The chance of the feature on each column.
+    #[knuffel(property)]
+    density: Option<f32>,
+
+    /// The names of the biomes the feature is found in, separated by commas.
+    #[knuffel(property)]
+    biomes: Option<String>,
+}
+
+/// The values of the `kind` property of a feature.
+#[derive(DecodeScalar, Debug)]
+pub enum KdlFeatureKind {
+    Tree,
+    Boulder,
+    Plant,
+}
+
 /// Returns the path to the given file name in the `data` directory.
 fn get_path<P>(file_name: P) -> PathBuf
 where
@@ -210,7 +866,12 @@ where
         match info {
             KdlTopLevel::Textures(textures) => process_textures(&mut database, textures),
             KdlTopLevel::Tiles(tiles) => process_tiles(&mut database, tiles),
+            KdlTopLevel::Materials(materials) => process_materials(&mut database, materials),
+            KdlTopLevel::Models(models) => process_models(&mut database, models),
             KdlTopLevel::Blocks(blocks) => process_blocks(&mut database, blocks),
+            KdlTopLevel::Ores(ores) => process_ores(&mut database, ores),
+            KdlTopLevel::Biomes(biomes) => process_biomes(&mut database, biomes),
+            KdlTopLevel::Features(features) => process_features(&mut database, features),
         }
     }
 
@@ -248,6 +909,13 @@ fn process_textures(db: &mut Database, textures: KdlTextures) {
 /// This adds the tiles to the database.
 fn process_tiles(db: &mut Database, tiles: KdlTiles) {
     for tile in tiles.tiles {
+        if db.tiles.len() >= MAX_TILES {
+            error!(
+                "Too many tiles, {:?} is past the limit of {MAX_TILES}",
+                tile.name
+            );
+            continue;
+        }
         let Some(&image_index) = db.image_names.get(&tile.texture_name) else {
             error!("Invalid image name ({:?}) for tile {:?}", tile.texture_name, tile.name);
             continue;
@@ -259,6 +927,10 @@ fn process_tiles(db: &mut Database, tiles: KdlTiles) {
         let tile_height = image_data.2 as f32;
         let pixel_width = 1.0 / image_width;
         let pixel_height = 1.0 / image_height;
+        let origin = (
+            tile.x as u32 * image_data.1 as u32,
+            tile.y as u32 * image_data.2 as u32,
+        );
         let tile_x = tile.x as f32;
         let tile_y = tile.y as f32;
         let u0 = (tile_x * tile_width + 0.5) * pixel_width;
@@ -273,96 +945,668 @@ fn process_tiles(db: &mut Database, tiles: KdlTiles) {
         );
         db.tiles.push(Tile {
             image_index,
+            origin,
             uv0: (u0, v0),
             uv1: (u1, v1),
+            layer: handle as u32,
         });
         db.tile_names.insert(tile.name, handle);
     }
 }
 
+/// Process the `materials` section of a KDL file.
+///
+/// This reads the shaders and adds the materials to the database.
+fn process_materials(db: &mut Database, materials: KdlMaterials) {
+    'materials: for material in materials.materials {
+        let Ok(source) = fs::read_to_string(get_path(&material.shader)) else {
+            error!(
+                "Unable to load shader ({:?}) for material {:?}",
+                material.shader, material.name
+            );
+            continue;
+        };
+
+        let mut textures = Vec::with_capacity(material.textures.len());
+        for texture_name in &material.textures {
+            let Some(&image_index) = db.image_names.get(texture_name) else {
+                error!(
+                    "Invalid texture name ({:?}) for material {:?}",
+                    texture_name, material.name
+                );
+                continue 'materials;
+            };
+            textures.push(image_index);
+        }
+
+        let state = PipelineState {
+            blend: match material.blend {
+                KdlBlend::Replace => BlendMode::Replace,
+                KdlBlend::Alpha => BlendMode::Alpha,
+                KdlBlend::Additive => BlendMode::Additive,
+            },
+            cull: match material.cull {
+                KdlCull::None => CullMode::None,
+                KdlCull::Back => CullMode::Back,
+                KdlCull::Front => CullMode::Front,
+            },
+            depth: match material.depth {
+                KdlDepth::None => DepthMode::None,
+                KdlDepth::Test => DepthMode::Test,
+                KdlDepth::TestAndWrite => DepthMode::TestAndWrite,
+            },
+            ..Default::default()
+        };
+
+        let handle = db.materials.len();
+        info!(
+            "Adding material ({handle}): {:?} ({:?}, {:?})",
+            material.name, material.shader, state
+        );
+        db.materials.push(Material {
+            shader: material.shader,
+            source,
+            vertex_entry: material.vertex,
+            fragment_entry: material.fragment,
+            state,
+            textures,
+            buffers: material.buffers,
+            transparent: material.transparent,
+        });
+        db.material_names.insert(material.name, handle);
+    }
+}
+
+/// Process the `models` section of a KDL file.
+///
+/// This turns the cuboids and crosses of each model into quads and adds the
+/// models to the database.
+fn process_models(db: &mut Database, models: KdlModels) {
+    'models: for model in models.models {
+        let mut quads = Vec::new();
+        let mut boxes = Vec::new();
+        for cuboid in &model.cuboids {
+            let from = [cuboid.x0, cuboid.y0, cuboid.z0];
+            let to = [cuboid.x1, cuboid.y1, cuboid.z1];
+            if (0..3).any(|axis| from[axis] > to[axis] || to[axis] > BLOCK_STEPS) {
+                error!(
+                    "Invalid cuboid {from:?}-{to:?} for model {:?}, coordinates must be from 0 to {BLOCK_STEPS}",
+                    model.name
+                );
+                continue 'models;
+            }
+
+            let faces = if cuboid.faces.is_empty() {
+                Face::ALL.map(|face| (face, None, None)).to_vec()
+            } else {
+                let mut faces = Vec::with_capacity(cuboid.faces.len());
+                for face in &cuboid.faces {
+                    let uv = match face.uv[..] {
+                        [] => None,
+                        [u0, v0, u1, v1] if u0.max(v0).max(u1).max(v1) <= BLOCK_STEPS => {
+                            Some([[u0, v1], [u1, v1], [u1, v0], [u0, v0]])
+                        }
+                        _ => {
+                            error!(
+                                "Invalid texture rectangle {:?} for model {:?}, it must be 4 values from 0 to {BLOCK_STEPS}",
+                                face.uv, model.name
+                            );
+                            continue 'models;
+                        }
+                    };
+                    let tile = match &face.tile {
+                        Some(name) => {
+                            let Some(&tile) = db.tile_names.get(name) else {
+                                error!("Invalid tile name ({name:?}) for model {:?}", model.name);
+                                continue 'models;
+                            };
+                            Some(tile)
+                        }
+                        None => None,
+                    };
+                    faces.push((face.side.into(), uv, tile));
+                }
+                faces
+            };
+
+            for (face, uvs, tile) in faces {
+                if let Some(quad) = ModelQuad::cuboid_face(from, to, face, uvs, tile) {
+                    quads.push(quad);
+                }
+            }
+            if (0..3).all(|axis| from[axis] < to[axis]) {
+                boxes.push([from, to]);
+            }
+        }
+
+        for cross in &model.crosses {
+            let tile = match &cross.tile {
+                Some(name) => {
+                    let Some(&tile) = db.tile_names.get(name) else {
+                        error!("Invalid tile name ({name:?}) for model {:?}", model.name);
+                        continue 'models;
+                    };
+                    Some(tile)
+                }
+                None => None,
+            };
+            quads.extend(ModelQuad::cross(tile));
+        }
+
+        let handle = db.models.len();
+        info!(
+            "Adding model ({handle}): {:?} ({} quads, {} boxes)",
+            model.name,
+            quads.len(),
+            boxes.len()
+        );
+        db.models.push(Model::new(quads).with_boxes(boxes));
+        db.model_names.insert(model.name, handle);
+    }
+}
+
+impl From<KdlFace> for Face {
+    fn from(face: KdlFace) -> Self {
+        match face {
+            KdlFace::Front => Face::Front,
+            KdlFace::Back => Face::Back,
+            KdlFace::Left => Face::Left,
+            KdlFace::Right => Face::Right,
+            KdlFace::Top => Face::Top,
+            KdlFace::Bottom => Face::Bottom,
+        }
+    }
+}
+
 /// Process the `blocks` section of a KDL file.
 ///
-/// This adds the blocks to the database.
+/// This adds the block types to the database, with a block for each of
+/// their states.
 fn process_blocks(db: &mut Database, blocks: KdlBlocks) {
-    fn on_tile<F>(db: &mut Database, possible_tile: Option<String>, f: F)
-    where
-        F: FnOnce(usize),
-    {
-        if let Some(name) = possible_tile {
-            if let Some(tile) = db.tile_names.get(&name).cloned() {
-                f(tile);
+    // The turned copies of models already added to the database.
+    let mut rotated_models = HashMap::new();
+
+    for block in blocks.blocks {
+        let Some(&material) = db.material_names.get(&block.material) else {
+            error!(
+                "Invalid material name ({:?}) for block {:?}",
+                block.material, block.name
+            );
+            continue;
+        };
+        let model = match &block.model {
+            Some(name) => {
+                let Some(&model) = db.model_names.get(name) else {
+                    error!("Invalid model name ({name:?}) for block {:?}", block.name);
+                    continue;
+                };
+                Some(model)
             }
+            None => None,
+        };
+        let Some(properties) = block_properties(&block, &db.materials[material]) else {
+            continue;
+        };
+
+        // Deal with all the sides, in the order of `Face::ALL`.
+        let mut tiles = [None; 6];
+        area_tiles(db, &block.areas, &mut tiles);
+        let [Some(front), Some(back), Some(left), Some(right), Some(top), Some(bottom)] = tiles
+        else {
+            error!("Block type `{}` is missing a side definition", block.name);
+            continue;
+        };
+        let tiles = [front, back, left, right, top, bottom];
+
+        let Some(block_type) = block_type(db, &block) else {
+            continue;
+        };
+        let Some(variants) = block_variants(db, &block, &block_type) else {
+            continue;
+        };
+        let state_count = block_type.state_count();
+        if db.blocks.len() + state_count >= u16::MAX as usize {
+            error!(
+                "Too many block states, {:?} with {state_count} states is past the limit",
+                block.name
+            );
+            continue;
         }
+
+        // Add a block for each state to the database.
+        let handle = db.block_types.len();
+        info!(
+            "Adding block ({handle}): {:?} ({:?}) [{:?}, {:?}] {:?}, {state_count} states",
+            block.name, tiles, block.material, block.model, properties
+        );
+        for state in 0..state_count {
+            let values = block_type.state_values(state);
+            let mut state_tiles = tiles.map(Some);
+            let mut state_model = model;
+            for (conditions, areas, variant_model) in &variants {
+                if conditions.iter().all(|&(p, v)| values[p] == v) {
+                    area_tiles(db, areas, &mut state_tiles);
+                    state_model = variant_model.or(state_model);
+                }
+            }
+
+            let rotation = block_type.rotation(&values);
+            let [front, back, left, right, top, bottom] =
+                rotation.tiles(state_tiles.map(|tile| tile.unwrap_or_default()));
+            let model = state_model.map(|model| {
+                if rotation == Rotation::IDENTITY {
+                    return model;
+                }
+                *rotated_models.entry((model, rotation)).or_insert_with(|| {
+                    let rotated = db.models[model].rotated(rotation);
+                    db.models.push(rotated);
+                    db.models.len() - 1
+                })
+            });
+            db.blocks.push(Block {
+                front,
+                back,
+                left,
+                right,
+                top,
+                bottom,
+                material,
+                properties: properties.clone(),
+                model,
+                block_type: handle,
+            });
+        }
+        db.block_types.push(block_type);
+        db.block_names.insert(block.name, handle);
     }
+}
 
-    for block in blocks.blocks {
-        // Deal with all the sides
-        let mut front = None;
-        let mut back = None;
-        let mut left = None;
-        let mut right = None;
-        let mut top = None;
-        let mut bottom = None;
-
-        // Deal with the `tiles` attribute and set all the sides to the same tile index.
-        on_tile(db, block.tiles, |tile| {
-            front = Some(tile);
-            back = Some(tile);
-            left = Some(tile);
-            right = Some(tile);
-            top = Some(tile);
-            bottom = Some(tile);
-        });
+/// Sets the tiles of the areas given in a `block` or `variant` entry.  The
+/// tiles are in the order of [Face::ALL] and unknown tile names are ignored.
+fn area_tiles(db: &Database, areas: &KdlAreas, tiles: &mut [Option<usize>; 6]) {
+    let tile = |name: &Option<String>| {
+        name.as_ref()
+            .and_then(|name| db.tile_names.get(name).copied())
+    };
 
-        // Deal with the `sides` attribute and set the sides to the given tile indices.
-        on_tile(db, block.sides, |tile| {
-            front = Some(tile);
-            back = Some(tile);
-            left = Some(tile);
-            right = Some(tile);
+    // The `tiles` attribute sets all the sides to the same tile index, and
+    // `sides` all but the top and bottom.  The individual sides win.
+    let all = tile(&areas.tiles);
+    let sides = tile(&areas.sides).or(all);
+    let [front, back, left, right, top, bottom] = [
+        &areas.front,
+        &areas.back,
+        &areas.left,
+        &areas.right,
+        &areas.top,
+        &areas.bottom,
+    ]
+    .map(tile);
+    let areas = [
+        front.or(sides),
+        back.or(sides),
+        left.or(sides),
+        right.or(sides),
+        top.or(all),
+        bottom.or(all),
+    ];
+    for (tile, area) in tiles.iter_mut().zip(areas) {
+        if area.is_some() {
+            *tile = area;
+        }
+    }
+}
+
+/// Works out the type of a block from its `state` entries and placement
+/// rule.  Invalid states are reported and return `None`.
+fn block_type(db: &Database, block: &KdlBlock) -> Option<BlockType> {
+    let mut properties: Vec<StateProperty> = Vec::with_capacity(block.states.len());
+    for state in &block.states {
+        let valid = |value: &String| match state.name.as_str() {
+            "axis" => ["x", "y", "z"].contains(&value.as_str()),
+            "facing" => compass_face(value).is_some(),
+            _ => true,
+        };
+        let repeated = |(i, value): (usize, &String)| state.values[..i].contains(value);
+        if state.values.is_empty()
+            || !state.values.iter().all(valid)
+            || state.values.iter().enumerate().any(repeated)
+            || properties.iter().any(|p| p.name == state.name)
+        {
+            error!(
+                "Invalid state {:?} {:?} for block {:?}",
+                state.name, state.values, block.name
+            );
+            return None;
+        }
+        properties.push(StateProperty {
+            name: state.name.clone(),
+            values: state.values.clone(),
         });
+    }
+
+    let placement = match block.placement {
+        KdlPlacement::Default => Placement::Default,
+        KdlPlacement::Axis => Placement::Axis,
+        KdlPlacement::Facing => Placement::Facing,
+    };
+    let needs = match placement {
+        Placement::Default => None,
+        Placement::Axis => Some("axis"),
+        Placement::Facing => Some("facing"),
+    };
+    if let Some(needs) = needs.filter(|&needs| !properties.iter().any(|p| p.name == needs)) {
+        error!(
+            "Block {:?} is placed by {needs:?} but has no {needs:?} state",
+            block.name
+        );
+        return None;
+    }
+
+    Some(BlockType {
+        name: block.name.clone(),
+        first: db.blocks.len(),
+        properties,
+        placement,
+    })
+}
+
+/// The conditions, tiles and model of a variant of a block.  Each condition
+/// is the index of a state property and the index of its value.
+type Variant<'a> = (Vec<(usize, usize)>, &'a KdlAreas, Option<usize>);
+
+/// Works out which states each `variant` entry of a block applies to.
+/// Invalid variants are reported and return `None`.
+fn block_variants<'a>(
+    db: &Database,
+    block: &'a KdlBlock,
+    block_type: &BlockType,
+) -> Option<Vec<Variant<'a>>> {
+    let mut variants = Vec::with_capacity(block.variants.len());
+    for variant in &block.variants {
+        let conditions = variant
+            .conditions
+            .split(',')
+            .map(|condition| {
+                let (name, value) = condition.trim().split_once('=')?;
+                let property = block_type.property_index(name)?;
+                let value = block_type.properties[property]
+                    .values
+                    .iter()
+                    .position(|v| v == value)?;
+                Some((property, value))
+            })
+            .collect::<Option<Vec<_>>>();
+        let Some(conditions) = conditions else {
+            error!(
+                "Invalid variant {:?} for block {:?}",
+                variant.conditions, block.name
+            );
+            return None;
+        };
+
+        let model = match &variant.model {
+            Some(name) => {
+                let Some(&model) = db.model_names.get(name) else {
+                    error!("Invalid model name ({name:?}) for block {:?}", block.name);
+                    return None;
+                };
+                Some(model)
+            }
+            None => None,
+        };
+        variants.push((conditions, &variant.areas, model));
+    }
+    Some(variants)
+}
 
-        // Deal with the individual sides.
-        on_tile(db, block.front, |tile| front = Some(tile));
-        on_tile(db, block.back, |tile| back = Some(tile));
-        on_tile(db, block.left, |tile| left = Some(tile));
-        on_tile(db, block.right, |tile| right = Some(tile));
-        on_tile(db, block.top, |tile| top = Some(tile));
-        on_tile(db, block.bottom, |tile| bottom = Some(tile));
-
-        // Handle any missing sides.
-        if front.is_none()
-            || back.is_none()
-            || left.is_none()
-            || right.is_none()
-            || top.is_none()
-            || bottom.is_none()
+/// Process the `ores` section of a KDL file.
+///
+/// This adds the ores to the database.
+fn process_ores(db: &mut Database, ores: KdlOres) {
+    for ore in ores.ores {
+        let (Some(block), Some(replaces)) = (db.block_id(&ore.block), db.block_id(&ore.replaces))
+        else {
+            error!(
+                "Invalid block name ({:?} or {:?}) for ore",
+                ore.block, ore.replaces
+            );
+            continue;
+        };
+        if !(1..=MAX_VEIN_SIZE).contains(&ore.size)
+            || ore.min_height > ore.max_height
+            || !ore.frequency.is_finite()
+            || ore.frequency < 0.0
         {
-            error!("Block type `{}` is missing a side definition", block.name);
+            error!(
+                "Invalid size ({}), heights ({} to {}) or frequency ({}) for ore {:?}",
+                ore.size, ore.min_height, ore.max_height, ore.frequency, ore.block
+            );
             continue;
         }
 
-        let front = front.unwrap();
-        let back = back.unwrap();
-        let left = left.unwrap();
-        let right = right.unwrap();
-        let top = top.unwrap();
-        let bottom = bottom.unwrap();
+        info!(
+            "Adding ore: {:?} (size {}, {} to {}, {} per chunk)",
+            ore.block, ore.size, ore.min_height, ore.max_height, ore.frequency
+        );
+        db.ores.push(Ore {
+            block,
+            replaces,
+            size: ore.size,
+            min_height: ore.min_height,
+            max_height: ore.max_height,
+            frequency: ore.frequency,
+        });
+    }
+}
+
+/// Process the `biomes` section of a KDL file.
+///
+/// This adds the biomes to the database.
+fn process_biomes(db: &mut Database, biomes: KdlBiomes) {
+    for biome in biomes.biomes {
+        if db.biomes.len() >= MAX_BIOMES {
+            error!("Too many biomes, {:?} is not added", biome.name);
+            continue;
+        }
+        let (Some(surface), Some(filler)) =
+            (db.block_id(&biome.surface), db.block_id(&biome.filler))
+        else {
+            error!(
+                "Invalid block name ({:?} or {:?}) for biome {:?}",
+                biome.surface, biome.filler, biome.name
+            );
+            continue;
+        };
+        let Some(grass_tint) = parse_colour(&biome.grass_tint) else {
+            error!(
+                "Invalid grass tint ({:?}) for biome {:?}, it must be \"#rrggbb\"",
+                biome.grass_tint, biome.name
+            );
+            continue;
+        };
+        let climate = 0.0..=1.0;
+        if ![
+            biome.min_temperature,
+            biome.max_temperature,
+            biome.min_humidity,
+            biome.max_humidity,
+            biome.tree_density,
+        ]
+        .iter()
+        .all(|value| climate.contains(value))
+            || biome.min_temperature > biome.max_temperature
+            || biome.min_humidity > biome.max_humidity
+            || !biome.height.is_finite()
+            || !(biome.hilliness >= 0.0 && biome.hilliness.is_finite())
+        {
+            error!(
+                "Invalid climate, tree density or height for biome {:?}",
+                biome.name
+            );
+            continue;
+        }
 
-        // Add the block to the database.
-        let handle = db.blocks.len();
         info!(
-            "Adding block ({handle}): {:?} ({:?}, {:?}, {:?}, {:?}, {:?}, {:?})",
-            block.name, front, back, left, right, top, bottom
+            "Adding biome ({}): {:?} (temperature {} to {}, humidity {} to {})",
+            db.biomes.len(),
+            biome.name,
+            biome.min_temperature,
+            biome.max_temperature,
+            biome.min_humidity,
+            biome.max_humidity
         );
-        db.blocks.push(Block {
-            front,
-            back,
-            left,
-            right,
-            top,
-            bottom,
+        db.biome_names.insert(biome.name.clone(), db.biomes.len());
+        db.biomes.push(Biome {
+            name: biome.name,
+            temperature: (biome.min_temperature, biome.max_temperature),
+            humidity: (biome.min_humidity, biome.max_humidity),
+            surface,
+            filler,
+            tree_density: biome.tree_density,
+            grass_tint,
+            height: biome.height,
+            hilliness: biome.hilliness,
         });
-        db.block_names.insert(block.name, handle);
     }
 }
+
+/// Process the `features` section of a KDL file.
+///
+/// This adds the features to the database.
+fn process_features(db: &mut Database, features: KdlFeatures) {
+    for feature in features.features {
+        let Some(shape) = feature_shape(db, &feature) else {
+            continue;
+        };
+        if shape.radius() > MAX_FEATURE_RADIUS
+            || feature
+                .density
+                .is_some_and(|density| !(0.0..=1.0).contains(&density))
+        {
+            error!(
+                "Invalid radius ({}) or density ({:?}) for feature {:?}",
+                feature.radius, feature.density, feature.name
+            );
+            continue;
+        }
+        let biomes = feature
+            .biomes
+            .iter()
+            .flat_map(|biomes| biomes.split(','))
+            .map(|name| db.biome_index(name.trim()).ok_or(name))
+            .collect::<Result<Vec<_>, _>>();
+        let Ok(biomes) = biomes else {
+            error!(
+                "Invalid biome names {:?} for feature {:?}",
+                feature.biomes, feature.name
+            );
+            continue;
+        };
+
+        info!("Adding feature: {:?} ({:?})", feature.name, feature.kind);
+        db.features.push(Feature {
+            name: feature.name,
+            shape,
+            density: feature.density,
+            biomes,
+        });
+    }
+}
+
+/// Works out the shape of a feature from its kind and blocks.  Missing or
+/// invalid values are reported and return `None`.
+fn feature_shape(db: &Database, feature: &KdlFeature) -> Option<FeatureShape> {
+    let block = |name: &Option<String>, property: &str| {
+        let id = name.as_deref().and_then(|name| db.block_id(name));
+        if id.is_none() {
+            error!(
+                "Invalid or missing {property} block ({:?}) for feature {:?}",
+                name, feature.name
+            );
+        }
+        id
+    };
+
+    match feature.kind {
+        KdlFeatureKind::Tree => {
+            if feature.min_trunk == 0 || feature.min_trunk > feature.max_trunk {
+                error!(
+                    "Invalid trunk heights ({} to {}) for feature {:?}",
+                    feature.min_trunk, feature.max_trunk, feature.name
+                );
+                return None;
+            }
+            Some(FeatureShape::Tree {
+                trunk: block(&feature.trunk, "trunk")?,
+                leaves: block(&feature.leaves, "leaves")?,
+                height: (feature.min_trunk, feature.max_trunk),
+                radius: feature.radius,
+            })
+        }
+        KdlFeatureKind::Boulder => Some(FeatureShape::Boulder {
+            block: block(&feature.block, "boulder")?,
+            radius: feature.radius,
+        }),
+        KdlFeatureKind::Plant => Some(FeatureShape::Plant {
+            block: block(&feature.block, "plant")?,
+        }),
+    }
+}
+
+/// Parses a colour of the form `#rrggbb` into red, green and blue from 0 to
+/// 1.
+fn parse_colour(colour: &str) -> Option<[f32; 3]> {
+    let hex = colour.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some([16, 8, 0].map(|shift| ((value >> shift) & 0xff) as f32 / 255.0))
+}
+
+/// Works out the properties of a block, filling in the defaults that depend
+/// on its material.  Invalid values are reported and return `None`.
+fn block_properties(block: &KdlBlock, material: &Material) -> Option<BlockProperties> {
+    let translucent = block
+        .translucent
+        .unwrap_or(material.state.blend == BlendMode::Alpha);
+    let transparent = translucent || block.transparent.unwrap_or(material.transparent);
+    let cube = block.model.is_none();
+    let light_opacity = block
+        .light_opacity
+        .unwrap_or(match (transparent || !cube, translucent) {
+            (_, true) => 1,
+            (true, false) => 0,
+            (false, false) => MAX_LIGHT,
+        });
+
+    for (name, level) in [
+        ("light-emission", block.light_emission),
+        ("light-opacity", light_opacity),
+    ] {
+        if level > MAX_LIGHT {
+            error!(
+                "Invalid {name} ({level}) for block {:?}, it must be from 0 to {MAX_LIGHT}",
+                block.name
+            );
+            return None;
+        }
+    }
+    if block.hardness < 0.0 || !(0.0..=1.0).contains(&block.friction) {
+        error!(
+            "Invalid hardness ({}) or friction ({}) for block {:?}",
+            block.hardness, block.friction, block.name
+        );
+        return None;
+    }
+
+    Some(BlockProperties {
+        solid: block.solid,
+        transparent,
+        translucent,
+        occludes: block.occludes.unwrap_or(cube && !transparent),
+        light_emission: block.light_emission,
+        light_opacity,
+        hardness: block.hardness,
+        friction: block.friction,
+        replaceable: block.replaceable,
+    })
+}
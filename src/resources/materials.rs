@@ -0,0 +1,124 @@
+use std::borrow::Cow;
+
+use gfx::{BindGroup, Buffer, BufferKind, GfxError, PipelineDesc, PipelineHandle, Screen, Texture};
+use tracing::info;
+use wgpu::{ShaderModuleDescriptor, ShaderSource, TextureViewDimension, VertexBufferLayout};
+
+use super::{Database, TileTextures};
+
+/// A material from the database that is ready to draw with.
+pub struct RenderMaterial {
+    /// The handle of the material's pipeline in the screen's cache.
+    pub pipeline: PipelineHandle,
+
+    /// The textures of the material, to be bound at index 0.  This is `None`
+    /// if the material does not use any textures.
+    pub bind_group: Option<BindGroup>,
+
+    /// The buffers of the material, to be bound at index 1.  This is `None`
+    /// if the material does not use any buffers.
+    pub buffer_bind_group: Option<BindGroup>,
+}
+
+impl Database {
+    /// Registers all the materials in the database with the screen.
+    ///
+    /// Each material is registered under its name and its pipeline is built
+    /// with the blending, culling and depth state from the KDL file.
+    ///
+    /// The textures of a material are bound as texture arrays.  With the
+    /// atlas backend each texture is the atlas it names.  With the array
+    /// backend every texture is the single array holding all the tiles.
+    ///
+    /// The buffers of a material are looked up by name in `buffers`, which
+    /// holds every buffer the game provides (such as the tile table).  Every
+    /// material reads its vertices from buffers with the given `layouts`.
+    ///
+    /// The returned materials are in the same order as the database's
+    /// materials, so a block's `material` index can be used to look them up.
+    pub fn register_materials(
+        &self,
+        screen: &mut Screen,
+        tile_textures: &TileTextures,
+        buffers: &[(&str, &Buffer)],
+        layouts: &[VertexBufferLayout<'static>],
+    ) -> Result<Vec<RenderMaterial>, GfxError> {
+        let mut names = self.material_names.iter().collect::<Vec<_>>();
+        names.sort_by_key(|(_, &index)| index);
+
+        names
+            .into_iter()
+            .map(|(name, &index)| {
+                let material = &self.materials[index];
+                info!("Registering material: {:?} ({:?})", name, material.shader);
+
+                let shader = ShaderModuleDescriptor {
+                    label: Some(name),
+                    source: ShaderSource::Wgsl(Cow::Borrowed(&material.source)),
+                };
+                let material_buffers = material
+                    .buffers
+                    .iter()
+                    .map(|buffer_name| {
+                        buffers
+                            .iter()
+                            .find(|(name, _)| name == buffer_name)
+                            .map(|&(_, buffer)| buffer)
+                            .ok_or_else(|| GfxError::UnknownBuffer(buffer_name.clone()))
+                    })
+                    .collect::<Result<Vec<&Buffer>, GfxError>>()?;
+
+                let gpu_material = layouts.iter().fold(
+                    screen.create_material(
+                        shader,
+                        &material.vertex_entry,
+                        &material.fragment_entry,
+                    ),
+                    |gpu_material, layout| gpu_material.add_buffer_layout(layout.clone()),
+                );
+                let gpu_material = material
+                    .textures
+                    .iter()
+                    .fold(gpu_material, |gpu_material, _| {
+                        gpu_material.add_texture(TextureViewDimension::D2Array)
+                    });
+                let gpu_material =
+                    material_buffers
+                        .iter()
+                        .fold(gpu_material, |gpu_material, buffer| {
+                            gpu_material.add_buffer(buffer.kind().unwrap_or(BufferKind::Storage))
+                        });
+
+                let textures = material
+                    .textures
+                    .iter()
+                    .map(|&image_index| match tile_textures {
+                        TileTextures::Atlases(atlases) => &atlases[image_index],
+                        TileTextures::Array(array) => array,
+                    })
+                    .collect::<Vec<&Texture>>();
+                let bind_group = if textures.is_empty() {
+                    None
+                } else {
+                    Some(screen.create_texture_bind_group(name, &gpu_material, &textures)?)
+                };
+
+                let buffer_bind_group = if material_buffers.is_empty() {
+                    None
+                } else {
+                    Some(screen.create_buffer_bind_group(name, &gpu_material, &material_buffers)?)
+                };
+
+                screen.register_material(name, gpu_material);
+                let pipeline =
+                    screen.pipeline(&PipelineDesc::new(name).state(material.state.clone()))?;
+
+                Ok(RenderMaterial {
+                    pipeline,
+                    bind_group,
+                    buffer_bind_group,
+                })
+            })
+            .collect()
+    }
+}
@@ -1,5 +1,11 @@
 mod database;
 mod loader;
+mod materials;
+mod states;
+mod textures;
 
 pub use database::*;
 pub use loader::*;
+pub use materials::*;
+pub use states::*;
+pub use textures::*;
@@ -0,0 +1,393 @@
+use crate::world::{Face, BLOCK_STEPS};
+
+use super::{Model, ModelQuad};
+
+/// A type of block with all its states, such as a log that can lie along any
+/// axis.
+///
+/// Every combination of state values is a block of its own in the `blocks`
+/// vector of the `Database`, with its own ID, tiles and model.  The states of
+/// a type are stored one after the other from `first`, with the last
+/// property changing fastest.  The first state, with the first value of every
+/// property, is the default.
+pub struct BlockType {
+    /// The name of the block type.
+    pub(crate) name: String,
+
+    /// The index of the type's first state in the `blocks` vector.
+    pub(crate) first: usize,
+
+    /// The state properties of the type, in the order they were declared.
+    pub(crate) properties: Vec<StateProperty>,
+
+    /// How the state of a newly placed block is chosen.
+    pub(crate) placement: Placement,
+}
+
+/// A property of a block's state and the values it can have, such as
+/// `open=false|true`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateProperty {
+    /// The name of the property.
+    pub(crate) name: String,
+
+    /// The values the property can have.  The first is the default.
+    pub(crate) values: Vec<String>,
+}
+
+/// How the state of a block is chosen when it is placed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Placement {
+    /// The block is placed in its default state.
+    #[default]
+    Default,
+
+    /// The block's `axis` property is set to the axis the player is looking
+    /// along, like a log.
+    Axis,
+
+    /// The block's `facing` property is set so that its front faces the
+    /// player, like a furnace.
+    Facing,
+}
+
+/// A rotation of a block by quarter turns, as a matrix of -1, 0 and 1.
+///
+/// Block states with an `axis` or `facing` property are drawn rotated: the
+/// tiles move to the rotated faces and the model's quads are turned about
+/// the middle of the block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rotation([[i32; 3]; 3]);
+
+impl BlockType {
+    /// Returns the name of the block type.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the state properties of the type.
+    pub fn properties(&self) -> &[StateProperty] {
+        &self.properties
+    }
+
+    /// Returns how the state of a newly placed block is chosen.
+    pub fn placement(&self) -> Placement {
+        self.placement
+    }
+
+    /// Returns the number of states the type has.
+    pub fn state_count(&self) -> usize {
+        self.properties.iter().map(|p| p.values.len()).product()
+    }
+
+    /// Returns the index of a state among the type's states.
+    ///
+    /// # Parameters
+    ///
+    /// * `values` - The index of the value of each property, in the order of
+    ///   [BlockType::properties].
+    ///
+    pub fn state_index(&self, values: &[usize]) -> usize {
+        debug_assert_eq!(values.len(), self.properties.len());
+        self.properties
+            .iter()
+            .zip(values)
+            .fold(0, |index, (property, &value)| {
+                index * property.values.len() + value
+            })
+    }
+
+    /// Returns the index of the value of each property for a state, the
+    /// reverse of [BlockType::state_index].
+    pub fn state_values(&self, mut index: usize) -> Vec<usize> {
+        let mut values = vec![0; self.properties.len()];
+        for (value, property) in values.iter_mut().zip(&self.properties).rev() {
+            *value = index % property.values.len();
+            index /= property.values.len();
+        }
+        values
+    }
+
+    /// Returns the index of a property by name.
+    pub fn property_index(&self, name: &str) -> Option<usize> {
+        self.properties.iter().position(|p| p.name == name)
+    }
+
+    /// Works out the rotation of a state from its `axis` and `facing`
+    /// properties.
+    ///
+    /// # Notes
+    ///
+    /// An `axis` of `x` or `z` turns the block's top to point along that
+    /// axis.  A `facing` turns the block's front, which faces south (+z)
+    /// unrotated, to face that way.  If a block has both, the axis is
+    /// applied first.
+    ///
+    pub fn rotation(&self, values: &[usize]) -> Rotation {
+        let value = |name: &str| {
+            let index = self.property_index(name)?;
+            Some(self.properties[index].values[values[index]].as_str())
+        };
+
+        let axis = match value("axis") {
+            Some("x") => Rotation::about_z(-1),
+            Some("z") => Rotation::about_x(1),
+            _ => Rotation::IDENTITY,
+        };
+        let facing = match value("facing").and_then(compass_face) {
+            Some(Face::Right) => Rotation::about_y(1),
+            Some(Face::Back) => Rotation::about_y(2),
+            Some(Face::Left) => Rotation::about_y(-1),
+            Some(Face::Top) => Rotation::about_x(-1),
+            Some(Face::Bottom) => Rotation::about_x(1),
+            _ => Rotation::IDENTITY,
+        };
+        axis.then(facing)
+    }
+}
+
+impl StateProperty {
+    /// Returns the name of the property.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the values the property can have.
+    pub fn values(&self) -> &[String] {
+        &self.values
+    }
+}
+
+impl Rotation {
+    /// No rotation at all.
+    pub const IDENTITY: Rotation = Rotation([[1, 0, 0], [0, 1, 0], [0, 0, 1]]);
+
+    /// Returns a rotation by a number of quarter turns about the x axis,
+    /// turning +y towards +z.
+    pub fn about_x(turns: i32) -> Rotation {
+        let (c, s) = quarter_turn(turns);
+        Rotation([[1, 0, 0], [0, c, -s], [0, s, c]])
+    }
+
+    /// Returns a rotation by a number of quarter turns about the y axis,
+    /// turning +z towards +x.
+    pub fn about_y(turns: i32) -> Rotation {
+        let (c, s) = quarter_turn(turns);
+        Rotation([[c, 0, s], [0, 1, 0], [-s, 0, c]])
+    }
+
+    /// Returns a rotation by a number of quarter turns about the z axis,
+    /// turning +x towards +y.
+    pub fn about_z(turns: i32) -> Rotation {
+        let (c, s) = quarter_turn(turns);
+        Rotation([[c, -s, 0], [s, c, 0], [0, 0, 1]])
+    }
+
+    /// Returns the rotation that applies this one and then another.
+    pub fn then(self, other: Rotation) -> Rotation {
+        let (a, b) = (other.0, self.0);
+        Rotation(std::array::from_fn(|row| {
+            std::array::from_fn(|col| (0..3).map(|k| a[row][k] * b[k][col]).sum())
+        }))
+    }
+
+    /// Rotates a vector.
+    pub fn vector(self, v: [i32; 3]) -> [i32; 3] {
+        self.0.map(|row| (0..3).map(|k| row[k] * v[k]).sum())
+    }
+
+    /// Returns the face that a face turns into.
+    pub fn face(self, face: Face) -> Face {
+        let (x, y, z) = face.offset();
+        let [x, y, z] = self.vector([x as i32, y as i32, z as i32]);
+        Face::ALL
+            .into_iter()
+            .find(|f| f.offset() == (x as i64, y as i64, z as i64))
+            .unwrap_or(face)
+    }
+
+    /// Rotates a point of a block, in steps, about the middle of the block.
+    pub fn point(self, point: [u32; 3]) -> [u32; 3] {
+        let steps = BLOCK_STEPS as i32;
+        let centred = point.map(|c| 2 * c as i32 - steps);
+        self.vector(centred).map(|c| ((c + steps) / 2) as u32)
+    }
+
+    /// Rotates the tiles of a cube, given in the order of [Face::ALL], so
+    /// each moves to the face its own face turns into.
+    pub fn tiles(self, tiles: [usize; 6]) -> [usize; 6] {
+        let mut rotated = tiles;
+        for face in Face::ALL {
+            rotated[self.face(face).index()] = tiles[face.index()];
+        }
+        rotated
+    }
+}
+
+impl Model {
+    /// Returns the model turned by a rotation about the middle of the
+    /// block.
+    pub fn rotated(&self, rotation: Rotation) -> Model {
+        let quads = self
+            .quads
+            .iter()
+            .map(|quad| ModelQuad {
+                face: rotation.face(quad.face),
+                corners: quad.corners.map(|corner| rotation.point(corner)),
+                uvs: quad.uvs,
+                tile: quad.tile,
+                cull: quad.cull.map(|side| rotation.face(side)),
+            })
+            .collect();
+        let boxes = self
+            .boxes
+            .iter()
+            .map(|&[from, to]| {
+                let (a, b) = (rotation.point(from), rotation.point(to));
+                [
+                    std::array::from_fn(|i| a[i].min(b[i])),
+                    std::array::from_fn(|i| a[i].max(b[i])),
+                ]
+            })
+            .collect();
+        Model::new(quads).with_boxes(boxes)
+    }
+}
+
+/// Returns the face that a compass direction of a `facing` property points
+/// to: `north` is -z, `south` +z, `east` +x and `west` -x.  `up` and `down`
+/// point along y.
+pub fn compass_face(direction: &str) -> Option<Face> {
+    match direction {
+        "north" => Some(Face::Back),
+        "south" => Some(Face::Front),
+        "east" => Some(Face::Right),
+        "west" => Some(Face::Left),
+        "up" => Some(Face::Top),
+        "down" => Some(Face::Bottom),
+        _ => None,
+    }
+}
+
+/// Returns the cosine and sine of a number of quarter turns.
+fn quarter_turn(turns: i32) -> (i32, i32) {
+    match turns.rem_euclid(4) {
+        0 => (1, 0),
+        1 => (0, 1),
+        2 => (-1, 0),
+        _ => (0, -1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::Database;
+    use crate::world::BlockId;
+
+    fn block_type(properties: &[(&str, &[&str])]) -> BlockType {
+        BlockType {
+            name: "test".into(),
+            first: 0,
+            properties: properties
+                .iter()
+                .map(|(name, values)| StateProperty {
+                    name: name.to_string(),
+                    values: values.iter().map(|v| v.to_string()).collect(),
+                })
+                .collect(),
+            placement: Placement::Default,
+        }
+    }
+
+    #[test]
+    fn state_indices_round_trip() {
+        let door = block_type(&[
+            ("facing", &["north", "east", "south", "west"]),
+            ("open", &["false", "true"]),
+        ]);
+        assert_eq!(door.state_count(), 8);
+        for index in 0..8 {
+            assert_eq!(door.state_index(&door.state_values(index)), index);
+        }
+        assert_eq!(door.state_index(&[2, 1]), 5);
+        assert_eq!(block_type(&[]).state_count(), 1);
+    }
+
+    #[test]
+    fn rotations_turn_faces() {
+        let log = block_type(&[("axis", &["y", "x", "z"])]);
+        assert_eq!(log.rotation(&[0]), Rotation::IDENTITY);
+        assert_eq!(log.rotation(&[1]).face(Face::Top), Face::Right);
+        assert_eq!(log.rotation(&[2]).face(Face::Top), Face::Front);
+
+        let furnace = block_type(&[("facing", &["north", "east", "south", "west", "up"])]);
+        let fronts = (0..5).map(|i| furnace.rotation(&[i]).face(Face::Front));
+        assert!(fronts.eq([Face::Back, Face::Right, Face::Front, Face::Left, Face::Top]));
+    }
+
+    #[test]
+    fn rotations_move_tiles_and_points() {
+        let turn = Rotation::about_y(1);
+        assert_eq!(turn.tiles([0, 1, 2, 3, 4, 5]), [2, 3, 1, 0, 4, 5]);
+        assert_eq!(turn.point([0, 0, 8]), [8, 0, 8]);
+        assert_eq!(turn.point([2, 3, 4]), [4, 3, 6]);
+        assert_eq!(turn.then(turn).then(turn).then(turn), Rotation::IDENTITY);
+
+        let step = Model::new(vec![]).with_boxes(vec![[[0, 4, 4], [8, 8, 8]]]);
+        assert_eq!(step.rotated(turn).boxes(), [[[4, 4, 0], [8, 8, 8]]]);
+    }
+
+    /// Creates a database with a furnace that can face any way but down,
+    /// and a log, with blocks for each of their states.
+    fn database() -> Database {
+        Database::test_blocks(&[])
+            .with_block_type(BlockType {
+                name: "furnace".into(),
+                placement: Placement::Facing,
+                ..block_type(&[("facing", &["north", "east", "south", "west", "up"])])
+            })
+            .with_block_type(BlockType {
+                name: "log".into(),
+                placement: Placement::Axis,
+                ..block_type(&[("axis", &["y", "x", "z"])])
+            })
+    }
+
+    #[test]
+    fn states_have_their_own_ids() {
+        let db = database();
+        let furnace = db.block_id("furnace").unwrap();
+        assert_eq!(db.state_value(furnace, "facing"), Some("north"));
+        assert_eq!(db.block_type(furnace).unwrap().name(), "furnace");
+
+        let east = db.with_state(furnace, "facing", "east").unwrap();
+        assert_eq!(east, BlockId::from_index(1));
+        assert_eq!(db.state_value(east, "facing"), Some("east"));
+        assert_eq!(db.with_state(furnace, "facing", "sideways"), None);
+        assert_eq!(db.with_state(furnace, "axis", "x"), None);
+        assert_eq!(db.block_id("log"), Some(BlockId::from_index(5)));
+    }
+
+    #[test]
+    fn placement_follows_the_player() {
+        let db = database();
+        let furnace = db.block_id("furnace").unwrap();
+        let facing = |look| {
+            let id = db.placement_state(furnace, look);
+            db.state_value(id, "facing").unwrap().to_string()
+        };
+        assert_eq!(facing([0.2, -0.1, 0.9]), "north");
+        assert_eq!(facing([-0.9, 0.0, 0.3]), "east");
+        assert_eq!(facing([0.1, -0.9, 0.3]), "up");
+
+        // It cannot face down, so looking up turns it to the nearest side.
+        assert_eq!(facing([0.1, 0.9, -0.3]), "south");
+
+        let log = db.block_id("log").unwrap();
+        let axis = |look| db.state_value(db.placement_state(log, look), "axis");
+        assert_eq!(axis([0.9, 0.1, 0.0]), Some("x"));
+        assert_eq!(axis([0.1, -0.9, 0.3]), Some("y"));
+        assert_eq!(axis([0.1, 0.2, -0.7]), Some("z"));
+    }
+}
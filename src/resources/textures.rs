@@ -0,0 +1,154 @@
+use bytemuck::{Pod, Zeroable};
+use gfx::{Buffer, GfxError, Screen, Texture};
+use image::imageops;
+use tracing::info;
+
+use super::{Database, Tile};
+
+/// Selects how the tiles in the database are stored on the GPU.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileBackend {
+    /// Each texture atlas is uploaded as it is.  A tile is addressed by its
+    /// `image_index` and its UV rectangle, which is inset by half a pixel to
+    /// stop neighbouring tiles bleeding in.
+    Atlas,
+
+    /// Every tile is copied into its own layer of a single texture array.  A
+    /// tile is addressed by its `layer`, UVs run from 0 to 1 and can wrap
+    /// across larger quads.
+    Array,
+}
+
+/// The textures holding all the tiles in the database.
+pub enum TileTextures {
+    /// One texture per atlas, in the same order as the database's images.
+    Atlases(Vec<Texture>),
+
+    /// A single texture array with one layer per tile.
+    Array(Texture),
+}
+
+/// Where a tile is found in the tile textures, as stored in the tile table.
+///
+/// The layout matches the `TileInfo` struct in the block shader, padded to
+/// the 16-byte alignment of a storage buffer array element.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+pub struct TileInfo {
+    /// The UV rectangle of the tile as `[u, v, width, height]`.
+    pub rect: [f32; 4],
+
+    /// The layer of the texture that the tile is in.
+    pub layer: u32,
+
+    _padding: [u32; 3],
+}
+
+impl Database {
+    /// Uploads all the tiles to the GPU using the given backend.
+    ///
+    /// Both backends generate mipmaps that do not mix neighbouring tiles.
+    pub fn create_tile_textures(
+        &self,
+        screen: &Screen,
+        backend: TileBackend,
+    ) -> Result<TileTextures, GfxError> {
+        match backend {
+            TileBackend::Atlas => self.create_atlases(screen).map(TileTextures::Atlases),
+            TileBackend::Array => self.create_tile_array(screen).map(TileTextures::Array),
+        }
+    }
+
+    /// Creates the tile table: a storage buffer with a [TileInfo] for every
+    /// tile, indexed by the tile numbers in the terrain vertices.
+    ///
+    /// # Parameters
+    ///
+    /// * `screen` - The screen to create the buffer on.
+    /// * `backend` - The backend that the tile textures were created with.
+    ///
+    pub fn create_tile_table(&self, screen: &Screen, backend: TileBackend) -> Buffer {
+        let table = self
+            .tiles
+            .iter()
+            .map(|tile| {
+                let (rect, layer) = tile.texture_rect(backend);
+                TileInfo {
+                    rect,
+                    layer,
+                    ..Default::default()
+                }
+            })
+            .collect::<Vec<_>>();
+        screen.create_storage_buffer("Tile table", &table)
+    }
+
+    /// Uploads each image as a texture atlas.
+    fn create_atlases(&self, screen: &Screen) -> Result<Vec<Texture>, GfxError> {
+        self.images
+            .iter()
+            .map(|(image, tile_width, tile_height)| {
+                screen.create_atlas_texture(
+                    "Block atlas",
+                    image.as_raw(),
+                    image.width(),
+                    image.height(),
+                    (*tile_width as u32, *tile_height as u32),
+                )
+            })
+            .collect()
+    }
+
+    /// Copies every tile into a layer of a single texture array.
+    ///
+    /// All the tiles must be the same size, whichever image they come from.
+    fn create_tile_array(&self, screen: &Screen) -> Result<Texture, GfxError> {
+        let mut tile_size = None;
+        let mut pixels = Vec::new();
+
+        for tile in &self.tiles {
+            let (image, tile_width, tile_height) = &self.images[tile.image_index];
+            let size = (*tile_width as u32, *tile_height as u32);
+            if *tile_size.get_or_insert(size) != size {
+                return Err(GfxError::BadTileSize);
+            }
+
+            let (x, y) = tile.origin;
+            let tile_image = imageops::crop_imm(image, x, y, size.0, size.1).to_image();
+            pixels.extend_from_slice(tile_image.as_raw());
+        }
+
+        let tile_size = tile_size.ok_or(GfxError::BadLayerCount(0))?;
+        info!(
+            "Creating tile array: {} layers of {}x{} pixels",
+            self.tiles.len(),
+            tile_size.0,
+            tile_size.1
+        );
+        screen.create_array_texture("Block tiles", &pixels, tile_size, self.tiles.len() as u32)
+    }
+}
+
+impl Tile {
+    /// Returns where the tile is found in the textures created for a backend.
+    ///
+    /// # Returns
+    ///
+    /// The UV rectangle of the tile as `[u, v, width, height]`, and the layer
+    /// of the texture that it is in.  With the array backend the rectangle
+    /// covers the whole of the tile's layer.
+    pub fn texture_rect(&self, backend: TileBackend) -> ([f32; 4], u32) {
+        match backend {
+            TileBackend::Atlas => (
+                [
+                    self.uv0.0,
+                    self.uv0.1,
+                    self.uv1.0 - self.uv0.0,
+                    self.uv1.1 - self.uv0.1,
+                ],
+                0,
+            ),
+            TileBackend::Array => ([0.0, 0.0, 1.0, 1.0], self.layer),
+        }
+    }
+}
@@ -0,0 +1,487 @@
+use std::{collections::HashMap, sync::Arc};
+
+/// The number of blocks along each side of a chunk.
+pub const CHUNK_SIZE: usize = 16;
+
+/// The number of blocks in a chunk.
+pub const CHUNK_VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+
+/// The largest number of biomes that chunks can refer to.
+pub const MAX_BIOMES: usize = 1 << 8;
+
+/// The number of bits in each word of a chunk's packed data.
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// Identifies a type of block in the world.
+///
+/// Air is not a block in the database, so it has an ID of its own.  Every
+/// other ID refers to a block in the `blocks` vector of the `Database`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BlockId(u16);
+
+impl BlockId {
+    /// The ID of an empty space.
+    pub const AIR: BlockId = BlockId(0);
+
+    /// Returns the ID of the block at the given index in the database.
+    pub const fn from_index(index: usize) -> BlockId {
+        debug_assert!(index < u16::MAX as usize);
+        BlockId(index as u16 + 1)
+    }
+
+    /// Returns the index of the block in the database, or `None` for air.
+    pub fn index(self) -> Option<usize> {
+        (self.0 as usize).checked_sub(1)
+    }
+
+    /// Returns true if this is air.
+    pub fn is_air(self) -> bool {
+        self == BlockId::AIR
+    }
+}
+
+/// The position of a block within a chunk.
+///
+/// Each coordinate is in the range `0..CHUNK_SIZE`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LocalPos {
+    pub x: usize,
+    pub y: usize,
+    pub z: usize,
+}
+
+impl LocalPos {
+    /// Creates a new local position.
+    pub fn new(x: usize, y: usize, z: usize) -> LocalPos {
+        debug_assert!(x < CHUNK_SIZE && y < CHUNK_SIZE && z < CHUNK_SIZE);
+        LocalPos { x, y, z }
+    }
+
+    /// Returns the index of the block in a chunk's storage.
+    pub(super) fn index(self) -> usize {
+        (self.y * CHUNK_SIZE + self.z) * CHUNK_SIZE + self.x
+    }
+
+    /// Returns the position of the block at an index in a chunk's storage.
+    pub(super) fn from_index(index: usize) -> LocalPos {
+        LocalPos {
+            x: index % CHUNK_SIZE,
+            y: index / (CHUNK_SIZE * CHUNK_SIZE),
+            z: (index / CHUNK_SIZE) % CHUNK_SIZE,
+        }
+    }
+}
+
+/// A cube of blocks that makes up part of the world.
+///
+/// The blocks are stored as indices into a palette of the block IDs that the
+/// chunk uses.  Each index takes as few bits as the size of the palette
+/// allows, and indices never straddle two words.  A chunk made of a single
+/// block type, such as air or stone, needs no indices at all.
+///
+/// The palette keeps a count of how many blocks use each entry.  Entries that
+/// are no longer used are reused by later blocks, and [Chunk::compact] removes
+/// them to shrink the indices again.
+///
+/// Each column of the chunk also records the index of its biome in the
+/// database.
+///
+/// Blocks loaded from a save that the database doesn't have are stored as a
+/// placeholder, and the chunk keeps the key they were saved under so that
+/// saving the chunk again doesn't lose them (see [Chunk::unknown_key]).
+#[derive(Clone, Debug)]
+pub struct Chunk {
+    /// The block IDs used by the chunk.
+    palette: Vec<BlockId>,
+
+    /// The number of blocks in the chunk using each palette entry.
+    counts: Vec<u16>,
+
+    /// The number of bits used for each palette index.  This is 0 when the
+    /// palette has a single entry.
+    bits: usize,
+
+    /// The packed palette indices of all the blocks.
+    data: Vec<u64>,
+
+    /// The biome of each column, indexed by `z * CHUNK_SIZE + x`.  This is
+    /// empty while every column is in the first biome.
+    biomes: Vec<u8>,
+
+    /// The keys of the placeholder blocks that were loaded in place of
+    /// blocks missing from the database, by index in the storage.
+    unknown: HashMap<usize, Arc<str>>,
+}
+
+impl Chunk {
+    /// Creates a new chunk full of air.
+    pub fn new() -> Chunk {
+        Chunk::filled(BlockId::AIR)
+    }
+
+    /// Creates a new chunk made of a single block type.
+    pub fn filled(block: BlockId) -> Chunk {
+        Chunk {
+            palette: vec![block],
+            counts: vec![CHUNK_VOLUME as u16],
+            bits: 0,
+            data: Vec::new(),
+            biomes: Vec::new(),
+            unknown: HashMap::new(),
+        }
+    }
+
+    /// Returns the block at a position.
+    pub fn get(&self, pos: LocalPos) -> BlockId {
+        self.palette[self.palette_index(pos.index())]
+    }
+
+    /// Sets the block at a position, forgetting the key of an unknown block
+    /// that was there.
+    ///
+    /// # Returns
+    ///
+    /// The block that was there before.
+    ///
+    pub fn set(&mut self, pos: LocalPos, block: BlockId) -> BlockId {
+        let index = pos.index();
+        if !self.unknown.is_empty() {
+            self.unknown.remove(&index);
+        }
+        let old_entry = self.palette_index(index);
+        let old_block = self.palette[old_entry];
+        if old_block == block {
+            return old_block;
+        }
+
+        // A chunk that becomes a single block type needs no indices.
+        let new_entry = self.entry_for(block);
+        self.counts[old_entry] -= 1;
+        self.counts[new_entry] += 1;
+        if self.counts[new_entry] as usize == CHUNK_VOLUME {
+            *self = Chunk {
+                biomes: std::mem::take(&mut self.biomes),
+                unknown: std::mem::take(&mut self.unknown),
+                ..Chunk::filled(block)
+            };
+        } else {
+            self.set_palette_index(index, new_entry);
+        }
+
+        old_block
+    }
+
+    /// Returns the block type of the whole chunk, if it only has one.
+    pub fn uniform(&self) -> Option<BlockId> {
+        self.palette
+            .iter()
+            .zip(&self.counts)
+            .find(|(_, &count)| count as usize == CHUNK_VOLUME)
+            .map(|(&block, _)| block)
+    }
+
+    /// Returns true if the chunk is all air.
+    pub fn is_empty(&self) -> bool {
+        self.uniform() == Some(BlockId::AIR)
+    }
+
+    /// Returns the number of bits used to store each block.
+    pub fn bits_per_block(&self) -> usize {
+        self.bits
+    }
+
+    /// Returns the number of entries in the palette, including unused ones.
+    pub fn palette_len(&self) -> usize {
+        self.palette.len()
+    }
+
+    /// Iterates over every block in the chunk with its position.
+    pub fn iter(&self) -> impl Iterator<Item = (LocalPos, BlockId)> + '_ {
+        (0..CHUNK_VOLUME).map(|index| {
+            (
+                LocalPos::from_index(index),
+                self.palette[self.palette_index(index)],
+            )
+        })
+    }
+
+    /// Iterates over the block types used in the chunk with the number of
+    /// blocks of each.
+    pub fn block_counts(&self) -> impl Iterator<Item = (BlockId, usize)> + '_ {
+        self.palette
+            .iter()
+            .zip(&self.counts)
+            .filter(|(_, &count)| count > 0)
+            .map(|(&block, &count)| (block, count as usize))
+    }
+
+    /// Removes unused palette entries and shrinks the indices to fit.
+    pub fn compact(&mut self) {
+        if self.counts.iter().all(|&count| count > 0) {
+            return;
+        }
+
+        let blocks = (0..CHUNK_VOLUME)
+            .map(|index| self.palette[self.palette_index(index)])
+            .collect::<Vec<_>>();
+        let mut chunk = Chunk::filled(blocks[0]);
+        for (index, block) in blocks.into_iter().enumerate() {
+            chunk.set(LocalPos::from_index(index), block);
+        }
+        chunk.biomes = std::mem::take(&mut self.biomes);
+        chunk.unknown = std::mem::take(&mut self.unknown);
+        *self = chunk;
+    }
+
+    /// Returns the key that the block at a position was saved under, if it
+    /// was missing from the database when it was loaded and so is a
+    /// placeholder.
+    pub fn unknown_key(&self, pos: LocalPos) -> Option<&Arc<str>> {
+        self.unknown.get(&pos.index())
+    }
+
+    /// Places a placeholder for a block that is missing from the database,
+    /// keeping the key it was saved under.
+    pub fn set_unknown(&mut self, pos: LocalPos, placeholder: BlockId, key: Arc<str>) {
+        self.set(pos, placeholder);
+        self.unknown.insert(pos.index(), key);
+    }
+
+    /// Returns the index in the database of the biome of a column.
+    pub fn biome(&self, x: usize, z: usize) -> usize {
+        debug_assert!(x < CHUNK_SIZE && z < CHUNK_SIZE);
+        self.biomes
+            .get(z * CHUNK_SIZE + x)
+            .map_or(0, |&biome| biome as usize)
+    }
+
+    /// Sets the biome of a column.
+    pub fn set_biome(&mut self, x: usize, z: usize, biome: usize) {
+        debug_assert!(x < CHUNK_SIZE && z < CHUNK_SIZE && biome < MAX_BIOMES);
+        if self.biomes.is_empty() {
+            if biome == 0 {
+                return;
+            }
+            self.biomes = vec![0; CHUNK_SIZE * CHUNK_SIZE];
+        }
+        self.biomes[z * CHUNK_SIZE + x] = biome as u8;
+    }
+
+    /// Returns the palette entry for a block, adding it to the palette if it
+    /// is not there yet.
+    fn entry_for(&mut self, block: BlockId) -> usize {
+        if let Some(entry) = self.palette.iter().position(|&b| b == block) {
+            return entry;
+        }
+
+        if let Some(entry) = self.counts.iter().position(|&count| count == 0) {
+            self.palette[entry] = block;
+            return entry;
+        }
+
+        self.palette.push(block);
+        self.counts.push(0);
+        let bits = bits_for(self.palette.len());
+        if bits != self.bits {
+            self.repack(bits);
+        }
+        self.palette.len() - 1
+    }
+
+    /// Returns the palette index of the block at an index in the storage.
+    fn palette_index(&self, index: usize) -> usize {
+        if self.bits == 0 {
+            return 0;
+        }
+        let per_word = WORD_BITS / self.bits;
+        let shift = (index % per_word) * self.bits;
+        let mask = (1 << self.bits) - 1;
+        ((self.data[index / per_word] >> shift) & mask) as usize
+    }
+
+    /// Sets the palette index of the block at an index in the storage.
+    fn set_palette_index(&mut self, index: usize, entry: usize) {
+        let per_word = WORD_BITS / self.bits;
+        let shift = (index % per_word) * self.bits;
+        let mask = ((1 << self.bits) - 1) << shift;
+        let word = &mut self.data[index / per_word];
+        *word = (*word & !mask) | ((entry as u64) << shift);
+    }
+
+    /// Stores the palette indices again using a new number of bits.
+    fn repack(&mut self, bits: usize) {
+        let entries = (0..CHUNK_VOLUME)
+            .map(|index| self.palette_index(index))
+            .collect::<Vec<_>>();
+
+        self.bits = bits;
+        self.data = vec![0; CHUNK_VOLUME.div_ceil(WORD_BITS / bits)];
+        for (index, entry) in entries.into_iter().enumerate() {
+            self.set_palette_index(index, entry);
+        }
+    }
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Chunk::new()
+    }
+}
+
+/// Returns the number of bits needed to index a palette of a given size.
+fn bits_for(palette_len: usize) -> usize {
+    match palette_len {
+        0 | 1 => 0,
+        n => (usize::BITS - (n - 1).leading_zeros()) as usize,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(index: usize) -> BlockId {
+        BlockId::from_index(index)
+    }
+
+    #[test]
+    fn block_ids() {
+        assert_eq!(BlockId::AIR.index(), None);
+        assert!(BlockId::AIR.is_air());
+        assert_eq!(block(0).index(), Some(0));
+        assert_eq!(block(41).index(), Some(41));
+        assert!(!block(0).is_air());
+    }
+
+    #[test]
+    fn local_positions_round_trip() {
+        for index in 0..CHUNK_VOLUME {
+            assert_eq!(LocalPos::from_index(index).index(), index);
+        }
+    }
+
+    #[test]
+    fn new_chunk_is_air() {
+        let chunk = Chunk::new();
+        assert!(chunk.is_empty());
+        assert_eq!(chunk.bits_per_block(), 0);
+        assert!(chunk.iter().all(|(_, b)| b == BlockId::AIR));
+    }
+
+    #[test]
+    fn filled_chunk_is_uniform() {
+        let chunk = Chunk::filled(block(1));
+        assert_eq!(chunk.uniform(), Some(block(1)));
+        assert_eq!(chunk.get(LocalPos::new(3, 4, 5)), block(1));
+        assert_eq!(chunk.bits_per_block(), 0);
+    }
+
+    #[test]
+    fn set_and_get() {
+        let mut chunk = Chunk::new();
+        let pos = LocalPos::new(1, 2, 3);
+        assert_eq!(chunk.set(pos, block(0)), BlockId::AIR);
+        assert_eq!(chunk.get(pos), block(0));
+        assert_eq!(chunk.get(LocalPos::new(3, 2, 1)), BlockId::AIR);
+        assert_eq!(chunk.set(pos, block(2)), block(0));
+        assert_eq!(chunk.get(pos), block(2));
+        assert_eq!(chunk.uniform(), None);
+    }
+
+    #[test]
+    fn bits_grow_with_palette() {
+        let mut chunk = Chunk::new();
+        for i in 0..20 {
+            chunk.set(LocalPos::from_index(i), block(i));
+        }
+        assert_eq!(chunk.palette_len(), 21);
+        assert_eq!(chunk.bits_per_block(), 5);
+        for i in 0..20 {
+            assert_eq!(chunk.get(LocalPos::from_index(i)), block(i));
+        }
+        assert_eq!(chunk.get(LocalPos::from_index(20)), BlockId::AIR);
+    }
+
+    #[test]
+    fn every_block_different() {
+        let mut chunk = Chunk::new();
+        for i in 0..CHUNK_VOLUME {
+            chunk.set(LocalPos::from_index(i), block(i));
+        }
+        assert_eq!(chunk.bits_per_block(), 13);
+        assert!(chunk.iter().all(|(pos, b)| b == block(pos.index())));
+    }
+
+    #[test]
+    fn uniform_again_drops_indices() {
+        let mut chunk = Chunk::new();
+        let pos = LocalPos::new(0, 0, 0);
+        chunk.set(pos, block(3));
+        assert_eq!(chunk.bits_per_block(), 1);
+        chunk.set(pos, BlockId::AIR);
+        assert!(chunk.is_empty());
+        assert_eq!(chunk.bits_per_block(), 0);
+        assert_eq!(chunk.palette_len(), 1);
+    }
+
+    #[test]
+    fn unused_entries_are_reused_and_compacted() {
+        let mut chunk = Chunk::new();
+        for i in 0..4 {
+            chunk.set(LocalPos::from_index(i), block(i));
+        }
+        assert_eq!(chunk.palette_len(), 5);
+        assert_eq!(chunk.bits_per_block(), 3);
+
+        // Replacing blocks 0..3 with stone leaves three entries unused, and
+        // a new block reuses one of them.
+        for i in 0..3 {
+            chunk.set(LocalPos::from_index(i), block(3));
+        }
+        chunk.set(LocalPos::from_index(10), block(9));
+        assert_eq!(chunk.palette_len(), 5);
+
+        chunk.compact();
+        assert_eq!(chunk.palette_len(), 3);
+        assert_eq!(chunk.bits_per_block(), 2);
+        for i in 0..4 {
+            assert_eq!(chunk.get(LocalPos::from_index(i)), block(3));
+        }
+        assert_eq!(chunk.get(LocalPos::from_index(10)), block(9));
+        assert_eq!(chunk.get(LocalPos::from_index(11)), BlockId::AIR);
+    }
+
+    #[test]
+    fn block_counts() {
+        let mut chunk = Chunk::new();
+        for x in 0..CHUNK_SIZE {
+            chunk.set(LocalPos::new(x, 0, 0), block(0));
+        }
+        let mut counts = chunk.block_counts().collect::<Vec<_>>();
+        counts.sort_by_key(|(b, _)| b.index());
+        assert_eq!(
+            counts,
+            vec![
+                (BlockId::AIR, CHUNK_VOLUME - CHUNK_SIZE),
+                (block(0), CHUNK_SIZE)
+            ]
+        );
+    }
+
+    #[test]
+    fn biomes_survive_block_changes() {
+        let mut chunk = Chunk::new();
+        assert_eq!(chunk.biome(3, 4), 0);
+        chunk.set_biome(3, 4, 7);
+        chunk.set_biome(15, 15, 255);
+        assert_eq!(chunk.biome(3, 4), 7);
+        assert_eq!(chunk.biome(4, 3), 0);
+
+        let pos = LocalPos::new(1, 2, 3);
+        chunk.set(pos, block(1));
+        chunk.set(pos, BlockId::AIR);
+        chunk.compact();
+        assert_eq!(chunk.biome(3, 4), 7);
+        assert_eq!(chunk.biome(15, 15), 255);
+    }
+}
@@ -0,0 +1,195 @@
+use super::{LocalPos, CHUNK_SIZE};
+
+/// The position of a block in the world.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BlockPos {
+    pub x: i64,
+    pub y: i64,
+    pub z: i64,
+}
+
+/// The position of a chunk in the world, in chunks.
+///
+/// The chunk at `(0, 0, 0)` holds the blocks from `(0, 0, 0)` to
+/// `(15, 15, 15)`, and the chunk at `(-1, -1, -1)` the blocks from
+/// `(-16, -16, -16)` to `(-1, -1, -1)`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ChunkPos {
+    pub x: i64,
+    pub y: i64,
+    pub z: i64,
+}
+
+/// One of the six faces of a block, and the direction it faces.
+///
+/// * `Right` faces +x and `Left` faces -x.
+/// * `Top` faces +y and `Bottom` faces -y.
+/// * `Front` faces +z and `Back` faces -z.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Face {
+    Front,
+    Back,
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl BlockPos {
+    /// Creates a new block position.
+    pub fn new(x: i64, y: i64, z: i64) -> BlockPos {
+        BlockPos { x, y, z }
+    }
+
+    /// Returns the position of the block containing a point in world space.
+    ///
+    /// Each block is 1 unit wide, so the block at `(0, 0, 0)` spans from
+    /// `(0.0, 0.0, 0.0)` up to (but not including) `(1.0, 1.0, 1.0)`.
+    pub fn containing(x: f64, y: f64, z: f64) -> BlockPos {
+        BlockPos {
+            x: x.floor() as i64,
+            y: y.floor() as i64,
+            z: z.floor() as i64,
+        }
+    }
+
+    /// Returns the position of the chunk that holds this block.
+    pub fn chunk(self) -> ChunkPos {
+        let size = CHUNK_SIZE as i64;
+        ChunkPos {
+            x: self.x.div_euclid(size),
+            y: self.y.div_euclid(size),
+            z: self.z.div_euclid(size),
+        }
+    }
+
+    /// Returns the position of this block within its chunk.
+    pub fn local(self) -> LocalPos {
+        let size = CHUNK_SIZE as i64;
+        LocalPos::new(
+            self.x.rem_euclid(size) as usize,
+            self.y.rem_euclid(size) as usize,
+            self.z.rem_euclid(size) as usize,
+        )
+    }
+
+    /// Returns the position of the neighbouring block on a face.
+    pub fn neighbour(self, face: Face) -> BlockPos {
+        let (dx, dy, dz) = face.offset();
+        BlockPos {
+            x: self.x + dx,
+            y: self.y + dy,
+            z: self.z + dz,
+        }
+    }
+}
+
+impl ChunkPos {
+    /// Creates a new chunk position.
+    pub fn new(x: i64, y: i64, z: i64) -> ChunkPos {
+        ChunkPos { x, y, z }
+    }
+
+    /// Returns the world position of a block in this chunk.
+    pub fn block(self, local: LocalPos) -> BlockPos {
+        let size = CHUNK_SIZE as i64;
+        BlockPos {
+            x: self.x * size + local.x as i64,
+            y: self.y * size + local.y as i64,
+            z: self.z * size + local.z as i64,
+        }
+    }
+
+    /// Returns the world position of the block at the lowest corner of this
+    /// chunk.
+    pub fn origin(self) -> BlockPos {
+        self.block(LocalPos::new(0, 0, 0))
+    }
+
+    /// Returns the position of the neighbouring chunk on a face.
+    pub fn neighbour(self, face: Face) -> ChunkPos {
+        let (dx, dy, dz) = face.offset();
+        ChunkPos {
+            x: self.x + dx,
+            y: self.y + dy,
+            z: self.z + dz,
+        }
+    }
+
+    /// Returns the square of the distance (in chunks) to another chunk.
+    pub fn distance_squared(self, other: ChunkPos) -> i64 {
+        let (dx, dy, dz) = (self.x - other.x, self.y - other.y, self.z - other.z);
+        dx * dx + dy * dy + dz * dz
+    }
+}
+
+impl Face {
+    /// All the faces of a block.
+    pub const ALL: [Face; 6] = [
+        Face::Front,
+        Face::Back,
+        Face::Left,
+        Face::Right,
+        Face::Top,
+        Face::Bottom,
+    ];
+
+    /// Returns the position of this face in [Face::ALL].
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Returns the face at a position in [Face::ALL].
+    pub fn from_index(index: usize) -> Option<Face> {
+        Face::ALL.get(index).copied()
+    }
+
+    /// Returns the step (in blocks) from a block to its neighbour on this
+    /// face.
+    pub fn offset(self) -> (i64, i64, i64) {
+        match self {
+            Face::Front => (0, 0, 1),
+            Face::Back => (0, 0, -1),
+            Face::Left => (-1, 0, 0),
+            Face::Right => (1, 0, 0),
+            Face::Top => (0, 1, 0),
+            Face::Bottom => (0, -1, 0),
+        }
+    }
+
+    /// Returns the face on the other side of a block.
+    pub fn opposite(self) -> Face {
+        match self {
+            Face::Front => Face::Back,
+            Face::Back => Face::Front,
+            Face::Left => Face::Right,
+            Face::Right => Face::Left,
+            Face::Top => Face::Bottom,
+            Face::Bottom => Face::Top,
+        }
+    }
+
+    /// Returns the axes of this face as indices (x = 0, y = 1, z = 2): the
+    /// axis it faces along, and the axes that point right and up on the face
+    /// as seen from outside.
+    pub fn axes(self) -> (usize, usize, usize) {
+        match self {
+            Face::Front | Face::Back => (2, 0, 1),
+            Face::Left | Face::Right => (0, 2, 1),
+            Face::Top | Face::Bottom => (1, 0, 2),
+        }
+    }
+
+    /// Returns the corners of this face on a unit cube, anti-clockwise as
+    /// seen from outside: bottom-left, bottom-right, top-right, top-left.
+    pub fn corners(self) -> [[u32; 3]; 4] {
+        match self {
+            Face::Front => [[0, 0, 1], [1, 0, 1], [1, 1, 1], [0, 1, 1]],
+            Face::Back => [[1, 0, 0], [0, 0, 0], [0, 1, 0], [1, 1, 0]],
+            Face::Left => [[0, 0, 0], [0, 0, 1], [0, 1, 1], [0, 1, 0]],
+            Face::Right => [[1, 0, 1], [1, 0, 0], [1, 1, 0], [1, 1, 1]],
+            Face::Top => [[0, 1, 1], [1, 1, 1], [1, 1, 0], [0, 1, 0]],
+            Face::Bottom => [[0, 0, 0], [1, 0, 0], [1, 0, 1], [0, 0, 1]],
+        }
+    }
+}
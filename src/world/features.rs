@@ -0,0 +1,670 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::Path,
+};
+
+use super::{
+    hash, region_pos, BlockId, BlockPos, ChunkPos, LocalPos, Random, TerrainGenerator, World,
+    CHUNK_SIZE,
+};
+use crate::resources::{Database, Feature, FeatureShape};
+
+/// The farthest (in blocks) that a feature can reach sideways from where it
+/// is placed.
+pub const MAX_FEATURE_RADIUS: u32 = 3;
+
+/// The size (in blocks) of the cells that trees and boulders are placed in.
+/// Each cell has at most one, which stays inside the cell, so they never
+/// overlap.
+const CELL_SIZE: i64 = 8;
+
+/// Mixed into the seed for the random numbers of the cells.
+const CELL_SALT: u64 = 0x6365_6c6c;
+
+/// Mixed into the seed for the random numbers of the plants.
+const PLANT_SALT: u64 = 0x706c_616e;
+
+/// The extension of the files that the blocks left for other chunks are
+/// saved in, one for each region.
+const FEATURES_EXTENSION: &str = "features";
+
+/// Places trees, boulders and plants on top of generated terrain.
+///
+/// Features are placed when the chunk holding their base is decorated, and
+/// they can reach into the chunks around it.  Blocks for a chunk that is
+/// loaded are written straight into it, and blocks for every other chunk are
+/// kept until that chunk is decorated.
+///
+/// # Notes
+///
+/// Where each feature goes only depends on the seed, so the same seed always
+/// gives the same features, whatever order the chunks are made in.  Feature
+/// blocks only fill air and replaceable blocks, and trees and boulders never
+/// overlap, so it doesn't matter which of two features writes a block first.
+///
+/// The blocks left for other chunks are kept after they are written, so a
+/// chunk that is unloaded and made again still gets them, until the chunk is
+/// saved and its region file holds them (see [FeaturePlacer::chunk_saved]).
+/// Chunks loaded from disk aren't decorated again, but may have been saved
+/// before a neighbour left blocks for them, so they are given the blocks
+/// waiting for them with [FeaturePlacer::apply_deferred].  The blocks still
+/// waiting are saved with the world, in a file for each region (see
+/// [FeaturePlacer::save]).
+///
+#[derive(Debug, Default)]
+pub struct FeaturePlacer {
+    /// The blocks left for each chunk by features in the chunks around it.
+    deferred: HashMap<ChunkPos, HashMap<LocalPos, BlockId>>,
+
+    /// The regions (see [region_pos]) whose waiting blocks have changed since
+    /// they were last saved.
+    changed: HashSet<(i64, i64, i64)>,
+}
+
+impl FeaturePlacer {
+    /// Creates a feature placer with no blocks waiting to be written.
+    pub fn new() -> FeaturePlacer {
+        FeaturePlacer::default()
+    }
+
+    /// Reads the blocks left for other chunks from the files written by
+    /// [FeaturePlacer::save] in a directory.  If there are none, no blocks
+    /// are waiting.  Blocks that are missing from the database are dropped.
+    pub fn load<P>(dir: P, database: &Database) -> io::Result<FeaturePlacer>
+    where
+        P: AsRef<Path>,
+    {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(FeaturePlacer::new()),
+            Err(err) => return Err(err),
+        };
+
+        let mut placer = FeaturePlacer::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == FEATURES_EXTENSION)
+            {
+                placer.read(&fs::read_to_string(path)?, database)?;
+            }
+        }
+        Ok(placer)
+    }
+
+    /// Adds the blocks in the text of a file written by
+    /// [FeaturePlacer::save].
+    fn read(&mut self, text: &str, database: &Database) -> io::Result<()> {
+        for line in text.lines() {
+            let mut fields = line.split(' ');
+            let numbers = fields
+                .by_ref()
+                .take(6)
+                .map(|field| field.parse::<i64>().ok())
+                .collect::<Option<Vec<_>>>()
+                .filter(|numbers| {
+                    numbers.len() == 6
+                        && numbers[3..]
+                            .iter()
+                            .all(|&n| (0..CHUNK_SIZE as i64).contains(&n))
+                })
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("bad feature block {line:?}"),
+                    )
+                })?;
+            let Some(block) = fields.next().and_then(|key| database.block_from_key(key)) else {
+                continue;
+            };
+            let pos = ChunkPos::new(numbers[0], numbers[1], numbers[2]);
+            let local = LocalPos::new(
+                numbers[3] as usize,
+                numbers[4] as usize,
+                numbers[5] as usize,
+            );
+            self.deferred.entry(pos).or_default().insert(local, block);
+        }
+        Ok(())
+    }
+
+    /// Writes the blocks left for other chunks into a directory, in a file
+    /// for each region with one block per line as the chunk position, the
+    /// position in the chunk and the block's name.  Only the files of regions
+    /// that have changed are written, and the files of regions with no
+    /// blocks left waiting are removed.
+    pub fn save<P>(&mut self, dir: P, database: &Database) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let mut texts = self
+            .changed
+            .iter()
+            .map(|&region| (region, String::new()))
+            .collect::<HashMap<_, _>>();
+        for (pos, blocks) in &self.deferred {
+            let Some(text) = texts.get_mut(&region_pos(*pos)) else {
+                continue;
+            };
+            for (local, &block) in blocks {
+                if let Some(key) = database.block_key(block) {
+                    *text += &format!(
+                        "{} {} {} {} {} {} {key}\n",
+                        pos.x, pos.y, pos.z, local.x, local.y, local.z
+                    );
+                }
+            }
+        }
+
+        for (region @ (x, y, z), text) in texts {
+            let path = dir
+                .as_ref()
+                .join(format!("r.{x}.{y}.{z}.{FEATURES_EXTENSION}"));
+            if !text.is_empty() {
+                fs::write(path, text)?;
+            } else if let Err(err) = fs::remove_file(path) {
+                if err.kind() != io::ErrorKind::NotFound {
+                    return Err(err);
+                }
+            }
+            self.changed.remove(&region);
+        }
+        Ok(())
+    }
+
+    /// Forgets the blocks left for a chunk once it has been saved, since the
+    /// chunk holds them from then on.  The chunk must have been loaded when
+    /// it was saved, so that the blocks were written into it.
+    pub fn chunk_saved(&mut self, pos: ChunkPos) {
+        if self.deferred.remove(&pos).is_some() {
+            self.changed.insert(region_pos(pos));
+        }
+    }
+
+    /// Places the features of a chunk that has just been generated, and
+    /// writes the blocks that features in other chunks left for it (see
+    /// [FeaturePlacer::apply_deferred]).
+    ///
+    /// # Parameters
+    ///
+    /// * `world` - The world holding the chunk.
+    /// * `generator` - The generator that made the chunk.
+    /// * `database` - The database holding the features.
+    /// * `pos` - The position of the chunk.
+    ///
+    /// # Returns
+    ///
+    /// The positions of the other loaded chunks that were changed, which
+    /// need lighting and meshing again.
+    ///
+    pub fn decorate(
+        &mut self,
+        world: &mut World,
+        generator: &TerrainGenerator,
+        database: &Database,
+        pos: ChunkPos,
+    ) -> Vec<ChunkPos> {
+        let mut writes = Vec::new();
+        self.place_cells(generator, database, pos, &mut writes);
+        self.place_plants(generator, database, pos, &mut writes);
+
+        let mut changed = Vec::new();
+        for (block_pos, block) in writes {
+            let (target, local) = (block_pos.chunk(), block_pos.local());
+            // Like blocks written straight into a chunk, the first block left
+            // for a position wins, so a tree's leaves don't replace its trunk.
+            if target != pos {
+                self.deferred
+                    .entry(target)
+                    .or_default()
+                    .entry(local)
+                    .or_insert(block);
+                self.changed.insert(region_pos(target));
+            }
+            let Some(chunk) = world.chunk_mut(target) else {
+                continue;
+            };
+            if !database.is_replaceable(chunk.get(local)) || chunk.set(local, block) == block {
+                continue;
+            }
+            if target != pos && !changed.contains(&target) {
+                changed.push(target);
+            }
+        }
+        self.apply_deferred(world, database, pos);
+        changed
+    }
+
+    /// Writes the blocks that features in other chunks left for a chunk.
+    /// Only air and replaceable blocks are written over.
+    ///
+    /// # Returns
+    ///
+    /// True if any block of the chunk was changed.  This is false if the
+    /// chunk is not loaded.
+    ///
+    pub fn apply_deferred(&self, world: &mut World, database: &Database, pos: ChunkPos) -> bool {
+        let (Some(deferred), Some(chunk)) = (self.deferred.get(&pos), world.chunk_mut(pos)) else {
+            return false;
+        };
+        let mut changed = false;
+        for (&local, &block) in deferred {
+            if database.is_replaceable(chunk.get(local)) && chunk.set(local, block) != block {
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Places the trees and boulders whose base is in a chunk.
+    fn place_cells(
+        &self,
+        generator: &TerrainGenerator,
+        database: &Database,
+        pos: ChunkPos,
+        writes: &mut Vec<(BlockPos, BlockId)>,
+    ) {
+        let origin = pos.origin();
+        let cells = CHUNK_SIZE as i64 / CELL_SIZE;
+        for cell_z in 0..cells {
+            for cell_x in 0..cells {
+                let (cell_x, cell_z) = (
+                    origin.x.div_euclid(CELL_SIZE) + cell_x,
+                    origin.z.div_euclid(CELL_SIZE) + cell_z,
+                );
+                let seed = generator.seed() ^ CELL_SALT;
+                let mut random = Random::new(hash(seed, cell_x, 0, cell_z));
+
+                // The feature is picked for the biome in the middle of the
+                // cell, and then placed where it fits inside the cell.
+                let middle = CELL_SIZE / 2;
+                let biome =
+                    generator.biome(cell_x * CELL_SIZE + middle, cell_z * CELL_SIZE + middle);
+                let roll = random.fraction();
+                let area = (CELL_SIZE * CELL_SIZE) as f32;
+                let Some(feature) = choose(database, biome, roll, area, false) else {
+                    continue;
+                };
+                let radius = feature.shape().radius() as i64;
+                let spread = (CELL_SIZE - 2 * radius) as u64;
+                let x = cell_x * CELL_SIZE + radius + random.below(spread) as i64;
+                let z = cell_z * CELL_SIZE + radius + random.below(spread) as i64;
+
+                let Some((height, _)) = generator.surface(x, z) else {
+                    continue;
+                };
+                let base = BlockPos::new(x, height + 1, z);
+                if base.chunk() == pos {
+                    shape_blocks(feature.shape(), base, &mut random, writes);
+                }
+            }
+        }
+    }
+
+    /// Places the plants whose base is in a chunk.
+    fn place_plants(
+        &self,
+        generator: &TerrainGenerator,
+        database: &Database,
+        pos: ChunkPos,
+        writes: &mut Vec<(BlockPos, BlockId)>,
+    ) {
+        let chance = database
+            .features()
+            .iter()
+            .filter(|feature| is_plant(feature))
+            .filter_map(|feature| feature.density())
+            .sum::<f32>() as f64;
+        if chance == 0.0 {
+            return;
+        }
+
+        let origin = pos.origin();
+        for z in origin.z..origin.z + CHUNK_SIZE as i64 {
+            for x in origin.x..origin.x + CHUNK_SIZE as i64 {
+                let seed = generator.seed() ^ PLANT_SALT;
+                let mut random = Random::new(hash(seed, x, 0, z));
+                let roll = random.fraction();
+                if roll >= chance {
+                    continue;
+                }
+                let Some((height, biome)) = generator.surface(x, z) else {
+                    continue;
+                };
+                let base = BlockPos::new(x, height + 1, z);
+                if base.chunk() != pos {
+                    continue;
+                }
+                if let Some(feature) = choose(database, biome, roll, 1.0, true) {
+                    shape_blocks(feature.shape(), base, &mut random, writes);
+                }
+            }
+        }
+    }
+}
+
+/// Returns true if a feature is a plant rather than a tree or boulder.
+fn is_plant(feature: &Feature) -> bool {
+    matches!(feature.shape(), FeatureShape::Plant { .. })
+}
+
+/// Picks the feature for a random roll, from the plants or from the trees and
+/// boulders that are found in a biome.  Each takes a share of the rolls
+/// matching its chance over an area, and rolls past them all give `None`.
+fn choose(
+    database: &Database,
+    biome: usize,
+    roll: f64,
+    area: f32,
+    plants: bool,
+) -> Option<&Feature> {
+    let info = database.biome(biome)?;
+    let mut chance = 0.0;
+    database
+        .features()
+        .iter()
+        .filter(|feature| is_plant(feature) == plants && feature.grows_in(biome))
+        .find(|feature| {
+            chance += (feature.density_in(info) * area) as f64;
+            roll < chance
+        })
+}
+
+/// Works out the blocks of a feature.
+///
+/// # Parameters
+///
+/// * `shape` - The shape of the feature.
+/// * `base` - The block above the ground where the feature is placed.
+/// * `random` - The random numbers that vary the shape.
+/// * `writes` - Where the blocks are added.
+///
+fn shape_blocks(
+    shape: FeatureShape,
+    base: BlockPos,
+    random: &mut Random,
+    writes: &mut Vec<(BlockPos, BlockId)>,
+) {
+    let at = |dx: i64, y: i64, dz: i64| BlockPos::new(base.x + dx, y, base.z + dz);
+    match shape {
+        FeatureShape::Tree {
+            trunk,
+            leaves,
+            height: (least, most),
+            radius,
+        } => {
+            let height = least as i64 + random.below((most - least + 1) as u64) as i64;
+            let top = base.y + height - 1;
+            for y in base.y..=top {
+                writes.push((at(0, y, 0), trunk));
+            }
+
+            // Two wide layers of leaves around the top of the trunk and two
+            // narrower ones above it, with some of the corners left off.
+            for dy in -1..=2 {
+                let reach = if dy <= 0 { radius } else { radius.div_ceil(2) } as i64;
+                for dz in -reach..=reach {
+                    for dx in -reach..=reach {
+                        let corner = dx.abs() == reach && dz.abs() == reach && reach > 0;
+                        if corner && (dy == 2 || random.below(2) == 0) {
+                            continue;
+                        }
+                        writes.push((at(dx, top + dy, dz), leaves));
+                    }
+                }
+            }
+        }
+        FeatureShape::Boulder { block, radius } => {
+            let radius = radius as i64;
+            for dy in -radius..=radius {
+                for dz in -radius..=radius {
+                    for dx in -radius..=radius {
+                        if dx * dx + dy * dy + dz * dz <= radius * radius + radius {
+                            writes.push((at(dx, base.y - 1 + dy, dz), block));
+                        }
+                    }
+                }
+            }
+        }
+        FeatureShape::Plant { block } => writes.push((base, block)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::BlockProperties;
+    use crate::world::{RegionStore, TestDir};
+
+    const GRASS: BlockId = BlockId::from_index(0);
+    const LOG: BlockId = BlockId::from_index(3);
+    const LEAVES: BlockId = BlockId::from_index(4);
+    const BOULDER: BlockId = BlockId::from_index(5);
+    const PLANT: BlockId = BlockId::from_index(6);
+
+    /// Creates a database with grass (block 0), dirt (1), stone (2), logs
+    /// (3), leaves (4), boulder stone (5) and a replaceable plant (6), and a
+    /// single biome with trees, boulders and plants.
+    fn database() -> Database {
+        let mut db = Database::test_blocks(&["grass", "dirt", "stone", "log", "leaves", "boulder"])
+            .with_block(
+                "plant",
+                0,
+                BlockProperties {
+                    replaceable: true,
+                    ..BlockProperties::default()
+                },
+                None,
+            )
+            .with_biome("woods", GRASS, BlockId::from_index(1));
+        db.biomes[0].tree_density = 0.008;
+        let feature = |name: &str, shape, density| Feature {
+            name: name.into(),
+            shape,
+            density,
+            biomes: vec![],
+        };
+        db.features = vec![
+            feature(
+                "tree",
+                FeatureShape::Tree {
+                    trunk: LOG,
+                    leaves: LEAVES,
+                    height: (4, 6),
+                    radius: 3,
+                },
+                None,
+            ),
+            feature(
+                "boulder",
+                FeatureShape::Boulder {
+                    block: BOULDER,
+                    radius: 2,
+                },
+                Some(0.004),
+            ),
+            feature("plant", FeatureShape::Plant { block: PLANT }, Some(0.2)),
+        ];
+        db
+    }
+
+    /// The chunks around the ground near the origin.
+    fn region() -> Vec<ChunkPos> {
+        let mut region = Vec::new();
+        for x in -2..=2 {
+            for y in -1..=1 {
+                for z in -2..=2 {
+                    region.push(ChunkPos::new(x, y, z));
+                }
+            }
+        }
+        region
+    }
+
+    /// Generates and decorates chunks one at a time, in the given order.
+    fn build(order: &[ChunkPos], db: &Database) -> (World, FeaturePlacer) {
+        let generator = TerrainGenerator::new(3, db).unwrap();
+        let mut world = World::new(0);
+        let mut placer = FeaturePlacer::new();
+        for &pos in order {
+            world.insert_chunk(pos, generator.generate(pos));
+            placer.decorate(&mut world, &generator, db, pos);
+        }
+        (world, placer)
+    }
+
+    fn count(world: &World, block: BlockId) -> usize {
+        world
+            .chunks()
+            .flat_map(|(_, chunk)| chunk.block_counts())
+            .filter(|&(id, _)| id == block)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    #[test]
+    fn features_are_the_same_in_any_order() {
+        let db = database();
+        let region = region();
+        let (forwards, placer) = build(&region, &db);
+        let reversed = region.iter().rev().copied().collect::<Vec<_>>();
+        let (backwards, _) = build(&reversed, &db);
+
+        for block in [LOG, LEAVES, BOULDER, PLANT] {
+            assert!(count(&forwards, block) > 0);
+        }
+        assert!(!placer.deferred.is_empty());
+        for &pos in &region {
+            let (a, b) = (forwards.chunk(pos).unwrap(), backwards.chunk(pos).unwrap());
+            assert!(a.iter().eq(b.iter()), "chunk {pos:?} differs");
+        }
+    }
+
+    #[test]
+    fn trees_grow_from_the_ground() {
+        let db = database();
+        let (world, _) = build(&region(), &db);
+        let mut trunks = 0;
+        for (pos, chunk) in world.chunks() {
+            for (local, id) in chunk.iter() {
+                let below = pos.block(local);
+                let below = BlockPos::new(below.x, below.y - 1, below.z);
+                match (id, world.block(below)) {
+                    (LOG, Some(under)) => {
+                        assert!(under == LOG || under == GRASS);
+                        trunks += (under == GRASS) as usize;
+                    }
+                    (PLANT, Some(under)) => assert_eq!(under, GRASS),
+                    _ => {}
+                }
+            }
+        }
+        assert!(trunks > 0);
+    }
+
+    #[test]
+    fn remade_chunks_get_their_neighbours_blocks_again() {
+        let db = database();
+        let generator = TerrainGenerator::new(3, &db).unwrap();
+        let (mut world, mut placer) = build(&region(), &db);
+        let target = *placer
+            .deferred
+            .keys()
+            .find(|pos| world.chunk(**pos).is_some())
+            .unwrap();
+        let before = world.remove_chunk(target).unwrap();
+
+        world.insert_chunk(target, generator.generate(target));
+        placer.decorate(&mut world, &generator, &db, target);
+        assert!(world.chunk(target).unwrap().iter().eq(before.iter()));
+    }
+
+    #[test]
+    fn saved_chunks_get_blocks_left_while_unloaded() {
+        let db = database();
+        let generator = TerrainGenerator::new(3, &db).unwrap();
+        let region = region();
+        let (expected, _) = build(&region, &db);
+
+        // The chunk is made and saved before its neighbours are, so none of
+        // their features reach into it while it is loaded.  Pick one that
+        // their features do reach into.
+        let made_alone = |pos| {
+            let (mut world, _) = build(&[pos], &db);
+            world.remove_chunk(pos).unwrap()
+        };
+        let target = region
+            .iter()
+            .copied()
+            .find(|&pos| {
+                !made_alone(pos)
+                    .iter()
+                    .eq(expected.chunk(pos).unwrap().iter())
+            })
+            .unwrap();
+
+        let mut world = World::new(0);
+        let mut placer = FeaturePlacer::new();
+        world.insert_chunk(target, generator.generate(target));
+        placer.decorate(&mut world, &generator, &db, target);
+        let dir = TestDir::new("features");
+        let mut regions = RegionStore::open(dir.path()).unwrap();
+        let unloaded = world.remove_chunk(target).unwrap();
+        regions.save_chunk(target, &unloaded, &db).unwrap();
+        regions.flush().unwrap();
+        placer.chunk_saved(target);
+        for &pos in region.iter().filter(|&&pos| pos != target) {
+            world.insert_chunk(pos, generator.generate(pos));
+            placer.decorate(&mut world, &generator, &db, pos);
+        }
+
+        // The blocks waiting for the chunk survive being saved and loaded.
+        placer.save(dir.path(), &db).unwrap();
+        let placer = FeaturePlacer::load(dir.path(), &db).unwrap();
+
+        let saved = RegionStore::open(dir.path())
+            .unwrap()
+            .load_chunk(target, &db)
+            .unwrap()
+            .unwrap();
+        assert!(!saved.iter().eq(expected.chunk(target).unwrap().iter()));
+        world.insert_chunk(target, saved);
+        assert!(placer.apply_deferred(&mut world, &db, target));
+        let reloaded = world.chunk(target).unwrap();
+        assert!(reloaded.iter().eq(expected.chunk(target).unwrap().iter()));
+        assert!(!placer.apply_deferred(&mut world, &db, target));
+    }
+
+    #[test]
+    fn saved_chunks_are_forgotten() {
+        let db = database();
+        let (_, mut placer) = build(&region(), &db);
+        let dir = TestDir::new("features-forget");
+        fs::create_dir_all(dir.path()).unwrap();
+        placer.save(dir.path(), &db).unwrap();
+        let files = || fs::read_dir(dir.path()).unwrap().count();
+        let regions = placer
+            .deferred
+            .keys()
+            .map(|&pos| region_pos(pos))
+            .collect::<HashSet<_>>();
+        assert_eq!(files(), regions.len());
+        assert!(placer.changed.is_empty());
+
+        // Once every chunk is saved, nothing is left waiting, and the files
+        // are removed.
+        let waiting = placer.deferred.keys().copied().collect::<Vec<_>>();
+        for pos in waiting {
+            placer.chunk_saved(pos);
+        }
+        assert!(placer.deferred.is_empty());
+        placer.save(dir.path(), &db).unwrap();
+        assert_eq!(files(), 0);
+        assert!(FeaturePlacer::load(dir.path(), &db)
+            .unwrap()
+            .deferred
+            .is_empty());
+    }
+}
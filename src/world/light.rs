@@ -0,0 +1,638 @@
+use std::collections::{HashSet, VecDeque};
+
+use super::{BlockId, BlockPos, ChunkPos, Face, LocalPos, World, CHUNK_SIZE, CHUNK_VOLUME};
+use crate::resources::Database;
+
+/// The brightest level of light.
+pub const MAX_LIGHT: u8 = 15;
+
+/// The light at a block.
+///
+/// There are two kinds of light, each from 0 (dark) to [MAX_LIGHT]: light
+/// from the sky, and light given off by blocks such as torches.  They are
+/// kept apart so that the sky can be dimmed at night without touching the
+/// block light.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Light {
+    pub sky: u8,
+    pub block: u8,
+}
+
+/// The light of every block in a chunk.
+#[derive(Clone, Debug)]
+pub struct LightMap {
+    /// The packed light of each block, in the same order as a chunk's
+    /// blocks.
+    levels: Box<[u8]>,
+}
+
+/// The result of [World::place_block].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BlockChange {
+    /// The block that was there before.
+    pub old: BlockId,
+
+    /// The chunks whose meshes need building again, because a block or the
+    /// light in them changed, or because they touch the changed block.
+    pub changed: Vec<ChunkPos>,
+}
+
+/// The two kinds of light, which spread independently.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Channel {
+    Sky,
+    Block,
+}
+
+impl Light {
+    /// No light at all.
+    pub const DARK: Light = Light { sky: 0, block: 0 };
+
+    /// Creates a new light.
+    pub fn new(sky: u8, block: u8) -> Light {
+        debug_assert!(sky <= MAX_LIGHT && block <= MAX_LIGHT);
+        Light { sky, block }
+    }
+
+    /// Packs the light into a byte, sky light in the high nibble and block
+    /// light in the low nibble.  This is the form used by terrain vertices.
+    pub fn packed(self) -> u8 {
+        self.sky << 4 | self.block
+    }
+
+    /// Unpacks a light from a byte made by [Light::packed].
+    pub fn from_packed(packed: u8) -> Light {
+        Light {
+            sky: packed >> 4,
+            block: packed & 0xf,
+        }
+    }
+
+    /// Returns the level of one kind of light.
+    fn level(self, channel: Channel) -> u8 {
+        match channel {
+            Channel::Sky => self.sky,
+            Channel::Block => self.block,
+        }
+    }
+
+    /// Returns the light with the level of one kind of light changed.
+    fn with_level(self, channel: Channel, level: u8) -> Light {
+        match channel {
+            Channel::Sky => Light { sky: level, ..self },
+            Channel::Block => Light {
+                block: level,
+                ..self
+            },
+        }
+    }
+}
+
+impl LightMap {
+    /// Creates a new light map with every block dark.
+    pub fn new() -> LightMap {
+        LightMap {
+            levels: vec![0; CHUNK_VOLUME].into_boxed_slice(),
+        }
+    }
+
+    /// Returns the light at a position.
+    pub fn get(&self, pos: LocalPos) -> Light {
+        Light::from_packed(self.levels[pos.index()])
+    }
+
+    /// Sets the light at a position.
+    pub fn set(&mut self, pos: LocalPos, light: Light) {
+        self.levels[pos.index()] = light.packed();
+    }
+}
+
+impl Default for LightMap {
+    fn default() -> Self {
+        LightMap::new()
+    }
+}
+
+impl World {
+    /// Returns the light at a position, or `None` if its chunk is not loaded.
+    pub fn light(&self, pos: BlockPos) -> Option<Light> {
+        let chunk = pos.chunk();
+        match self.light.get(&chunk) {
+            Some(map) => Some(map.get(pos.local())),
+            None => self.chunk(chunk).map(|_| Light::DARK),
+        }
+    }
+
+    /// Lights newly loaded chunks and spreads their light into the chunks
+    /// around them.
+    ///
+    /// # Parameters
+    ///
+    /// * `chunks` - The positions of the new chunks, such as the chunks
+    ///   loaded by [World::update_loaded].
+    /// * `database` - The database that the chunks' block IDs refer to.
+    ///
+    /// # Returns
+    ///
+    /// The positions of all the chunks whose light changed.
+    ///
+    /// # Notes
+    ///
+    /// The world has no fixed top, so sky light falls in from above the
+    /// highest loaded chunk of each column.  Light at full strength falls
+    /// straight down through clear blocks without fading, and every other
+    /// step (sideways, up, or down from dimmer light) dims it by one level.
+    /// Blocks dim the light spreading into them by their light opacity as
+    /// well, so opaque blocks stop it altogether.
+    ///
+    /// A new chunk on top of a lit chunk blocks the sky that fell into it, so
+    /// that light is taken away before the new chunk's light is spread.  The
+    /// opposite is not true: unloading a chunk does not let the sky into the
+    /// chunk below it until that chunk is lit again.
+    ///
+    pub fn light_chunks(&mut self, chunks: &[ChunkPos], database: &Database) -> Vec<ChunkPos> {
+        let new = chunks
+            .iter()
+            .copied()
+            .filter(|&pos| self.chunk(pos).is_some())
+            .collect::<HashSet<_>>();
+        let mut changed = HashSet::new();
+        let mut sky = VecDeque::new();
+        let mut block = VecDeque::new();
+
+        for &pos in &new {
+            self.light.remove(&pos);
+
+            // Take away the sky light that used to fall into the chunk below.
+            let below = pos.neighbour(Face::Bottom);
+            if self.chunk(below).is_some() && !new.contains(&below) {
+                let mut removal = VecDeque::new();
+                for top in border(below, Face::Top) {
+                    if self.level(top, Channel::Sky) == Some(MAX_LIGHT) {
+                        self.set_level(top, Channel::Sky, 0, &mut changed);
+                        removal.push_back((top, MAX_LIGHT));
+                    }
+                }
+                self.unspread(Channel::Sky, removal, &mut sky, database, &mut changed);
+            }
+        }
+
+        for &pos in &new {
+            // Light the sources inside the chunk: the top layer if it is open
+            // to the sky, and any blocks that give off light.
+            let Some(chunk) = self.chunk(pos) else {
+                continue;
+            };
+            let emitters = if chunk
+                .block_counts()
+                .any(|(id, _)| database.light_emission(id) > 0)
+            {
+                chunk
+                    .iter()
+                    .filter(|&(_, id)| database.light_emission(id) > 0)
+                    .map(|(local, _)| pos.block(local))
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            let sources = border(pos, Face::Top)
+                .map(|top| (Channel::Sky, top))
+                .chain(
+                    emitters
+                        .into_iter()
+                        .map(|emitter| (Channel::Block, emitter)),
+                )
+                .collect::<Vec<_>>();
+            for (channel, source) in sources {
+                let level = self.source_level(source, channel, database);
+                if level > 0 {
+                    self.set_level(source, channel, level, &mut changed);
+                    match channel {
+                        Channel::Sky => sky.push_back(source),
+                        Channel::Block => block.push_back(source),
+                    }
+                }
+            }
+
+            // Let the light of the chunks around it spread in.
+            for face in Face::ALL {
+                let neighbour = pos.neighbour(face);
+                if self.chunk(neighbour).is_some() && !new.contains(&neighbour) {
+                    let outside = border(pos, face).map(|inside| inside.neighbour(face));
+                    for outside in outside {
+                        sky.push_back(outside);
+                        block.push_back(outside);
+                    }
+                }
+            }
+        }
+
+        self.spread(Channel::Sky, sky, database, &mut changed);
+        self.spread(Channel::Block, block, database, &mut changed);
+        changed.into_iter().collect()
+    }
+
+    /// Sets a block and updates the light around it.
+    ///
+    /// # Parameters
+    ///
+    /// * `pos` - The position of the block.
+    /// * `block` - The new block.
+    /// * `database` - The database that the block IDs refer to.
+    ///
+    /// # Returns
+    ///
+    /// The block that was there before and the chunks that need meshing
+    /// again, or `None` if the block's chunk is not loaded, in which case
+    /// nothing is changed.
+    ///
+    /// # Notes
+    ///
+    /// The light that came from or through the old block is taken away,
+    /// spreading out until it meets light from other sources, which then
+    /// spreads back in.  Any light the new block gives off, or lets through,
+    /// is spread afterwards.
+    ///
+    pub fn place_block(
+        &mut self,
+        pos: BlockPos,
+        block: BlockId,
+        database: &Database,
+    ) -> Option<BlockChange> {
+        let old = self.set_block(pos, block)?;
+        let mut changed = HashSet::new();
+
+        for channel in [Channel::Sky, Channel::Block] {
+            let mut removal = VecDeque::new();
+            let mut relight = VecDeque::new();
+            let level = self.level(pos, channel).unwrap_or(0);
+            if level > 0 {
+                self.set_level(pos, channel, 0, &mut changed);
+                removal.push_back((pos, level));
+            }
+            self.unspread(channel, removal, &mut relight, database, &mut changed);
+
+            let source = self.source_level(pos, channel, database);
+            if source > 0 {
+                self.set_level(pos, channel, source, &mut changed);
+                relight.push_back(pos);
+            }
+            relight.extend(Face::ALL.map(|face| pos.neighbour(face)));
+            self.spread(channel, relight, database, &mut changed);
+        }
+
+        // The faces and ambient occlusion of the blocks around the changed
+        // block depend on it, even across chunk borders.
+        for dy in -1..=1 {
+            for dz in -1..=1 {
+                for dx in -1..=1 {
+                    let chunk = BlockPos::new(pos.x + dx, pos.y + dy, pos.z + dz).chunk();
+                    if self.chunk(chunk).is_some() {
+                        changed.insert(chunk);
+                    }
+                }
+            }
+        }
+
+        Some(BlockChange {
+            old,
+            changed: changed.into_iter().collect(),
+        })
+    }
+
+    /// Spreads light out from the blocks in a queue, which must already be
+    /// lit, until it fades away or is blocked.
+    fn spread(
+        &mut self,
+        channel: Channel,
+        mut queue: VecDeque<BlockPos>,
+        database: &Database,
+        changed: &mut HashSet<ChunkPos>,
+    ) {
+        while let Some(pos) = queue.pop_front() {
+            let Some(level) = self.level(pos, channel) else {
+                continue;
+            };
+            for face in Face::ALL {
+                // Most neighbours are already bright enough, which can be
+                // seen without looking up their blocks.
+                let next = pos.neighbour(face);
+                let Some(old) = self.level(next, channel) else {
+                    continue;
+                };
+                if old >= spread_level(channel, face, level, 0) {
+                    continue;
+                }
+
+                let next_level = spread_level(channel, face, level, self.opacity(next, database));
+                if old < next_level {
+                    self.set_level(next, channel, next_level, changed);
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+
+    /// Takes away the light that spread from the blocks in a queue.
+    ///
+    /// Each block in the queue has already been darkened, and is queued with
+    /// the level it used to have.  Every neighbour that was lit by it is
+    /// darkened in turn.  Neighbours that are brighter were lit by something
+    /// else, so they are added to `relight` to spread their light back in
+    /// afterwards, as are any light sources that were darkened.
+    fn unspread(
+        &mut self,
+        channel: Channel,
+        mut queue: VecDeque<(BlockPos, u8)>,
+        relight: &mut VecDeque<BlockPos>,
+        database: &Database,
+        changed: &mut HashSet<ChunkPos>,
+    ) {
+        while let Some((pos, level)) = queue.pop_front() {
+            for face in Face::ALL {
+                let next = pos.neighbour(face);
+                let Some(next_level) = self.level(next, channel) else {
+                    continue;
+                };
+                if next_level == 0 {
+                    continue;
+                }
+
+                let opacity = self.opacity(next, database);
+                if next_level < level || next_level == spread_level(channel, face, level, opacity) {
+                    self.set_level(next, channel, 0, changed);
+                    queue.push_back((next, next_level));
+
+                    let source = self.source_level(next, channel, database);
+                    if source > 0 {
+                        self.set_level(next, channel, source, changed);
+                        relight.push_back(next);
+                    }
+                } else {
+                    relight.push_back(next);
+                }
+            }
+        }
+    }
+
+    /// Returns the level of light that a block starts with before any light
+    /// spreads to it: the light it gives off, or full sky light if nothing
+    /// is loaded above it.
+    fn source_level(&self, pos: BlockPos, channel: Channel, database: &Database) -> u8 {
+        match channel {
+            Channel::Sky => {
+                let open_sky = pos.local().y == CHUNK_SIZE - 1
+                    && self.chunk(pos.chunk().neighbour(Face::Top)).is_none();
+                if open_sky {
+                    MAX_LIGHT.saturating_sub(self.opacity(pos, database))
+                } else {
+                    0
+                }
+            }
+            Channel::Block => self.block(pos).map_or(0, |id| database.light_emission(id)),
+        }
+    }
+
+    /// Returns how many extra levels light is dimmed by in a block.  Blocks
+    /// in chunks that are not loaded stop light.
+    fn opacity(&self, pos: BlockPos, database: &Database) -> u8 {
+        self.block(pos)
+            .map_or(MAX_LIGHT, |id| database.light_opacity(id))
+    }
+
+    /// Returns the level of one kind of light at a position, or `None` if its
+    /// chunk is not loaded.
+    fn level(&self, pos: BlockPos, channel: Channel) -> Option<u8> {
+        self.light(pos).map(|light| light.level(channel))
+    }
+
+    /// Sets the level of one kind of light at a position, and records that
+    /// its chunk changed.
+    fn set_level(
+        &mut self,
+        pos: BlockPos,
+        channel: Channel,
+        level: u8,
+        changed: &mut HashSet<ChunkPos>,
+    ) {
+        let chunk = pos.chunk();
+        let map = self.light.entry(chunk).or_default();
+        let local = pos.local();
+        map.set(local, map.get(local).with_level(channel, level));
+        changed.insert(chunk);
+    }
+}
+
+/// Returns the level of light that spreads to a neighbour on a face, given
+/// the light opacity of the neighbour.
+fn spread_level(channel: Channel, face: Face, level: u8, opacity: u8) -> u8 {
+    if channel == Channel::Sky && face == Face::Bottom && level == MAX_LIGHT && opacity == 0 {
+        MAX_LIGHT
+    } else {
+        level.saturating_sub(1 + opacity)
+    }
+}
+
+/// Iterates over the blocks of a chunk that lie against one of its faces.
+fn border(pos: ChunkPos, face: Face) -> impl Iterator<Item = BlockPos> {
+    let (dx, dy, dz) = face.offset();
+    let edge = |offset: i64| if offset < 0 { 0 } else { CHUNK_SIZE - 1 };
+    (0..CHUNK_SIZE).flat_map(move |a| {
+        (0..CHUNK_SIZE).map(move |b| {
+            let local = match face {
+                Face::Left | Face::Right => LocalPos::new(edge(dx), a, b),
+                Face::Top | Face::Bottom => LocalPos::new(a, edge(dy), b),
+                Face::Front | Face::Back => LocalPos::new(a, b, edge(dz)),
+            };
+            pos.block(local)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::BlockProperties;
+    use crate::world::Chunk;
+
+    /// Creates a database with stone (block 0), glass (block 1), a lamp
+    /// (block 2) that gives off light at level 12 and tinted glass (block 3)
+    /// that dims light by 3 extra levels.
+    fn database() -> Database {
+        let glass = BlockProperties {
+            transparent: true,
+            occludes: false,
+            light_opacity: 0,
+            ..Default::default()
+        };
+        let lamp = BlockProperties {
+            light_emission: 12,
+            ..Default::default()
+        };
+        let tinted = BlockProperties {
+            light_opacity: 3,
+            ..glass.clone()
+        };
+        Database::test_blocks(&["stone"])
+            .with_block("glass", 1, glass, None)
+            .with_block("lamp", 0, lamp, None)
+            .with_block("tinted glass", 1, tinted, None)
+    }
+
+    fn stone() -> BlockId {
+        BlockId::from_index(0)
+    }
+
+    fn glass() -> BlockId {
+        BlockId::from_index(1)
+    }
+
+    fn lamp() -> BlockId {
+        BlockId::from_index(2)
+    }
+
+    fn tinted() -> BlockId {
+        BlockId::from_index(3)
+    }
+
+    /// Creates a world of air chunks around the origin, with blocks set by a
+    /// function before it is lit.
+    fn world<F>(database: &Database, fill: F) -> World
+    where
+        F: Fn(&mut World),
+    {
+        let mut world = World::new(1);
+        let changes = world.update_loaded(ChunkPos::default(), |_| Chunk::new());
+        fill(&mut world);
+        world.light_chunks(&changes.loaded, database);
+        world
+    }
+
+    fn sky(world: &World, x: i64, y: i64, z: i64) -> u8 {
+        world.light(BlockPos::new(x, y, z)).unwrap().sky
+    }
+
+    fn block(world: &World, x: i64, y: i64, z: i64) -> u8 {
+        world.light(BlockPos::new(x, y, z)).unwrap().block
+    }
+
+    #[test]
+    fn packed_light() {
+        let light = Light::new(15, 3);
+        assert_eq!(light.packed(), 0xf3);
+        assert_eq!(Light::from_packed(0xf3), light);
+    }
+
+    #[test]
+    fn sky_falls_and_spreads_under_roofs() {
+        let database = database();
+        let world = world(&database, |world| {
+            for x in 0..16 {
+                for z in 0..16 {
+                    world.set_block(BlockPos::new(x, 10, z), stone());
+                }
+            }
+        });
+
+        assert_eq!(sky(&world, 8, 11, 8), MAX_LIGHT);
+        assert_eq!(sky(&world, 8, 10, 8), 0);
+        assert_eq!(sky(&world, -1, 0, 8), MAX_LIGHT);
+
+        // Under the roof, the light comes in sideways from the open sky
+        // beyond the edges of the chunk, 8 blocks away at the nearest.
+        assert_eq!(sky(&world, 8, 5, 8), MAX_LIGHT - 8);
+        assert_eq!(sky(&world, 0, 5, 8), MAX_LIGHT - 1);
+        assert!(world.light(BlockPos::new(8, 40, 8)).is_none());
+    }
+
+    #[test]
+    fn placing_and_removing_blocks_updates_the_sky() {
+        let database = database();
+        let mut world = world(&database, |_| {});
+        assert_eq!(sky(&world, 8, 0, 8), MAX_LIGHT);
+
+        let change = world
+            .place_block(BlockPos::new(8, 12, 8), stone(), &database)
+            .unwrap();
+        assert_eq!(change.old, BlockId::AIR);
+        assert!(change.changed.contains(&ChunkPos::new(0, 0, 0)));
+        assert_eq!(sky(&world, 8, 12, 8), 0);
+        assert_eq!(sky(&world, 8, 11, 8), MAX_LIGHT - 1);
+        assert_eq!(sky(&world, 8, -10, 8), MAX_LIGHT - 1);
+
+        // Glass lets the sky straight through.
+        world.place_block(BlockPos::new(8, 12, 8), glass(), &database);
+        assert_eq!(sky(&world, 8, 12, 8), MAX_LIGHT);
+        assert_eq!(sky(&world, 8, -10, 8), MAX_LIGHT);
+    }
+
+    #[test]
+    fn block_light_fades_with_distance() {
+        let database = database();
+        let mut world = world(&database, |world| {
+            world.set_block(BlockPos::new(8, 8, 8), lamp());
+        });
+        assert_eq!(block(&world, 8, 8, 8), 12);
+        assert_eq!(block(&world, 10, 8, 8), 10);
+        assert_eq!(block(&world, 8, 8, 13), 7);
+        assert_eq!(block(&world, 8, 8, 20), 0);
+
+        // A block in the way makes the light go around it.
+        world.place_block(BlockPos::new(9, 8, 8), stone(), &database);
+        assert_eq!(block(&world, 9, 8, 8), 0);
+        assert_eq!(block(&world, 10, 8, 8), 8);
+
+        // Taking the lamp away takes all of its light with it.
+        world.place_block(BlockPos::new(8, 8, 8), BlockId::AIR, &database);
+        assert_eq!(block(&world, 8, 8, 8), 0);
+        assert_eq!(block(&world, 10, 8, 8), 0);
+        assert_eq!(block(&world, 8, 8, 13), 0);
+    }
+
+    #[test]
+    fn removing_one_lamp_keeps_the_light_of_another() {
+        let database = database();
+        let mut world = world(&database, |world| {
+            world.set_block(BlockPos::new(4, 8, 8), lamp());
+            world.set_block(BlockPos::new(12, 8, 8), lamp());
+        });
+        assert_eq!(block(&world, 8, 8, 8), 8);
+
+        world.place_block(BlockPos::new(4, 8, 8), BlockId::AIR, &database);
+        assert_eq!(block(&world, 4, 8, 8), 4);
+        assert_eq!(block(&world, 8, 8, 8), 8);
+        assert_eq!(block(&world, 12, 8, 8), 12);
+    }
+
+    #[test]
+    fn loading_a_chunk_above_blocks_the_sky() {
+        let database = database();
+        let mut world = World::new(0);
+        let changes = world.update_loaded(ChunkPos::default(), |_| Chunk::new());
+        world.light_chunks(&changes.loaded, &database);
+        assert_eq!(sky(&world, 5, 5, 5), MAX_LIGHT);
+
+        let above = ChunkPos::new(0, 1, 0);
+        world.insert_chunk(above, Chunk::filled(stone()));
+        let changed = world.light_chunks(&[above], &database);
+        assert!(changed.contains(&ChunkPos::default()));
+        assert_eq!(sky(&world, 5, 5, 5), 0);
+        assert_eq!(sky(&world, 5, 20, 5), 0);
+    }
+
+    #[test]
+    fn light_opacity_dims_light() {
+        let database = database();
+        let mut world = World::new(0);
+        let changes = world.update_loaded(ChunkPos::default(), |_| Chunk::new());
+        for x in 0..16 {
+            for z in 0..16 {
+                world.set_block(BlockPos::new(x, 15, z), tinted());
+            }
+        }
+        world.light_chunks(&changes.loaded, &database);
+        assert_eq!(sky(&world, 5, 15, 5), MAX_LIGHT - 3);
+        assert_eq!(sky(&world, 5, 14, 5), MAX_LIGHT - 4);
+        assert_eq!(sky(&world, 5, 10, 5), MAX_LIGHT - 8);
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+
+use super::{BlockId, BlockPos, Chunk, ChunkPos, Face, LightMap};
+
+/// All the loaded chunks of an endless world.
+///
+/// Chunks are kept in a map keyed by their position, so the world can grow in
+/// every direction, including below and behind the origin.  Only the chunks
+/// within the render distance of a centre point (usually the player) are kept
+/// loaded; see [World::update_loaded].
+///
+/// The light of each chunk is kept alongside it; see [World::light_chunks] and
+/// [World::place_block].
+pub struct World {
+    /// The loaded chunks.
+    chunks: HashMap<ChunkPos, Chunk>,
+
+    /// The light of the loaded chunks.  A chunk with no light map is dark.
+    pub(super) light: HashMap<ChunkPos, LightMap>,
+
+    /// The distance (in chunks) from the centre within which chunks are
+    /// loaded.
+    render_distance: u32,
+}
+
+/// The chunks that were loaded and unloaded by [World::update_loaded].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ChunkChanges {
+    /// The positions of the chunks that were loaded, nearest first.
+    pub loaded: Vec<ChunkPos>,
+
+    /// The positions of the chunks that were unloaded.
+    pub unloaded: Vec<ChunkPos>,
+}
+
+impl World {
+    /// Creates a new world with no chunks loaded.
+    ///
+    /// # Parameters
+    ///
+    /// * `render_distance` - The distance (in chunks) from the centre within
+    ///   which chunks are loaded.
+    ///
+    pub fn new(render_distance: u32) -> World {
+        World {
+            chunks: HashMap::new(),
+            light: HashMap::new(),
+            render_distance,
+        }
+    }
+
+    /// Returns the distance (in chunks) within which chunks are loaded.
+    pub fn render_distance(&self) -> u32 {
+        self.render_distance
+    }
+
+    /// Sets the distance (in chunks) within which chunks are loaded.  This
+    /// takes effect on the next call to [World::update_loaded].
+    pub fn set_render_distance(&mut self, render_distance: u32) {
+        self.render_distance = render_distance;
+    }
+
+    /// Returns the chunk at a position, if it is loaded.
+    pub fn chunk(&self, pos: ChunkPos) -> Option<&Chunk> {
+        self.chunks.get(&pos)
+    }
+
+    /// Returns the chunk at a position for changing, if it is loaded.
+    pub fn chunk_mut(&mut self, pos: ChunkPos) -> Option<&mut Chunk> {
+        self.chunks.get_mut(&pos)
+    }
+
+    /// Adds a chunk to the world, replacing any chunk already at that
+    /// position.  The new chunk is dark until it is lit.
+    pub fn insert_chunk(&mut self, pos: ChunkPos, chunk: Chunk) -> Option<Chunk> {
+        self.light.remove(&pos);
+        self.chunks.insert(pos, chunk)
+    }
+
+    /// Removes a chunk from the world.
+    pub fn remove_chunk(&mut self, pos: ChunkPos) -> Option<Chunk> {
+        self.light.remove(&pos);
+        self.chunks.remove(&pos)
+    }
+
+    /// Iterates over all the loaded chunks.
+    pub fn chunks(&self) -> impl Iterator<Item = (ChunkPos, &Chunk)> {
+        self.chunks.iter().map(|(&pos, chunk)| (pos, chunk))
+    }
+
+    /// Returns the number of loaded chunks.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Returns the block at a position, or `None` if its chunk is not loaded.
+    pub fn block(&self, pos: BlockPos) -> Option<BlockId> {
+        self.chunk(pos.chunk()).map(|chunk| chunk.get(pos.local()))
+    }
+
+    /// Returns the index in the database of the biome at a position, or
+    /// `None` if its chunk is not loaded.
+    pub fn biome(&self, pos: BlockPos) -> Option<usize> {
+        let local = pos.local();
+        self.chunk(pos.chunk())
+            .map(|chunk| chunk.biome(local.x, local.z))
+    }
+
+    /// Sets the block at a position.
+    ///
+    /// # Returns
+    ///
+    /// The block that was there before, or `None` if its chunk is not loaded,
+    /// in which case nothing is changed.
+    ///
+    pub fn set_block(&mut self, pos: BlockPos, block: BlockId) -> Option<BlockId> {
+        self.chunk_mut(pos.chunk())
+            .map(|chunk| chunk.set(pos.local(), block))
+    }
+
+    /// Returns the neighbouring block on a face, which may be in another
+    /// chunk.  This is `None` if that chunk is not loaded.
+    pub fn neighbour(&self, pos: BlockPos, face: Face) -> Option<BlockId> {
+        self.block(pos.neighbour(face))
+    }
+
+    /// Loads and unloads chunks so that exactly the chunks within the render
+    /// distance of a centre chunk are loaded.
+    ///
+    /// # Parameters
+    ///
+    /// * `centre` - The chunk at the centre of the loaded area.
+    /// * `load` - Called to create each chunk that needs loading.
+    ///
+    /// # Returns
+    ///
+    /// The positions of the chunks that were loaded and unloaded.
+    ///
+    /// # Notes
+    ///
+    /// The loaded area is a sphere, measured from chunk to chunk.  New chunks
+    /// are created nearest first, and are dark until they are passed to
+    /// [World::light_chunks].
+    ///
+    pub fn update_loaded<F>(&mut self, centre: ChunkPos, mut load: F) -> ChunkChanges
+    where
+        F: FnMut(ChunkPos) -> Chunk,
+    {
+        let distance = self.render_distance as i64;
+        let unloaded = self
+            .chunks
+            .keys()
+            .copied()
+            .filter(|&pos| pos.distance_squared(centre) > distance * distance)
+            .collect::<Vec<_>>();
+        for &pos in &unloaded {
+            self.remove_chunk(pos);
+        }
+
+        let loaded = chunks_within(centre, self.render_distance)
+            .into_iter()
+            .filter(|pos| !self.chunks.contains_key(pos))
+            .collect::<Vec<_>>();
+        for &pos in &loaded {
+            self.insert_chunk(pos, load(pos));
+        }
+
+        ChunkChanges { loaded, unloaded }
+    }
+}
+
+/// Returns the positions of the chunks within a distance (in chunks) of a
+/// centre chunk, nearest first.  The area is a sphere, measured from chunk to
+/// chunk, as used by [World::update_loaded].
+pub fn chunks_within(centre: ChunkPos, distance: u32) -> Vec<ChunkPos> {
+    let distance = distance as i64;
+    let mut chunks = Vec::new();
+    for x in -distance..=distance {
+        for y in -distance..=distance {
+            for z in -distance..=distance {
+                let pos = ChunkPos::new(centre.x + x, centre.y + y, centre.z + z);
+                if pos.distance_squared(centre) <= distance * distance {
+                    chunks.push(pos);
+                }
+            }
+        }
+    }
+    chunks.sort_by_key(|pos| pos.distance_squared(centre));
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{LocalPos, CHUNK_SIZE};
+
+    #[test]
+    fn negative_positions() {
+        let pos = BlockPos::new(-1, -16, -17);
+        assert_eq!(pos.chunk(), ChunkPos::new(-1, -1, -2));
+        assert_eq!(pos.local(), LocalPos::new(15, 0, 15));
+        assert_eq!(pos.chunk().block(pos.local()), pos);
+        assert_eq!(
+            BlockPos::containing(-0.5, 0.5, -1.0),
+            BlockPos::new(-1, 0, -1)
+        );
+    }
+
+    #[test]
+    fn positions_round_trip() {
+        for v in -40..40 {
+            let pos = BlockPos::new(v, -v, v * 7);
+            assert_eq!(pos.chunk().block(pos.local()), pos);
+        }
+        assert_eq!(ChunkPos::new(-2, 0, 3).origin(), BlockPos::new(-32, 0, 48));
+    }
+
+    #[test]
+    fn neighbours_across_chunks() {
+        let mut world = World::new(1);
+        world.insert_chunk(ChunkPos::new(0, 0, 0), Chunk::new());
+        world.insert_chunk(ChunkPos::new(-1, 0, 0), Chunk::new());
+
+        let stone = BlockId::from_index(1);
+        assert_eq!(
+            world.set_block(BlockPos::new(-1, 3, 4), stone),
+            Some(BlockId::AIR)
+        );
+        assert_eq!(
+            world.neighbour(BlockPos::new(0, 3, 4), Face::Left),
+            Some(stone)
+        );
+        assert_eq!(
+            world.neighbour(BlockPos::new(0, 3, 4), Face::Back),
+            Some(BlockId::AIR)
+        );
+        assert_eq!(world.neighbour(BlockPos::new(0, 15, 4), Face::Top), None);
+        assert_eq!(
+            world.set_block(BlockPos::new(0, CHUNK_SIZE as i64, 0), stone),
+            None
+        );
+    }
+
+    #[test]
+    fn load_and_unload() {
+        let mut world = World::new(1);
+        let changes = world.update_loaded(ChunkPos::new(0, 0, 0), |_| Chunk::new());
+        assert_eq!(changes.loaded.len(), 7);
+        assert_eq!(changes.loaded[0], ChunkPos::new(0, 0, 0));
+        assert!(changes.unloaded.is_empty());
+
+        let changes = world.update_loaded(ChunkPos::new(1, 0, 0), |_| Chunk::new());
+        assert_eq!(world.chunk_count(), 7);
+        assert_eq!(changes.loaded.len(), 5);
+        assert_eq!(changes.unloaded.len(), 5);
+        assert!(world.chunk(ChunkPos::new(-1, 0, 0)).is_none());
+        assert!(world.chunk(ChunkPos::new(2, 0, 0)).is_some());
+
+        world.set_render_distance(0);
+        let changes = world.update_loaded(ChunkPos::new(1, 0, 0), |_| Chunk::new());
+        assert!(changes.loaded.is_empty());
+        assert_eq!(changes.unloaded.len(), 6);
+        assert_eq!(world.chunk_count(), 1);
+    }
+}
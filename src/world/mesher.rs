@@ -0,0 +1,976 @@
+use std::collections::BTreeMap;
+
+use gfx::{Buffer, Screen};
+
+use super::{
+    BlockId, BlockPos, Chunk, ChunkOrigin, ChunkPos, Face, LocalPos, TerrainVertex, World,
+    BLOCK_STEPS, CHUNK_SIZE, NO_OCCLUSION,
+};
+use crate::resources::Database;
+
+/// The most vertices in one part of a mesh, so that the indices of its
+/// triangles fit in 16 bits.
+pub const MAX_PART_VERTICES: usize = u16::MAX as usize + 1;
+
+/// Selects how a chunk's faces are turned into quads.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MeshMode {
+    /// Every visible block face is a quad of its own.
+    #[default]
+    Naive,
+
+    /// Neighbouring faces that lie in the same plane and look the same are
+    /// merged into larger quads with repeating UVs.
+    Greedy,
+}
+
+/// The faces of a chunk that share a material.
+#[derive(Debug, Default, PartialEq)]
+pub struct MeshPart {
+    /// The index of the material in the database.
+    pub material: usize,
+
+    /// Whether the faces belong to translucent blocks, which are blended
+    /// with what is behind them and so must be drawn after everything else.
+    pub translucent: bool,
+
+    /// The vertices of the faces, 4 per face, and at most
+    /// [MAX_PART_VERTICES].
+    pub vertices: Vec<TerrainVertex>,
+
+    /// The indices of the triangles, 6 per face.
+    pub indices: Vec<u16>,
+}
+
+/// The geometry of a chunk, split by material.
+#[derive(Debug, Default, PartialEq)]
+pub struct ChunkMesh {
+    /// The parts of the mesh, with the solid parts before the translucent
+    /// ones, each in order of material index.  Materials with no faces have
+    /// no part, and materials with more than [MAX_PART_VERTICES] vertices
+    /// are split over several parts.
+    pub parts: Vec<MeshPart>,
+}
+
+/// A chunk mesh that has been uploaded to the GPU.
+pub struct GpuChunkMesh {
+    /// The instance buffer holding the chunk's [ChunkOrigin].
+    pub origin: Buffer,
+
+    /// The parts of the mesh, in the same order as [ChunkMesh::parts].
+    pub parts: Vec<GpuMeshPart>,
+}
+
+/// A part of a chunk mesh that has been uploaded to the GPU.
+pub struct GpuMeshPart {
+    /// The index of the material in the database.
+    pub material: usize,
+
+    /// Whether the faces belong to translucent blocks, which must be drawn
+    /// after everything else.
+    pub translucent: bool,
+
+    /// The vertex buffer.
+    pub vertices: Buffer,
+
+    /// The index buffer.
+    pub indices: Buffer,
+}
+
+/// What a visible block face looks like.  Faces that look the same can be
+/// merged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct FaceLook {
+    /// The index of the material in the database.
+    material: usize,
+
+    /// Whether the block is translucent.
+    translucent: bool,
+
+    /// The index of the tile in the database.
+    tile: usize,
+
+    /// The ambient occlusion level of each corner, in the same order as
+    /// [Face::corners].
+    ao: [u8; 4],
+
+    /// The packed light of the block in front of the face (see
+    /// [Light::packed](super::Light::packed)).
+    light: u8,
+}
+
+/// Everything needed to find the visible faces of a chunk.
+struct ChunkFaces<'a> {
+    world: &'a World,
+    chunk: &'a Chunk,
+    pos: ChunkPos,
+    database: &'a Database,
+}
+
+/// Collects the quads of a mesh, split by translucency and material, and
+/// into parts of at most [MAX_PART_VERTICES] vertices.
+#[derive(Default)]
+struct MeshBuilder {
+    parts: BTreeMap<(bool, usize), Vec<MeshPart>>,
+}
+
+impl ChunkMesh {
+    /// Builds the mesh of a chunk in the world.
+    ///
+    /// # Parameters
+    ///
+    /// * `world` - The world that holds the chunk and its neighbours.
+    /// * `pos` - The position of the chunk.
+    /// * `database` - The database that the chunk's block IDs refer to.
+    /// * `mode` - Whether faces are merged into larger quads.
+    ///
+    /// # Returns
+    ///
+    /// The mesh, which is empty if the chunk is not loaded.
+    ///
+    /// # Notes
+    ///
+    /// A face is only emitted if the neighbouring block is transparent (or
+    /// air), and not the same block, so the inside of a body of glass or water
+    /// has no faces.  Neighbours drawn with a model (see [Database::model])
+    /// only hide the faces against the sides they fully cover.  Model quads
+    /// are never merged and are hidden in the same way.  Faces on the border of
+    /// the chunk look at the neighbouring chunk.  If that chunk is not loaded,
+    /// the face is left out, so a chunk should be meshed again when its
+    /// neighbours are loaded.
+    ///
+    /// Each corner of a face is darkened by the occluding blocks around it
+    /// (see [Database::occludes]), so the mesh also depends on the blocks
+    /// diagonally next to the chunk.  Each face is lit by the light of the
+    /// block in front of it (see [World::light_chunks]).
+    ///
+    pub fn build(world: &World, pos: ChunkPos, database: &Database, mode: MeshMode) -> ChunkMesh {
+        let Some(chunk) = world.chunk(pos) else {
+            return ChunkMesh::default();
+        };
+        if chunk.is_empty() {
+            return ChunkMesh::default();
+        }
+
+        let faces = ChunkFaces {
+            world,
+            chunk,
+            pos,
+            database,
+        };
+        let mut builder = MeshBuilder::default();
+        match mode {
+            MeshMode::Naive => faces.mesh_naive(&mut builder),
+            MeshMode::Greedy => faces.mesh_greedy(&mut builder),
+        }
+        faces.mesh_models(&mut builder);
+
+        ChunkMesh {
+            parts: builder.parts.into_values().flatten().collect(),
+        }
+    }
+
+    /// Returns true if the mesh has no faces.
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    /// Returns the number of vertices in all the parts of the mesh.
+    pub fn vertex_count(&self) -> usize {
+        self.parts.iter().map(|part| part.vertices.len()).sum()
+    }
+
+    /// Uploads every part of the mesh of the chunk at a position to the GPU.
+    pub fn upload(&self, screen: &Screen, pos: ChunkPos) -> GpuChunkMesh {
+        GpuChunkMesh {
+            origin: screen.create_vertex_buffer("Chunk origin", &[ChunkOrigin::new(pos)]),
+            parts: self
+                .parts
+                .iter()
+                .map(|part| GpuMeshPart {
+                    material: part.material,
+                    translucent: part.translucent,
+                    vertices: screen.create_vertex_buffer("Chunk vertices", &part.vertices),
+                    indices: screen.create_index_buffer("Chunk indices", &part.indices),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ChunkFaces<'_> {
+    /// Emits a quad for every visible face.
+    fn mesh_naive(&self, builder: &mut MeshBuilder) {
+        for (local, _) in self.chunk.iter() {
+            for face in Face::ALL {
+                if let Some(look) = self.visible_face(local, face) {
+                    builder.quad(face, look, local, 1, 1);
+                }
+            }
+        }
+    }
+
+    /// Emits as few quads as possible by merging visible faces.
+    ///
+    /// Each slice of the chunk facing each direction is turned into a grid of
+    /// visible faces.  Starting from the first face in the grid, a quad grows
+    /// as wide as it can and then as high as it can while every face it
+    /// covers looks the same.  The faces it covers are removed and the search
+    /// goes on from the next face.
+    fn mesh_greedy(&self, builder: &mut MeshBuilder) {
+        for face in Face::ALL {
+            let (normal, right, up) = face.axes();
+            for slice in 0..CHUNK_SIZE {
+                let local = |a: usize, b: usize| {
+                    let mut coords = [0; 3];
+                    coords[normal] = slice;
+                    coords[right] = a;
+                    coords[up] = b;
+                    LocalPos::new(coords[0], coords[1], coords[2])
+                };
+
+                let mut grid = [[None; CHUNK_SIZE]; CHUNK_SIZE];
+                for (b, row) in grid.iter_mut().enumerate() {
+                    for (a, cell) in row.iter_mut().enumerate() {
+                        *cell = self.visible_face(local(a, b), face);
+                    }
+                }
+
+                for b in 0..CHUNK_SIZE {
+                    let mut a = 0;
+                    while a < CHUNK_SIZE {
+                        let Some(look) = grid[b][a] else {
+                            a += 1;
+                            continue;
+                        };
+
+                        let width = grid[b][a..]
+                            .iter()
+                            .take_while(|&&cell| cell == Some(look))
+                            .count();
+                        let height = grid[b..]
+                            .iter()
+                            .take_while(|row| {
+                                row[a..a + width].iter().all(|&cell| cell == Some(look))
+                            })
+                            .count();
+                        for row in &mut grid[b..b + height] {
+                            row[a..a + width].fill(None);
+                        }
+
+                        builder.quad(face, look, local(a, b), width, height);
+                        a += width;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Emits the quads of every block drawn with a model.
+    ///
+    /// Quads on a side of the block are lit by the block on that side, like
+    /// cube faces, and the rest by the block's own light.  Model quads are
+    /// not darkened by ambient occlusion.
+    fn mesh_models(&self, builder: &mut MeshBuilder) {
+        if !self
+            .chunk
+            .block_counts()
+            .any(|(id, _)| self.database.model(id).is_some())
+        {
+            return;
+        }
+
+        for (local, id) in self.chunk.iter() {
+            let Some(model) = self.database.model(id) else {
+                continue;
+            };
+            let Some(block) = self.database.block(id) else {
+                continue;
+            };
+
+            let own_light = self
+                .world
+                .light(self.pos.block(local))
+                .unwrap_or_default()
+                .packed();
+            let min = [local.x as u32, local.y as u32, local.z as u32].map(|c| c * BLOCK_STEPS);
+            for quad in model.quads() {
+                let light = match quad.cull() {
+                    Some(side) => {
+                        if self.hidden(local, id, side) != Some(false) {
+                            continue;
+                        }
+                        self.face_light(local, side)
+                    }
+                    None => own_light,
+                };
+                let look = FaceLook {
+                    material: block.material(),
+                    translucent: block.properties().translucent(),
+                    tile: quad.tile(block),
+                    ao: [NO_OCCLUSION; 4],
+                    light,
+                };
+                let corners = quad
+                    .corners()
+                    .map(|corner| [0, 1, 2].map(|axis| min[axis] + corner[axis]));
+                builder.push_quad(quad.face(), look, corners, quad.uvs());
+            }
+        }
+    }
+
+    /// Returns what a block's face looks like, or `None` if it cannot be
+    /// seen.  Blocks drawn with a model have no cube faces.
+    fn visible_face(&self, local: LocalPos, face: Face) -> Option<FaceLook> {
+        let id = self.chunk.get(local);
+        let block = self.database.block(id)?;
+        if block.model().is_some() || self.hidden(local, id, face)? {
+            return None;
+        }
+
+        Some(FaceLook {
+            material: block.material(),
+            translucent: block.properties().translucent(),
+            tile: block.tile(face),
+            ao: self.face_ao(local, face),
+            light: self.face_light(local, face),
+        })
+    }
+
+    /// Returns true if the side of a block is hidden by the block next to
+    /// it, which must cover that side fully and either be the same block or
+    /// not be transparent.  This is `None` if the neighbour is not loaded.
+    fn hidden(&self, local: LocalPos, id: BlockId, side: Face) -> Option<bool> {
+        let neighbour = self.neighbour(local, side)?;
+        Some(
+            self.database.covers_side(neighbour, side.opposite())
+                && (neighbour == id || !self.database.is_transparent(neighbour)),
+        )
+    }
+
+    /// Returns the packed light of the block in front of a face.
+    fn face_light(&self, local: LocalPos, face: Face) -> u8 {
+        let front = self.pos.block(local).neighbour(face);
+        self.world.light(front).unwrap_or_default().packed()
+    }
+
+    /// Works out the ambient occlusion level of each corner of a face.
+    ///
+    /// Each corner looks at the three blocks in front of the face that touch
+    /// it: the two at its sides and the one diagonally across.  The level is
+    /// 3 with none of them occluding, one less for each that does, and 0 if
+    /// both sides do, since they hide the diagonal block anyway.
+    fn face_ao(&self, local: LocalPos, face: Face) -> [u8; 4] {
+        let (_, right, up) = face.axes();
+        let (nx, ny, nz) = face.offset();
+
+        face.corners().map(|corner| {
+            let occludes = |along_right: bool, along_up: bool| {
+                let mut offset = [nx, ny, nz];
+                if along_right {
+                    offset[right] = if corner[right] == 0 { -1 } else { 1 };
+                }
+                if along_up {
+                    offset[up] = if corner[up] == 0 { -1 } else { 1 };
+                }
+                self.block_at(local, offset)
+                    .is_some_and(|id| self.database.occludes(id))
+            };
+
+            let side1 = occludes(true, false);
+            let side2 = occludes(false, true);
+            if side1 && side2 {
+                0
+            } else {
+                3 - side1 as u8 - side2 as u8 - occludes(true, true) as u8
+            }
+        })
+    }
+
+    /// Returns the block next to a block in the chunk, looking in the
+    /// neighbouring chunk if needed.  This is `None` if that chunk is not
+    /// loaded.
+    fn neighbour(&self, local: LocalPos, face: Face) -> Option<BlockId> {
+        let (dx, dy, dz) = face.offset();
+        self.block_at(local, [dx, dy, dz])
+    }
+
+    /// Returns the block at an offset from a block in the chunk, looking in
+    /// the neighbouring chunks if needed.  This is `None` if that chunk is
+    /// not loaded.
+    fn block_at(&self, local: LocalPos, [dx, dy, dz]: [i64; 3]) -> Option<BlockId> {
+        let x = local.x as i64 + dx;
+        let y = local.y as i64 + dy;
+        let z = local.z as i64 + dz;
+        let size = CHUNK_SIZE as i64;
+        if (0..size).contains(&x) && (0..size).contains(&y) && (0..size).contains(&z) {
+            Some(
+                self.chunk
+                    .get(LocalPos::new(x as usize, y as usize, z as usize)),
+            )
+        } else {
+            let origin = self.pos.origin();
+            self.world
+                .block(BlockPos::new(origin.x + x, origin.y + y, origin.z + z))
+        }
+    }
+}
+
+impl MeshBuilder {
+    /// Adds a quad covering a rectangle of faces.
+    ///
+    /// # Parameters
+    ///
+    /// * `face` - The direction the quad faces.
+    /// * `look` - The material and tile of the quad.
+    /// * `min` - The block with the lowest coordinates that the quad covers.
+    /// * `width` - The number of blocks the quad covers along its right axis.
+    /// * `height` - The number of blocks the quad covers along its up axis.
+    ///
+    fn quad(&mut self, face: Face, look: FaceLook, min: LocalPos, width: usize, height: usize) {
+        let (_, right, up) = face.axes();
+        let mut size = [BLOCK_STEPS; 3];
+        size[right] *= width as u32;
+        size[up] *= height as u32;
+
+        let (w, h) = (size[right], size[up]);
+        let uvs = [[0, h], [w, h], [w, 0], [0, 0]];
+        let min = [min.x as u32, min.y as u32, min.z as u32].map(|c| c * BLOCK_STEPS);
+        let corners = face
+            .corners()
+            .map(|corner| [0, 1, 2].map(|axis| min[axis] + corner[axis] * size[axis]));
+        self.push_quad(face, look, corners, uvs);
+    }
+
+    /// Adds a quad with any corners.
+    ///
+    /// # Parameters
+    ///
+    /// * `face` - The direction the quad is shaded as facing.
+    /// * `look` - The material, tile, shading and light of the quad.
+    /// * `corners` - The positions of the corners relative to the chunk
+    ///   origin, in steps, in the same order as [Face::corners].
+    /// * `uvs` - The texture coordinates of the corners, in steps.
+    ///
+    fn push_quad(
+        &mut self,
+        face: Face,
+        look: FaceLook,
+        corners: [[u32; 3]; 4],
+        uvs: [[u32; 2]; 4],
+    ) {
+        let key = (look.translucent, look.material);
+        let parts = self.parts.entry(key).or_default();
+        if parts
+            .last()
+            .is_none_or(|part| part.vertices.len() + 4 > MAX_PART_VERTICES)
+        {
+            parts.push(MeshPart {
+                material: look.material,
+                translucent: look.translucent,
+                ..Default::default()
+            });
+        }
+        let part = parts.last_mut().unwrap();
+        let base = part.vertices.len() as u16;
+        for ((position, uv), ao) in corners.into_iter().zip(uvs).zip(look.ao) {
+            part.vertices.push(TerrainVertex::new(
+                position, face, ao, look.light, look.tile, uv,
+            ));
+        }
+
+        // The colours of a quad are blended across each triangle, so the
+        // diagonal the quad is split along shows up when the corners are
+        // darkened unevenly.  Splitting between the darker pair of corners
+        // keeps the shading symmetrical.
+        let [ao0, ao1, ao2, ao3] = look.ao.map(u32::from);
+        let indices = if ao0 + ao2 > ao1 + ao3 {
+            [1, 2, 3, 3, 0, 1]
+        } else {
+            [0, 1, 2, 2, 3, 0]
+        };
+        part.indices.extend(indices.into_iter().map(|i| base + i));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::{BlockProperties, Model, ModelQuad, Tile};
+    use crate::world::{Light, FULL_LIGHT, MAX_LIGHT};
+
+    const S: u32 = BLOCK_STEPS;
+
+    /// Creates a database with two tiles, stone (block 0), glass (block 1),
+    /// water (block 2), a slab (block 3), a glass slab (block 4) and a plant
+    /// (block 5).  The blocks that can be seen through use material 1.
+    fn database() -> Database {
+        let clear = BlockProperties {
+            transparent: true,
+            occludes: false,
+            light_opacity: 0,
+            ..Default::default()
+        };
+        let water = BlockProperties {
+            translucent: true,
+            ..clear.clone()
+        };
+        let mut db = Database::test_blocks(&["stone"])
+            .with_block("glass", 1, clear.clone(), None)
+            .with_block("water", 1, water, None)
+            .with_block("slab", 0, BlockProperties::default(), Some(0))
+            .with_block("glass slab", 1, clear.clone(), Some(0))
+            .with_block("plant", 1, clear, Some(1));
+        db.tiles.push(Tile {
+            image_index: 0,
+            origin: (0, 0),
+            uv0: (0.25, 0.5),
+            uv1: (0.75, 1.0),
+            layer: 3,
+        });
+        db.tiles.push(Tile {
+            image_index: 0,
+            origin: (16, 0),
+            uv0: (0.0, 0.0),
+            uv1: (0.25, 0.5),
+            layer: 4,
+        });
+        let slab = (0..6)
+            .filter_map(|i| ModelQuad::cuboid_face([0; 3], [S, S / 2, S], Face::ALL[i], None, None))
+            .collect();
+        db.models.push(Model::new(slab));
+        db.models
+            .push(Model::new(ModelQuad::cross(Some(1)).to_vec()));
+        db
+    }
+
+    fn stone() -> BlockId {
+        BlockId::from_index(0)
+    }
+
+    fn glass() -> BlockId {
+        BlockId::from_index(1)
+    }
+
+    fn water() -> BlockId {
+        BlockId::from_index(2)
+    }
+
+    fn slab() -> BlockId {
+        BlockId::from_index(3)
+    }
+
+    fn glass_slab() -> BlockId {
+        BlockId::from_index(4)
+    }
+
+    fn plant() -> BlockId {
+        BlockId::from_index(5)
+    }
+
+    /// Creates a world with an air chunk at the origin surrounded by air, lit
+    /// by the sky.
+    fn world() -> World {
+        let mut world = World::new(1);
+        let changes = world.update_loaded(ChunkPos::default(), |_| Chunk::new());
+        world.light_chunks(&changes.loaded, &database());
+        world
+    }
+
+    fn mesh(world: &World, mode: MeshMode) -> ChunkMesh {
+        ChunkMesh::build(world, ChunkPos::default(), &database(), mode)
+    }
+
+    fn face_count(mesh: &ChunkMesh) -> usize {
+        mesh.parts.iter().map(|part| part.indices.len() / 6).sum()
+    }
+
+    /// Returns the corner occlusion levels and the triangle indices (relative
+    /// to the quad) of the top face of the stone block at `(x, 0, z)`.
+    fn top_face(mesh: &ChunkMesh, x: u32, z: u32) -> ([u8; 4], Vec<u16>) {
+        let part = &mesh.parts[0];
+        let (quad, indices) = part
+            .vertices
+            .chunks(4)
+            .zip(part.indices.chunks(6))
+            .find(|(quad, _)| {
+                quad[0].face() == Face::Top && quad[3].local_position() == [x * S, S, z * S]
+            })
+            .unwrap();
+        let base = indices.iter().min().unwrap();
+        (
+            [quad[0].ao(), quad[1].ao(), quad[2].ao(), quad[3].ao()],
+            indices.iter().map(|i| i - base).collect(),
+        )
+    }
+
+    #[test]
+    fn empty_chunk_has_no_faces() {
+        assert!(mesh(&world(), MeshMode::Naive).is_empty());
+        assert!(mesh(&world(), MeshMode::Greedy).is_empty());
+    }
+
+    #[test]
+    fn single_block_has_six_faces() {
+        let mut world = world();
+        world.set_block(BlockPos::new(3, 4, 5), stone());
+        let mesh = mesh(&world, MeshMode::Naive);
+        assert_eq!(mesh.parts.len(), 1);
+        assert_eq!(face_count(&mesh), 6);
+
+        let part = &mesh.parts[0];
+        assert_eq!(part.vertices.len(), 24);
+        for vertex in &part.vertices {
+            assert!(vertex
+                .local_position()
+                .into_iter()
+                .zip([3, 4, 5])
+                .all(|(p, b)| p == b * S || p == (b + 1) * S));
+            assert!(vertex.uv().iter().all(|&c| c == 0 || c == S));
+            assert_eq!(vertex.tile(), 0);
+            assert_eq!(vertex.ao(), NO_OCCLUSION);
+            assert_eq!(vertex.light(), FULL_LIGHT);
+        }
+    }
+
+    #[test]
+    fn touching_blocks_hide_faces() {
+        let mut world = world();
+        world.set_block(BlockPos::new(3, 4, 5), stone());
+        world.set_block(BlockPos::new(4, 4, 5), stone());
+        assert_eq!(face_count(&mesh(&world, MeshMode::Naive)), 10);
+    }
+
+    #[test]
+    fn transparent_neighbours_show_faces() {
+        let mut world = world();
+        world.set_block(BlockPos::new(3, 4, 5), stone());
+        world.set_block(BlockPos::new(4, 4, 5), glass());
+        world.set_block(BlockPos::new(5, 4, 5), glass());
+        let mesh = mesh(&world, MeshMode::Naive);
+
+        // The stone shows all 6 faces.  The two glass blocks hide the face
+        // between them and the glass face against the stone.
+        assert_eq!(mesh.parts.len(), 2);
+        assert_eq!(mesh.parts[0].material, 0);
+        assert_eq!(mesh.parts[0].indices.len() / 6, 6);
+        assert_eq!(mesh.parts[1].material, 1);
+        assert_eq!(mesh.parts[1].indices.len() / 6, 9);
+    }
+
+    #[test]
+    fn faces_across_chunk_borders() {
+        let mut world = world();
+        world.set_block(BlockPos::new(0, 0, 0), stone());
+        world.set_block(BlockPos::new(-1, 0, 0), stone());
+        assert_eq!(face_count(&mesh(&world, MeshMode::Naive)), 5);
+
+        // With the neighbouring chunk unloaded, its side is hidden as well.
+        world.remove_chunk(ChunkPos::new(0, -1, 0));
+        assert_eq!(face_count(&mesh(&world, MeshMode::Naive)), 4);
+    }
+
+    #[test]
+    fn full_chunk_shows_only_its_surface() {
+        let mut world = world();
+        world.insert_chunk(ChunkPos::default(), Chunk::filled(stone()));
+        let naive = mesh(&world, MeshMode::Naive);
+        assert_eq!(face_count(&naive), 6 * CHUNK_SIZE * CHUNK_SIZE);
+        let greedy = mesh(&world, MeshMode::Greedy);
+        assert_eq!(face_count(&greedy), 6);
+    }
+
+    #[test]
+    fn greedy_quads_repeat_uvs() {
+        let mut world = world();
+        for x in 0..3 {
+            for z in 0..2 {
+                world.set_block(BlockPos::new(x, 0, z), stone());
+            }
+        }
+        let mesh = mesh(&world, MeshMode::Greedy);
+        assert_eq!(face_count(&mesh), 6);
+
+        let top = mesh.parts[0]
+            .vertices
+            .chunks(4)
+            .find(|quad| quad[0].face() == Face::Top)
+            .unwrap();
+        assert_eq!(
+            top.iter().map(|v| v.local_position()).collect::<Vec<_>>(),
+            vec![[0, S, 2 * S], [3 * S, S, 2 * S], [3 * S, S, 0], [0, S, 0]]
+        );
+        assert_eq!(
+            top.iter().map(|v| v.uv()).collect::<Vec<_>>(),
+            vec![[0, 2 * S], [3 * S, 2 * S], [3 * S, 0], [0, 0]]
+        );
+    }
+
+    #[test]
+    fn greedy_keeps_different_looks_apart() {
+        let mut world = world();
+        for x in 0..4 {
+            let block = if x < 2 { stone() } else { glass() };
+            world.set_block(BlockPos::new(x, 0, 0), block);
+        }
+        let mesh = mesh(&world, MeshMode::Greedy);
+
+        // Each material gets a single quad for each of its 5 outside faces.
+        // The glass side facing the stone is hidden, the stone side facing
+        // the glass is not.
+        assert_eq!(mesh.parts[0].indices.len() / 6, 6);
+        assert_eq!(mesh.parts[1].indices.len() / 6, 5);
+    }
+
+    #[test]
+    fn greedy_covers_the_same_area() {
+        // A bumpy surface: a floor with a few blocks on top.
+        let mut world = world();
+        for x in 0..CHUNK_SIZE as i64 {
+            for z in 0..CHUNK_SIZE as i64 {
+                world.set_block(BlockPos::new(x, 0, z), stone());
+                if (x * 7 + z * 3) % 5 == 0 {
+                    world.set_block(BlockPos::new(x, 1, z), stone());
+                }
+            }
+        }
+
+        let area = |mesh: &ChunkMesh| -> u32 {
+            mesh.parts[0]
+                .vertices
+                .chunks(4)
+                .map(|quad| quad[1].uv()[0] * quad[1].uv()[1] / (S * S))
+                .sum()
+        };
+        let naive = mesh(&world, MeshMode::Naive);
+        let greedy = mesh(&world, MeshMode::Greedy);
+        assert_eq!(area(&naive), face_count(&naive) as u32);
+        assert_eq!(area(&greedy), area(&naive));
+        assert!(greedy.vertex_count() < naive.vertex_count());
+    }
+
+    #[test]
+    fn walls_darken_corners() {
+        let mut world = world();
+        world.set_block(BlockPos::new(1, 0, 1), stone());
+        world.set_block(BlockPos::new(2, 1, 1), stone());
+        let wall = mesh(&world, MeshMode::Naive);
+        assert_eq!(
+            top_face(&wall, 1, 1),
+            ([3, 2, 2, 3], vec![0, 1, 2, 2, 3, 0])
+        );
+
+        // In an inside corner, the corner between both walls is fully dark
+        // and the quad is split between the darker corners.
+        world.set_block(BlockPos::new(1, 1, 2), stone());
+        let corner = mesh(&world, MeshMode::Naive);
+        assert_eq!(
+            top_face(&corner, 1, 1),
+            ([2, 0, 2, 3], vec![1, 2, 3, 3, 0, 1])
+        );
+    }
+
+    #[test]
+    fn diagonal_blocks_darken_corners() {
+        let mut world = world();
+        world.set_block(BlockPos::new(1, 0, 1), stone());
+        world.set_block(BlockPos::new(0, 1, 0), stone());
+        let mesh = mesh(&world, MeshMode::Naive);
+        assert_eq!(top_face(&mesh, 1, 1).0, [3, 3, 3, 2]);
+    }
+
+    #[test]
+    fn transparent_blocks_do_not_occlude() {
+        let mut world = world();
+        world.set_block(BlockPos::new(1, 0, 1), stone());
+        world.set_block(BlockPos::new(2, 1, 1), glass());
+        world.set_block(BlockPos::new(1, 1, 2), glass());
+        let mesh = mesh(&world, MeshMode::Naive);
+        assert_eq!(top_face(&mesh, 1, 1).0, [NO_OCCLUSION; 4]);
+    }
+
+    #[test]
+    fn greedy_keeps_different_shading_apart() {
+        // A floor with a wall along one edge: the row of faces next to the
+        // wall is darker and cannot merge with the rest.  The faces at the
+        // ends of the row have no wall diagonally beyond them, so they are
+        // lighter than the middle ones.
+        let mut world = world();
+        for x in 0..4 {
+            for z in 0..4 {
+                world.set_block(BlockPos::new(x, 0, z), stone());
+            }
+            world.set_block(BlockPos::new(x, 1, 4), stone());
+        }
+        let mesh = mesh(&world, MeshMode::Greedy);
+        let tops = mesh.parts[0]
+            .vertices
+            .chunks(4)
+            .filter(|quad| quad[0].face() == Face::Top && quad[0].local_position()[1] == S)
+            .map(|quad| quad.iter().map(|v| v.ao()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tops,
+            vec![
+                vec![3, 3, 3, 3],
+                vec![2, 1, 3, 3],
+                vec![1, 1, 3, 3],
+                vec![1, 2, 3, 3],
+            ]
+        );
+    }
+
+    #[test]
+    fn faces_are_lit_by_the_block_in_front() {
+        let database = database();
+        let mut world = world();
+        world.place_block(BlockPos::new(1, 0, 1), stone(), &database);
+        world.place_block(BlockPos::new(1, 2, 1), stone(), &database);
+        let mesh = mesh(&world, MeshMode::Naive);
+
+        let light = |face: Face, y: u32| {
+            mesh.parts[0]
+                .vertices
+                .iter()
+                .find(|v| v.face() == face && v.local_position()[1] == y)
+                .unwrap()
+                .light()
+        };
+        assert_eq!(light(Face::Top, 3 * S), FULL_LIGHT);
+        assert_eq!(light(Face::Top, S), Light::new(MAX_LIGHT - 1, 0).packed());
+    }
+
+    #[test]
+    fn translucent_parts_come_last() {
+        let mut world = world();
+        world.set_block(BlockPos::new(0, 0, 0), water());
+        world.set_block(BlockPos::new(2, 0, 0), glass());
+        world.set_block(BlockPos::new(4, 0, 0), stone());
+        let mesh = mesh(&world, MeshMode::Naive);
+        assert_eq!(
+            mesh.parts
+                .iter()
+                .map(|part| (part.material, part.translucent))
+                .collect::<Vec<_>>(),
+            vec![(0, false), (1, false), (1, true)]
+        );
+    }
+
+    #[test]
+    fn slabs_are_half_height() {
+        let mut world = world();
+        world.set_block(BlockPos::new(1, 2, 3), slab());
+        let mesh = mesh(&world, MeshMode::Greedy);
+        assert_eq!(face_count(&mesh), 6);
+
+        let vertices = &mesh.parts[0].vertices;
+        assert!(vertices.iter().all(|v| {
+            let [x, y, z] = v.local_position();
+            (x == S || x == 2 * S)
+                && (y == 2 * S || y == 2 * S + S / 2)
+                && (z == 3 * S || z == 4 * S)
+        }));
+
+        // The sides show the bottom half of the tile, as a full cube would.
+        let side = vertices.iter().filter(|v| v.face() == Face::Front);
+        assert!(side.map(|v| v.uv()[1]).all(|v| v == S / 2 || v == S));
+        assert!(vertices.iter().all(|v| v.ao() == NO_OCCLUSION));
+    }
+
+    #[test]
+    fn slabs_hide_only_covered_faces() {
+        let mut world = world();
+        world.set_block(BlockPos::new(1, 0, 1), stone());
+        world.set_block(BlockPos::new(1, 1, 1), slab());
+        world.set_block(BlockPos::new(2, 1, 1), stone());
+        let mesh = mesh(&world, MeshMode::Naive);
+
+        // The slab's bottom covers the top of the stone below, but its half
+        // side does not cover the side of the stone next to it.  The stones
+        // hide the slab's bottom and right side.
+        assert_eq!(face_count(&mesh), 5 + 6 + 4);
+        let vertices = &mesh.parts[0].vertices;
+        assert!(!vertices
+            .iter()
+            .any(|v| v.face() == Face::Top && v.local_position() == [S, S, S]));
+        assert!(vertices
+            .iter()
+            .any(|v| v.face() == Face::Left && v.local_position() == [2 * S, S, S]));
+    }
+
+    #[test]
+    fn transparent_models_only_hide_themselves() {
+        let mut world = world();
+        world.set_block(BlockPos::new(1, 0, 1), stone());
+        world.set_block(BlockPos::new(1, 1, 1), glass_slab());
+        world.set_block(BlockPos::new(3, 0, 1), glass_slab());
+        world.set_block(BlockPos::new(4, 0, 1), glass_slab());
+        let mesh = mesh(&world, MeshMode::Naive);
+
+        // The stone shows all its faces through the glass slab.  The glass
+        // slabs next to each other only have half sides, so both still show.
+        assert_eq!(mesh.parts[0].indices.len() / 6, 6);
+        assert_eq!(mesh.parts[1].indices.len() / 6, 5 + 6 + 6);
+    }
+
+    #[test]
+    fn plants_are_crossed_quads() {
+        let database = database();
+        let mut world = world();
+        world.set_block(BlockPos::new(0, 0, 0), stone());
+        world.place_block(BlockPos::new(0, 1, 0), plant(), &database);
+        let mesh = mesh(&world, MeshMode::Greedy);
+
+        // The plant does not hide the top of the stone below.
+        assert_eq!(mesh.parts[0].indices.len() / 6, 6);
+        let plant = &mesh.parts[1].vertices;
+        assert_eq!(plant.len(), 16);
+        assert!(plant
+            .iter()
+            .all(|v| v.tile() == 1 && v.light() == FULL_LIGHT));
+        assert!(plant.chunks(4).all(|quad| {
+            let [x0, _, z0] = quad[0].local_position();
+            let [x1, _, z1] = quad[1].local_position();
+            x0 != x1 && z0 != z1
+        }));
+    }
+
+    #[test]
+    fn models_cover_full_sides() {
+        let database = database();
+        let slab = database.model(slab()).unwrap();
+        assert!(slab.covers_side(Face::Bottom));
+        assert!(Face::ALL
+            .into_iter()
+            .filter(|&side| side != Face::Bottom)
+            .all(|side| !slab.covers_side(side)));
+        assert!(Face::ALL
+            .into_iter()
+            .all(|side| !database.model(plant()).unwrap().covers_side(side)));
+        assert!(database.covers_side(stone(), Face::Top));
+        assert!(!database.covers_side(BlockId::default(), Face::Top));
+    }
+
+    #[test]
+    fn large_meshes_are_split_into_parts() {
+        // A chunk full of slabs shows the top and sides of every slab, which
+        // is more vertices than 16-bit indices can reach.
+        let mut world = world();
+        for (local, _) in Chunk::new().iter() {
+            world
+                .chunk_mut(ChunkPos::default())
+                .unwrap()
+                .set(local, slab());
+        }
+        let mesh = mesh(&world, MeshMode::Naive);
+        assert!(mesh.vertex_count() > MAX_PART_VERTICES);
+        assert!(mesh.parts.len() > 1);
+        for part in &mesh.parts {
+            assert_eq!(part.material, 0);
+            assert!(part.vertices.len() <= MAX_PART_VERTICES);
+            assert_eq!(part.indices.len(), part.vertices.len() / 4 * 6);
+            let highest = part.indices.iter().max().copied().unwrap() as usize;
+            assert_eq!(highest, part.vertices.len() - 1);
+        }
+    }
+}
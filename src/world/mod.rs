@@ -0,0 +1,25 @@
+mod chunk;
+mod coords;
+mod features;
+mod light;
+mod map;
+mod mesher;
+mod noise;
+mod physics;
+mod region;
+mod streaming;
+mod terrain;
+mod vertex;
+
+pub use chunk::*;
+pub use coords::*;
+pub use features::*;
+pub use light::*;
+pub use map::*;
+pub use mesher::*;
+pub use noise::*;
+pub use physics::*;
+pub use region::*;
+pub use streaming::*;
+pub use terrain::*;
+pub use vertex::*;
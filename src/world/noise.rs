@@ -0,0 +1,260 @@
+use std::f64::consts::FRAC_1_SQRT_2;
+
+/// Layered gradient noise, used to shape terrain and carve caves.
+///
+/// Each layer (octave) is gradient noise with half the wavelength and a
+/// fraction (`persistence`) of the strength of the one before, so the first
+/// layer gives the broad shape and the later ones add detail.
+///
+/// The noise only depends on the seed and the position, and is worked out
+/// with plain arithmetic on a fixed table of gradients, so it is the same on
+/// every machine.
+#[derive(Clone, Debug)]
+pub struct Noise {
+    /// The seed that the noise of each layer is made from.
+    seed: u64,
+
+    /// The number of layers.
+    octaves: u32,
+
+    /// The distance (in blocks) between the points of the first layer.
+    wavelength: f64,
+
+    /// How strong each layer is compared to the one before.
+    persistence: f64,
+}
+
+/// The directions of the gradients at the points of 2D noise.
+const GRADIENTS: [(f64, f64); 8] = [
+    (1.0, 0.0),
+    (-1.0, 0.0),
+    (0.0, 1.0),
+    (0.0, -1.0),
+    (FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+    (-FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+    (FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+    (-FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+];
+
+/// The directions of the gradients at the points of 3D noise: the middles of
+/// the edges of a cube.
+const GRADIENTS_3D: [(f64, f64, f64); 12] = [
+    (1.0, 1.0, 0.0),
+    (-1.0, 1.0, 0.0),
+    (1.0, -1.0, 0.0),
+    (-1.0, -1.0, 0.0),
+    (1.0, 0.0, 1.0),
+    (-1.0, 0.0, 1.0),
+    (1.0, 0.0, -1.0),
+    (-1.0, 0.0, -1.0),
+    (0.0, 1.0, 1.0),
+    (0.0, -1.0, 1.0),
+    (0.0, 1.0, -1.0),
+    (0.0, -1.0, -1.0),
+];
+
+/// A stream of random numbers made from a seed, used to scatter things such
+/// as ore veins.  The same seed always gives the same numbers.
+#[derive(Clone, Debug)]
+pub struct Random {
+    state: u64,
+}
+
+impl Noise {
+    /// Creates layered noise.
+    ///
+    /// # Parameters
+    ///
+    /// * `seed` - The seed of the noise.  The same seed gives the same noise.
+    /// * `octaves` - The number of layers, at least 1.
+    /// * `wavelength` - The size (in blocks) of the features of the first
+    ///   layer.
+    /// * `persistence` - How strong each layer is compared to the one before,
+    ///   usually 0.5.
+    ///
+    pub fn new(seed: u64, octaves: u32, wavelength: f64, persistence: f64) -> Noise {
+        debug_assert!(octaves > 0 && wavelength > 0.0);
+        Noise {
+            seed,
+            octaves,
+            wavelength,
+            persistence,
+        }
+    }
+
+    /// Returns the noise at a point on a plane, from -1 to 1.
+    pub fn sample2(&self, x: f64, z: f64) -> f64 {
+        let mut total = 0.0;
+        let mut strength = 1.0;
+        let mut max = 0.0;
+        let mut frequency = 1.0 / self.wavelength;
+        for octave in 0..self.octaves {
+            let seed = hash(self.seed, octave as i64, 0, 0);
+            total += gradient2(seed, x * frequency, z * frequency) * strength;
+            max += strength;
+            strength *= self.persistence;
+            frequency *= 2.0;
+        }
+        total / max
+    }
+
+    /// Returns the noise at a point in space, from about -1 to 1.
+    pub fn sample3(&self, x: f64, y: f64, z: f64) -> f64 {
+        let mut total = 0.0;
+        let mut strength = 1.0;
+        let mut max = 0.0;
+        let mut frequency = 1.0 / self.wavelength;
+        for octave in 0..self.octaves {
+            let seed = hash(self.seed, octave as i64, 0, 0);
+            total += gradient3(seed, x * frequency, y * frequency, z * frequency) * strength;
+            max += strength;
+            strength *= self.persistence;
+            frequency *= 2.0;
+        }
+        total / max
+    }
+}
+
+impl Random {
+    /// Creates a stream of random numbers from a seed.
+    pub fn new(seed: u64) -> Random {
+        Random { state: seed }
+    }
+
+    /// Returns the next random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(1);
+        mix(self.state)
+    }
+
+    /// Returns a random number from 0 up to (but not including) `n`.
+    pub fn below(&mut self, n: u64) -> u64 {
+        debug_assert!(n > 0);
+        self.next_u64() % n
+    }
+
+    /// Returns a random fraction from 0 up to (but not including) 1.
+    pub fn fraction(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Mixes a seed and a position into a well spread out hash.
+pub fn hash(seed: u64, x: i64, y: i64, z: i64) -> u64 {
+    [x, y, z]
+        .into_iter()
+        .fold(mix(seed), |h, v| mix(h ^ v as u64))
+}
+
+/// Scrambles the bits of a number (the finaliser of SplitMix64).
+fn mix(mut h: u64) -> u64 {
+    h = h.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^ (h >> 31)
+}
+
+/// Returns 2D gradient (Perlin) noise, from -1 to 1, with points one unit
+/// apart.
+fn gradient2(seed: u64, x: f64, z: f64) -> f64 {
+    let (x0, z0) = (x.floor(), z.floor());
+    let (fx, fz) = (x - x0, z - z0);
+    let (ix, iz) = (x0 as i64, z0 as i64);
+
+    let dot = |dx: i64, dz: i64| {
+        let (gx, gz) = GRADIENTS[(hash(seed, ix + dx, 0, iz + dz) & 7) as usize];
+        gx * (fx - dx as f64) + gz * (fz - dz as f64)
+    };
+    let (u, v) = (fade(fx), fade(fz));
+    let near = lerp(dot(0, 0), dot(1, 0), u);
+    let far = lerp(dot(0, 1), dot(1, 1), u);
+
+    // The largest value 2D gradient noise can reach is 1/sqrt(2).
+    lerp(near, far, v) / FRAC_1_SQRT_2
+}
+
+/// Returns 3D gradient (Perlin) noise, from about -1 to 1, with points one
+/// unit apart.
+fn gradient3(seed: u64, x: f64, y: f64, z: f64) -> f64 {
+    let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+    let (fx, fy, fz) = (x - x0, y - y0, z - z0);
+    let (ix, iy, iz) = (x0 as i64, y0 as i64, z0 as i64);
+
+    let dot = |dx: i64, dy: i64, dz: i64| {
+        let (gx, gy, gz) = GRADIENTS_3D[(hash(seed, ix + dx, iy + dy, iz + dz) % 12) as usize];
+        gx * (fx - dx as f64) + gy * (fy - dy as f64) + gz * (fz - dz as f64)
+    };
+    let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+    let face = |dz: i64| {
+        let near = lerp(dot(0, 0, dz), dot(1, 0, dz), u);
+        let far = lerp(dot(0, 1, dz), dot(1, 1, dz), u);
+        lerp(near, far, v)
+    };
+    lerp(face(0), face(1), w)
+}
+
+/// Eases a fraction so the noise is smooth across the points.
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn noise_is_deterministic_and_bounded() {
+        let noise = Noise::new(7, 4, 32.0, 0.5);
+        let other = Noise::new(8, 4, 32.0, 0.5);
+        let mut differs = false;
+        for i in 0..1000 {
+            let (x, z) = (i as f64 * 1.37 - 500.0, i as f64 * -0.91 + 200.0);
+            let value = noise.sample2(x, z);
+            assert!((-1.0..=1.0).contains(&value));
+            assert_eq!(value, Noise::new(7, 4, 32.0, 0.5).sample2(x, z));
+            differs |= value != other.sample2(x, z);
+        }
+        assert!(differs);
+    }
+
+    #[test]
+    fn noise_is_zero_on_points_and_smooth() {
+        let noise = Noise::new(1, 1, 1.0, 0.5);
+        assert_eq!(noise.sample2(3.0, -2.0), 0.0);
+        for i in 0..100 {
+            let x = i as f64 * 0.05;
+            assert!((noise.sample2(x, 0.3) - noise.sample2(x + 0.001, 0.3)).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn noise_3d_is_deterministic() {
+        let noise = Noise::new(7, 3, 16.0, 0.5);
+        let mut values = Vec::new();
+        for i in 0..500 {
+            let (x, y, z) = (i as f64 * 0.77, i as f64 * -1.3, i as f64 * 0.4 - 50.0);
+            let value = noise.sample3(x, y, z);
+            assert!((-1.2..=1.2).contains(&value));
+            assert_eq!(value, Noise::new(7, 3, 16.0, 0.5).sample3(x, y, z));
+            values.push(value);
+        }
+        assert!(values.iter().any(|&v| v > 0.2) && values.iter().any(|&v| v < -0.2));
+    }
+
+    #[test]
+    fn random_numbers_repeat_for_a_seed() {
+        let (mut first, mut second) = (Random::new(99), Random::new(99));
+        for _ in 0..100 {
+            assert_eq!(first.next_u64(), second.next_u64());
+            assert!(first.below(10) < 10);
+            assert!((0.0..1.0).contains(&first.fraction()));
+            second.below(10);
+            second.fraction();
+        }
+        assert_ne!(Random::new(1).next_u64(), Random::new(2).next_u64());
+    }
+}
@@ -0,0 +1,484 @@
+use glam::DVec3;
+
+use crate::resources::Database;
+
+use super::{BlockPos, World, BLOCK_STEPS};
+
+/// The width (and depth) of the player, in blocks.
+pub const PLAYER_WIDTH: f64 = 0.6;
+
+/// The height of the player, in blocks.
+pub const PLAYER_HEIGHT: f64 = 1.8;
+
+/// The highest ledge (in blocks) the player walks up without jumping, such
+/// as a slab.
+pub const STEP_HEIGHT: f64 = 0.5;
+
+/// How fast falling things speed up, in blocks per second per second.
+const GRAVITY: f64 = 32.0;
+
+/// The fastest things fall, in blocks per second.
+const TERMINAL_SPEED: f64 = 60.0;
+
+/// How fast the player leaves the ground when jumping, in blocks per second.
+/// This clears a block and a quarter.
+const JUMP_SPEED: f64 = 9.0;
+
+/// How fast the player walks, in blocks per second.
+const WALK_SPEED: f64 = 4.5;
+
+/// How quickly the player's speed along the ground changes to the speed they
+/// are walking at, per second for each unit of friction of the block under
+/// them.
+const ACCELERATION: f64 = 20.0;
+
+/// The friction used for the player's speed when they aren't on the ground,
+/// so they can steer a little in the air.
+const AIR_FRICTION: f64 = 0.1;
+
+/// The friction of the ground when the block under the middle of the player
+/// isn't solid, such as when they stand on an edge.
+const DEFAULT_FRICTION: f64 = 0.6;
+
+/// How far (in blocks) boxes can overlap and still be treated as touching,
+/// which allows for rounding errors.
+const EPSILON: f64 = 1e-7;
+
+/// A box lined up with the axes of the world.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    /// The lowest corner, in blocks.
+    pub min: DVec3,
+
+    /// The highest corner, in blocks.
+    pub max: DVec3,
+}
+
+/// The player: a box that falls, jumps, walks and collides with the solid
+/// blocks of the world.
+///
+/// # Notes
+///
+/// Movement is worked out one axis at a time (up and down first), with each
+/// axis stopping at the first box in the way.  Moving into a wall at an angle
+/// slides along it, and however far the player moves in one update, they
+/// can't pass through a block.
+///
+/// Blocks collide with their model's boxes (see [Database::collision_boxes]),
+/// so a player walking into a ledge no higher than [STEP_HEIGHT], such as a
+/// slab, steps up onto it.  Blocks in chunks that aren't loaded are treated
+/// as solid, so the player doesn't fall out of the world while it loads.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Player {
+    /// The middle of the bottom of the player, in blocks.
+    pub position: DVec3,
+
+    /// How fast the player is moving, in blocks per second.
+    pub velocity: DVec3,
+
+    /// Whether the player is standing on something.
+    pub on_ground: bool,
+}
+
+impl Aabb {
+    /// Creates a box from its lowest and highest corners.
+    pub fn new(min: DVec3, max: DVec3) -> Aabb {
+        Aabb { min, max }
+    }
+
+    /// Returns the box moved by an offset.
+    pub fn translated(self, offset: DVec3) -> Aabb {
+        Aabb::new(self.min + offset, self.max + offset)
+    }
+
+    /// Returns the box stretched to cover everything it passes through when
+    /// moved by an offset.
+    pub fn swept(self, offset: DVec3) -> Aabb {
+        Aabb::new(
+            self.min + offset.min(DVec3::ZERO),
+            self.max + offset.max(DVec3::ZERO),
+        )
+    }
+
+    /// Works out how far the box can move along an axis before it hits
+    /// another box.
+    ///
+    /// # Parameters
+    ///
+    /// * `other` - The box that may be in the way.
+    /// * `axis` - The axis to move along: 0 for x, 1 for y or 2 for z.
+    /// * `distance` - How far to move, which is negative to move down the
+    ///   axis.
+    ///
+    /// # Returns
+    ///
+    /// The distance moved, which is shorter than `distance` if the other box
+    /// is in the way.  Boxes that already overlap this one don't stop it.
+    ///
+    pub fn clip(&self, other: &Aabb, axis: usize, distance: f64) -> f64 {
+        let across = (0..3)
+            .filter(|&i| i != axis)
+            .all(|i| self.min[i] < other.max[i] - EPSILON && self.max[i] > other.min[i] + EPSILON);
+        if !across {
+            return distance;
+        }
+
+        if distance > 0.0 {
+            let gap = other.min[axis] - self.max[axis];
+            if gap >= -EPSILON {
+                return distance.min(gap.max(0.0));
+            }
+        } else if distance < 0.0 {
+            let gap = other.max[axis] - self.min[axis];
+            if gap <= EPSILON {
+                return distance.max(gap.min(0.0));
+            }
+        }
+        distance
+    }
+}
+
+impl Player {
+    /// Creates a player standing still at a position, which is the middle of
+    /// their feet.
+    pub fn new(position: DVec3) -> Player {
+        Player {
+            position,
+            velocity: DVec3::ZERO,
+            on_ground: false,
+        }
+    }
+
+    /// Returns the box taken up by the player.
+    pub fn bounds(&self) -> Aabb {
+        let half = PLAYER_WIDTH / 2.0;
+        Aabb::new(
+            self.position - DVec3::new(half, 0.0, half),
+            self.position + DVec3::new(half, PLAYER_HEIGHT, half),
+        )
+    }
+
+    /// Moves the player by their velocity, after speeding up towards the way
+    /// they are walking, jumping and falling.
+    ///
+    /// # Parameters
+    ///
+    /// * `world` - The world to collide with.
+    /// * `database` - The database of blocks, for their boxes and friction.
+    /// * `walk` - The way the player is walking along the ground, no longer
+    ///   than 1.  Its y is ignored.
+    /// * `jump` - Whether to jump, if the player is on the ground.
+    /// * `dt` - The time (in seconds) to move for.
+    ///
+    pub fn update(&mut self, world: &World, database: &Database, walk: DVec3, jump: bool, dt: f64) {
+        // The player speeds up and slows down along the ground by the
+        // friction of the block under them, so they slide on slippery
+        // blocks.
+        let friction = if self.on_ground {
+            self.ground_friction(world, database)
+        } else {
+            AIR_FRICTION
+        };
+        let blend = (ACCELERATION * friction * dt).min(1.0);
+        let target = DVec3::new(walk.x, 0.0, walk.z) * WALK_SPEED;
+        self.velocity.x += (target.x - self.velocity.x) * blend;
+        self.velocity.z += (target.z - self.velocity.z) * blend;
+
+        if jump && self.on_ground {
+            self.velocity.y = JUMP_SPEED;
+        }
+        self.velocity.y = (self.velocity.y - GRAVITY * dt).max(-TERMINAL_SPEED);
+
+        let delta = self.velocity * dt;
+        let moved = self.move_by(world, database, delta);
+        for axis in 0..3 {
+            if moved[axis] != delta[axis] {
+                self.velocity[axis] = 0.0;
+            }
+        }
+    }
+
+    /// Moves the player as far as they can go by an offset, stepping up
+    /// ledges if they are on the ground.
+    ///
+    /// # Returns
+    ///
+    /// How far the player moved.
+    ///
+    pub fn move_by(&mut self, world: &World, database: &Database, delta: DVec3) -> DVec3 {
+        let start = self.bounds();
+        let reach = start.swept(delta).swept(DVec3::new(0.0, STEP_HEIGHT, 0.0));
+        let boxes = collision_boxes(world, database, reach);
+
+        let (_, mut moved) = sweep(&boxes, start, delta);
+        let blocked = moved.x != delta.x || moved.z != delta.z;
+        if self.on_ground && blocked {
+            // Try lifting the player up, moving them along and dropping them
+            // back down, and keep that if it gets them further.
+            let (lifted, up) = sweep(&boxes, start, DVec3::new(0.0, STEP_HEIGHT, 0.0));
+            let (across, along) = sweep(&boxes, lifted, DVec3::new(delta.x, 0.0, delta.z));
+            let (dropped, _) = sweep(
+                &boxes,
+                across,
+                DVec3::new(0.0, delta.y.min(0.0) - up.y, 0.0),
+            );
+            if along.x.hypot(along.z) > moved.x.hypot(moved.z) + EPSILON {
+                moved = dropped.min - start.min;
+            }
+        }
+
+        self.position += moved;
+        self.on_ground = delta.y < 0.0 && moved.y > delta.y;
+        moved
+    }
+
+    /// Returns the friction of the block the middle of the player stands on.
+    fn ground_friction(&self, world: &World, database: &Database) -> f64 {
+        let below = self.position - DVec3::new(0.0, 0.01, 0.0);
+        world
+            .block(BlockPos::containing(below.x, below.y, below.z))
+            .filter(|&block| database.is_solid(block))
+            .and_then(|block| database.friction(block))
+            .map_or(DEFAULT_FRICTION, f64::from)
+    }
+}
+
+/// Returns the boxes of the solid blocks touching a region.  Blocks in
+/// chunks that aren't loaded fill their whole space.
+fn collision_boxes(world: &World, database: &Database, region: Aabb) -> Vec<Aabb> {
+    let low = region.min.floor();
+    let high = region.max.floor();
+    let step = 1.0 / BLOCK_STEPS as f64;
+    let mut boxes = Vec::new();
+    for y in low.y as i64..=high.y as i64 {
+        for z in low.z as i64..=high.z as i64 {
+            for x in low.x as i64..=high.x as i64 {
+                let corner = DVec3::new(x as f64, y as f64, z as f64);
+                match world.block(BlockPos::new(x, y, z)) {
+                    Some(block) => {
+                        boxes.extend(database.collision_boxes(block).iter().map(|[from, to]| {
+                            Aabb::new(
+                                corner + DVec3::from(from.map(f64::from)) * step,
+                                corner + DVec3::from(to.map(f64::from)) * step,
+                            )
+                        }))
+                    }
+                    None => boxes.push(Aabb::new(corner, corner + DVec3::ONE)),
+                }
+            }
+        }
+    }
+    boxes
+}
+
+/// Moves a box by an offset one axis at a time, up and down first, stopping
+/// at the boxes in the way.
+///
+/// # Returns
+///
+/// The moved box and how far it moved.
+///
+fn sweep(boxes: &[Aabb], mut bounds: Aabb, delta: DVec3) -> (Aabb, DVec3) {
+    let mut moved = DVec3::ZERO;
+    for axis in [1, 0, 2] {
+        let distance = boxes.iter().fold(delta[axis], |distance, other| {
+            bounds.clip(other, axis, distance)
+        });
+        moved[axis] = distance;
+        let mut offset = DVec3::ZERO;
+        offset[axis] = distance;
+        bounds = bounds.translated(offset);
+    }
+    (bounds, moved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::{BlockProperties, Model};
+    use crate::world::{BlockId, Chunk, ChunkPos};
+
+    const S: u32 = BLOCK_STEPS;
+
+    /// The length of a tick in the tests, in seconds.
+    const DT: f64 = 0.05;
+
+    /// Creates a database with stone (block 0), ice (block 1), a slab
+    /// (block 2) and a plant that isn't solid (block 3).
+    fn database() -> Database {
+        let ice = BlockProperties {
+            friction: 0.02,
+            ..Default::default()
+        };
+        let plant = BlockProperties {
+            solid: false,
+            ..Default::default()
+        };
+        let mut db = Database::test_blocks(&["stone"])
+            .with_block("ice", 0, ice, None)
+            .with_block("slab", 0, BlockProperties::default(), Some(0))
+            .with_block("plant", 0, plant, None);
+        db.models
+            .push(Model::new(vec![]).with_boxes(vec![[[0; 3], [S, S / 2, S]]]));
+        db
+    }
+
+    fn stone() -> BlockId {
+        BlockId::from_index(0)
+    }
+
+    fn ice() -> BlockId {
+        BlockId::from_index(1)
+    }
+
+    fn slab() -> BlockId {
+        BlockId::from_index(2)
+    }
+
+    fn plant() -> BlockId {
+        BlockId::from_index(3)
+    }
+
+    /// Creates a world of air chunks around the origin, with a floor of a
+    /// block filling y = 0 of the chunk at the origin.
+    fn world(floor: BlockId) -> World {
+        let mut world = World::new(1);
+        world.update_loaded(ChunkPos::default(), |_| Chunk::new());
+        for z in 0..16 {
+            for x in 0..16 {
+                world.set_block(BlockPos::new(x, 0, z), floor);
+            }
+        }
+        world
+    }
+
+    /// Creates a stone-floored world with blocks placed in it.
+    fn world_with(block: BlockId, positions: &[(i64, i64, i64)]) -> World {
+        let mut world = world(stone());
+        for &(x, y, z) in positions {
+            world.set_block(BlockPos::new(x, y, z), block);
+        }
+        world
+    }
+
+    /// Runs ticks of a player walking one way.
+    fn walk(player: &mut Player, world: &World, walk: DVec3, ticks: usize) {
+        let db = database();
+        for _ in 0..ticks {
+            player.update(world, &db, walk, false, DT);
+        }
+    }
+
+    fn assert_near(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-6, "{a} != {b}");
+    }
+
+    #[test]
+    fn players_fall_onto_the_ground() {
+        let world = world(stone());
+        let mut player = Player::new(DVec3::new(8.5, 6.0, 8.5));
+        walk(&mut player, &world, DVec3::ZERO, 40);
+        assert_near(player.position.y, 1.0);
+        assert!(player.on_ground);
+        assert_eq!(player.velocity, DVec3::ZERO);
+
+        // Plants don't hold anything up.
+        let world = world_with(plant(), &[(8, 1, 8)]);
+        let mut player = Player::new(DVec3::new(8.5, 2.0, 8.5));
+        walk(&mut player, &world, DVec3::ZERO, 20);
+        assert_near(player.position.y, 1.0);
+    }
+
+    #[test]
+    fn fast_moves_stop_at_blocks() {
+        let world = world_with(stone(), &[(12, 1, 8), (12, 2, 8)]);
+        let db = database();
+        let mut player = Player::new(DVec3::new(8.5, 12.0, 8.5));
+        let moved = player.move_by(&world, &db, DVec3::new(0.0, -40.0, 0.0));
+        assert_near(moved.y, -11.0);
+        assert!(player.on_ground);
+
+        let moved = player.move_by(&world, &db, DVec3::new(100.0, 0.0, 0.0));
+        assert_near(moved.x, 12.0 - 8.5 - PLAYER_WIDTH / 2.0);
+    }
+
+    #[test]
+    fn walls_are_slid_along() {
+        let wall: Vec<_> = (0..16).flat_map(|z| [(10, 1, z), (10, 2, z)]).collect();
+        let world = world_with(stone(), &wall);
+        let mut player = Player::new(DVec3::new(8.5, 1.0, 4.5));
+        player.on_ground = true;
+        walk(
+            &mut player,
+            &world,
+            DVec3::new(1.0, 0.0, 1.0).normalize(),
+            40,
+        );
+        assert_near(player.position.x, 10.0 - PLAYER_WIDTH / 2.0);
+        assert!(player.position.z > 8.0, "{}", player.position);
+        assert_eq!(player.velocity.x, 0.0);
+    }
+
+    #[test]
+    fn slabs_are_stepped_up_but_blocks_are_not() {
+        let step: Vec<_> = (0..16)
+            .flat_map(|z| (10..16).map(move |x| (x, 1, z)))
+            .collect();
+        let world = world_with(slab(), &step);
+        let mut player = Player::new(DVec3::new(8.5, 1.0, 8.5));
+        player.on_ground = true;
+        walk(&mut player, &world, DVec3::X, 20);
+        assert!(player.position.x > 10.5);
+        assert_near(player.position.y, 1.5);
+
+        let world = world_with(stone(), &step);
+        let mut player = Player::new(DVec3::new(8.5, 1.0, 8.5));
+        player.on_ground = true;
+        walk(&mut player, &world, DVec3::X, 20);
+        assert_near(player.position.x, 10.0 - PLAYER_WIDTH / 2.0);
+        assert_near(player.position.y, 1.0);
+    }
+
+    #[test]
+    fn jumping_clears_a_block() {
+        let world = world(stone());
+        let db = database();
+        let mut player = Player::new(DVec3::new(8.5, 1.0, 8.5));
+        player.on_ground = true;
+        let mut highest: f64 = 0.0;
+        player.update(&world, &db, DVec3::ZERO, true, DT);
+        assert!(!player.on_ground);
+        for _ in 0..20 {
+            player.update(&world, &db, DVec3::ZERO, false, DT);
+            highest = highest.max(player.position.y);
+        }
+        assert!(highest > 2.0 && highest < 2.5, "{highest}");
+        assert!(player.on_ground);
+        assert_near(player.position.y, 1.0);
+    }
+
+    #[test]
+    fn ice_is_slippery() {
+        let slide = |floor| {
+            let world = world(floor);
+            let mut player = Player::new(DVec3::new(1.5, 1.0, 8.5));
+            player.on_ground = true;
+            player.velocity.x = WALK_SPEED;
+            walk(&mut player, &world, DVec3::ZERO, 10);
+            player.position.x - 1.5
+        };
+        let (on_stone, on_ice) = (slide(stone()), slide(ice()));
+        assert!(on_stone < 0.5, "{on_stone}");
+        assert!(on_ice > 2.0 * on_stone, "{on_ice} vs {on_stone}");
+    }
+
+    #[test]
+    fn unloaded_chunks_are_solid() {
+        let world = World::new(1);
+        let mut player = Player::new(DVec3::new(0.5, 5.0, 0.5));
+        walk(&mut player, &world, DVec3::ZERO, 10);
+        assert_eq!(player.position, DVec3::new(0.5, 5.0, 0.5));
+        assert!(player.on_ground);
+    }
+}
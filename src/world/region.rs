@@ -0,0 +1,832 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use tracing::{info, warn};
+
+use super::{BlockId, Chunk, ChunkPos, LocalPos, World, CHUNK_SIZE, CHUNK_VOLUME};
+use crate::resources::Database;
+
+/// The number of chunks along the x and z sides of a region.  Each region
+/// holds a single layer of chunks.
+pub const REGION_SIZE: i64 = 32;
+
+/// The version of the region file format that is written.  Files from later
+/// versions are refused.
+pub const REGION_VERSION: u32 = 1;
+
+/// The name of the block that blocks missing from the database are loaded
+/// as.  If the database doesn't have it either, they are loaded as air.
+/// Either way their own names are kept and they are saved under them again.
+pub const MISSING_BLOCK: &str = "missing";
+
+/// The number of chunks in a region.
+const REGION_CHUNKS: usize = (REGION_SIZE * REGION_SIZE) as usize;
+
+/// The bytes that every region file starts with.
+const MAGIC: &[u8; 4] = b"DLVR";
+
+/// The chunk data is stored as it is.
+const UNCOMPRESSED: u8 = 0;
+
+/// The chunk data is compressed with zlib.
+const ZLIB: u8 = 1;
+
+/// The chunks of one region of the world, as stored in a region file.
+///
+/// # Notes
+///
+/// A region file is laid out as follows, with all numbers little endian:
+///
+/// | Size              | Field                                               |
+/// |-------------------|-----------------------------------------------------|
+/// | 4                 | `DLVR`                                              |
+/// | 4                 | format version                                      |
+/// | 8 × 1024          | offset and length of each chunk (0 if not saved)    |
+/// | 4 + ..            | block names                                         |
+/// | 4 + ..            | biome names                                         |
+/// | ..                | the data of each chunk                              |
+///
+/// The offset and length of each chunk are 4 bytes each, and both are 0 for
+/// a chunk that isn't saved.  The block and biome names are each a 4 byte
+/// count followed by the names, each as a 2 byte length and UTF-8.
+///
+/// The data of each chunk starts with a byte for how it is compressed and
+/// its length (4 bytes) before compression.  Once decompressed, it is the
+/// blocks as runs of a 2 byte count and a 2 byte block (0 for air, otherwise
+/// 1 more than the index of its name), in the same order as [Chunk::iter],
+/// followed by the biome of each column as an index into the biome names.
+///
+/// Blocks and biomes are saved by name, so a world still loads after the
+/// database is reordered.
+///
+#[derive(Clone, Debug)]
+pub struct RegionFile {
+    /// The names of the blocks used by the chunks (see
+    /// [Database::block_key]).
+    blocks: Vec<String>,
+
+    /// The names of the biomes used by the chunks.
+    biomes: Vec<String>,
+
+    /// The stored data of each chunk, if it has been saved.
+    chunks: Vec<Option<Vec<u8>>>,
+}
+
+/// Saves chunks to and loads them from the region files in a directory.
+///
+/// Region files are read when first used and kept in memory until they are
+/// dropped by [RegionStore::forget_unused].  Saved chunks are only written to
+/// disk by [RegionStore::flush], or by taking the regions
+/// to write with [RegionStore::take_writes] so that the store can be used
+/// while they are written.
+pub struct RegionStore {
+    /// The directory holding the region files.
+    dir: PathBuf,
+
+    /// The regions that have been read or saved to, keyed by the position of
+    /// their first chunk divided by the region size.
+    regions: HashMap<(i64, i64, i64), RegionFile>,
+
+    /// The regions with chunks saved since they were last written.
+    dirty: HashSet<(i64, i64, i64)>,
+
+    /// The regions taken by [RegionStore::take_writes] that haven't been
+    /// handed back to [RegionStore::written] yet.
+    writing: HashSet<(i64, i64, i64)>,
+}
+
+/// A region taken from a [RegionStore] to be written to its file.
+pub struct RegionWrite {
+    /// The position of the region (see [region_pos]).
+    key: (i64, i64, i64),
+
+    /// The path of the region file.
+    path: PathBuf,
+
+    /// The bytes of the region file.
+    bytes: Vec<u8>,
+}
+
+impl RegionFile {
+    /// Creates a region with no chunks saved.
+    pub fn new() -> RegionFile {
+        RegionFile {
+            blocks: Vec::new(),
+            biomes: Vec::new(),
+            chunks: vec![None; REGION_CHUNKS],
+        }
+    }
+
+    /// Reads a region from the bytes of a region file.
+    pub fn read(bytes: &[u8]) -> io::Result<RegionFile> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("not a region file"));
+        }
+        let version = reader.u32()?;
+        if version > REGION_VERSION {
+            return Err(invalid(&format!(
+                "region file version {version} is newer than {REGION_VERSION}"
+            )));
+        }
+
+        let table = (0..REGION_CHUNKS)
+            .map(|_| Ok((reader.u32()? as usize, reader.u32()? as usize)))
+            .collect::<io::Result<Vec<_>>>()?;
+        let blocks = reader.names()?;
+        let biomes = reader.names()?;
+        let chunks = table
+            .into_iter()
+            .map(|(offset, length)| match length {
+                0 => Ok(None),
+                _ => bytes
+                    .get(offset..offset + length)
+                    .map(|data| Some(data.to_vec()))
+                    .ok_or_else(|| invalid("chunk data is past the end of the file")),
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(RegionFile {
+            blocks,
+            biomes,
+            chunks,
+        })
+    }
+
+    /// Returns the bytes of the region file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut names = Vec::new();
+        for list in [&self.blocks, &self.biomes] {
+            names.extend_from_slice(&(list.len() as u32).to_le_bytes());
+            for name in list {
+                names.extend_from_slice(&(name.len() as u16).to_le_bytes());
+                names.extend_from_slice(name.as_bytes());
+            }
+        }
+
+        let header = MAGIC.len() + 4 + REGION_CHUNKS * 8 + names.len();
+        let mut bytes = Vec::with_capacity(header);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&REGION_VERSION.to_le_bytes());
+        let mut offset = header;
+        for data in &self.chunks {
+            let length = data.as_ref().map_or(0, |data| data.len());
+            let start = if length == 0 { 0 } else { offset };
+            bytes.extend_from_slice(&(start as u32).to_le_bytes());
+            bytes.extend_from_slice(&(length as u32).to_le_bytes());
+            offset += length;
+        }
+        bytes.extend_from_slice(&names);
+        for data in self.chunks.iter().flatten() {
+            bytes.extend_from_slice(data);
+        }
+        bytes
+    }
+
+    /// Returns true if a chunk of the region has been saved.
+    pub fn contains(&self, pos: ChunkPos) -> bool {
+        self.chunks[chunk_index(pos)].is_some()
+    }
+
+    /// Returns the names of the blocks used by the region that aren't in a
+    /// database.
+    pub fn missing_blocks<'a>(&'a self, database: &'a Database) -> impl Iterator<Item = &'a str> {
+        self.blocks
+            .iter()
+            .filter(|name| database.block_from_key(name).is_none())
+            .map(String::as_str)
+    }
+
+    /// Loads a chunk of the region.
+    ///
+    /// # Returns
+    ///
+    /// The chunk, or `None` if it hasn't been saved.  Blocks that are missing
+    /// from the database are loaded as the [MISSING_BLOCK] block, or as air
+    /// if the database doesn't have that either.  The chunk keeps the names
+    /// of these blocks, so they are saved under their own names again (see
+    /// [Chunk::unknown_key]).
+    ///
+    pub fn load(&self, pos: ChunkPos, database: &Database) -> io::Result<Option<Chunk>> {
+        let Some(stored) = &self.chunks[chunk_index(pos)] else {
+            return Ok(None);
+        };
+        let data = decompress(stored)?;
+
+        let missing = database.block_id(MISSING_BLOCK).unwrap_or(BlockId::AIR);
+        let blocks = std::iter::once((BlockId::AIR, None))
+            .chain(
+                self.blocks
+                    .iter()
+                    .map(|name| match database.block_from_key(name) {
+                        Some(block) => (block, None),
+                        None => (missing, Some(Arc::<str>::from(name.as_str()))),
+                    }),
+            )
+            .collect::<Vec<_>>();
+
+        let mut reader = Reader {
+            bytes: &data,
+            pos: 0,
+        };
+        let mut chunk = Chunk::new();
+        let mut index = 0;
+        while index < CHUNK_VOLUME {
+            let count = reader.u16()? as usize;
+            let (block, unknown) = blocks
+                .get(reader.u16()? as usize)
+                .ok_or_else(|| invalid("unknown block in chunk data"))?;
+            let block = *block;
+            if count == 0 || index + count > CHUNK_VOLUME {
+                return Err(invalid("bad run of blocks in chunk data"));
+            }
+            if let Some(key) = unknown {
+                for index in index..index + count {
+                    chunk.set_unknown(LocalPos::from_index(index), block, key.clone());
+                }
+            } else if count == CHUNK_VOLUME {
+                chunk = Chunk::filled(block);
+            } else if !block.is_air() {
+                for index in index..index + count {
+                    chunk.set(LocalPos::from_index(index), block);
+                }
+            }
+            index += count;
+        }
+
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let name = self
+                    .biomes
+                    .get(reader.u16()? as usize)
+                    .ok_or_else(|| invalid("unknown biome in chunk data"))?;
+                chunk.set_biome(x, z, database.biome_index(name).unwrap_or(0));
+            }
+        }
+        Ok(Some(chunk))
+    }
+
+    /// Saves a chunk into the region, replacing it if it was saved before.
+    /// Placeholders for unknown blocks are saved under the names they were
+    /// loaded from.
+    pub fn save(&mut self, pos: ChunkPos, chunk: &Chunk, database: &Database) {
+        let ids = chunk
+            .block_counts()
+            .map(|(block, _)| (block, self.block_number(block, database)))
+            .collect::<HashMap<_, _>>();
+
+        let mut data = Vec::new();
+        let mut blocks = chunk
+            .iter()
+            .map(|(pos, block)| match chunk.unknown_key(pos) {
+                Some(key) => self.name_number(Names::Blocks, key) + 1,
+                None => ids[&block],
+            })
+            .peekable();
+        while let Some(block) = blocks.next() {
+            let mut count = 1u16;
+            while blocks.next_if_eq(&block).is_some() {
+                count += 1;
+            }
+            data.extend_from_slice(&count.to_le_bytes());
+            data.extend_from_slice(&block.to_le_bytes());
+        }
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let name = database
+                    .biome(chunk.biome(x, z))
+                    .map_or("", |biome| biome.name());
+                let number = self.name_number(Names::Biomes, name);
+                data.extend_from_slice(&number.to_le_bytes());
+            }
+        }
+
+        self.chunks[chunk_index(pos)] = Some(compress(&data));
+    }
+
+    /// Returns the number a block is saved as, adding its name to the region
+    /// if it is new.
+    fn block_number(&mut self, block: BlockId, database: &Database) -> u16 {
+        if block.is_air() {
+            return 0;
+        }
+        let name = database
+            .block_key(block)
+            .unwrap_or_else(|| MISSING_BLOCK.into());
+        self.name_number(Names::Blocks, &name) + 1
+    }
+
+    /// Returns the index of a name in one of the region's lists of names,
+    /// adding it if it is new.
+    fn name_number(&mut self, names: Names, name: &str) -> u16 {
+        let list = match names {
+            Names::Blocks => &mut self.blocks,
+            Names::Biomes => &mut self.biomes,
+        };
+        let index = list.iter().position(|n| n == name).unwrap_or_else(|| {
+            list.push(name.into());
+            list.len() - 1
+        });
+        index as u16
+    }
+}
+
+impl Default for RegionFile {
+    fn default() -> Self {
+        RegionFile::new()
+    }
+}
+
+impl RegionStore {
+    /// Opens the region files in a directory, creating it if it doesn't exist.
+    pub fn open<P>(dir: P) -> io::Result<RegionStore>
+    where
+        P: AsRef<Path>,
+    {
+        fs::create_dir_all(&dir)?;
+        Ok(RegionStore {
+            dir: dir.as_ref().to_path_buf(),
+            regions: HashMap::new(),
+            dirty: HashSet::new(),
+            writing: HashSet::new(),
+        })
+    }
+
+    /// Loads a chunk, or returns `None` if it has never been saved.
+    pub fn load_chunk(&mut self, pos: ChunkPos, database: &Database) -> io::Result<Option<Chunk>> {
+        self.region(pos, database)?.load(pos, database)
+    }
+
+    /// Saves a chunk.  It is written to disk by the next
+    /// [RegionStore::flush] or [RegionStore::take_writes].
+    pub fn save_chunk(
+        &mut self,
+        pos: ChunkPos,
+        chunk: &Chunk,
+        database: &Database,
+    ) -> io::Result<()> {
+        self.region(pos, database)?.save(pos, chunk, database);
+        self.dirty.insert(region_pos(pos));
+        Ok(())
+    }
+
+    /// Saves every loaded chunk of a world and writes them to disk.
+    pub fn save_world(&mut self, world: &World, database: &Database) -> io::Result<()> {
+        for (pos, chunk) in world.chunks() {
+            self.save_chunk(pos, chunk, database)?;
+        }
+        self.flush()
+    }
+
+    /// Writes the regions with newly saved chunks to disk.
+    ///
+    /// # Notes
+    ///
+    /// Every region is tried even if an earlier one fails, and the ones that
+    /// fail are tried again by the next flush.
+    ///
+    pub fn flush(&mut self) -> io::Result<()> {
+        let mut result = Ok(());
+        for write in self.take_writes() {
+            let written = write.write();
+            result = result.and(self.written(write, written));
+        }
+        result
+    }
+
+    /// Takes the regions with newly saved chunks, to be written with
+    /// [RegionWrite::write] and handed back with [RegionStore::written].
+    /// Regions that are still being written are left for the next time.
+    pub fn take_writes(&mut self) -> Vec<RegionWrite> {
+        let keys = self
+            .dirty
+            .iter()
+            .filter(|key| !self.writing.contains(key))
+            .copied()
+            .collect::<Vec<_>>();
+        keys.into_iter()
+            .map(|key| {
+                self.dirty.remove(&key);
+                self.writing.insert(key);
+                RegionWrite {
+                    key,
+                    path: self.dir.join(region_file_name(key)),
+                    bytes: self.regions[&key].to_bytes(),
+                }
+            })
+            .collect()
+    }
+
+    /// Hands back a region taken by [RegionStore::take_writes] once it has
+    /// been written.  If writing it failed, it is written again next time.
+    ///
+    /// # Returns
+    ///
+    /// The result of writing the region.
+    ///
+    pub fn written(&mut self, write: RegionWrite, result: io::Result<()>) -> io::Result<()> {
+        self.writing.remove(&write.key);
+        if result.is_err() {
+            self.dirty.insert(write.key);
+        }
+        result
+    }
+
+    /// Drops the regions that have been written to disk, apart from the given
+    /// ones, so that regions the player has left don't stay in memory.  They
+    /// are read again if they are used again.
+    ///
+    /// # Parameters
+    ///
+    /// * `in_use` - The positions (see [region_pos]) of the regions holding
+    ///   loaded chunks.
+    ///
+    pub fn forget_unused(&mut self, in_use: &HashSet<(i64, i64, i64)>) {
+        self.regions.retain(|key, _| {
+            in_use.contains(key) || self.dirty.contains(key) || self.writing.contains(key)
+        });
+    }
+
+    /// Returns the region holding a chunk, reading it from disk the first
+    /// time.
+    fn region(&mut self, pos: ChunkPos, database: &Database) -> io::Result<&mut RegionFile> {
+        let key = region_pos(pos);
+        if !self.regions.contains_key(&key) {
+            let path = self.dir.join(region_file_name(key));
+            let region = match fs::read(&path) {
+                Ok(bytes) => {
+                    info!("Reading region file {path:?}");
+                    RegionFile::read(&bytes)?
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => RegionFile::new(),
+                Err(err) => return Err(err),
+            };
+            let placeholder = match database.block_id(MISSING_BLOCK) {
+                Some(_) => MISSING_BLOCK,
+                None => "air",
+            };
+            for name in region.missing_blocks(database) {
+                warn!("Block {name:?} in {path:?} is missing, loading it as {placeholder:?}");
+            }
+            self.regions.insert(key, region);
+        }
+        Ok(self.regions.get_mut(&key).unwrap())
+    }
+}
+
+impl RegionWrite {
+    /// Writes the region to its file.
+    ///
+    /// # Notes
+    ///
+    /// The file is written under a temporary name and then renamed over the
+    /// old one, so a crash part way through leaves the old file whole.
+    ///
+    pub fn write(&self) -> io::Result<()> {
+        let temporary = self.path.with_extension("tmp");
+        let mut file = fs::File::create(&temporary)?;
+        file.write_all(&self.bytes)?;
+        file.sync_all()?;
+        fs::rename(&temporary, &self.path)
+    }
+}
+
+/// The lists of names in a region file.
+#[derive(Clone, Copy)]
+enum Names {
+    Blocks,
+    Biomes,
+}
+
+/// Reads numbers and names from the bytes of a region file.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + length)
+            .ok_or_else(|| invalid("unexpected end of data"))?;
+        self.pos += length;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn names(&mut self) -> io::Result<Vec<String>> {
+        (0..self.u32()?)
+            .map(|_| {
+                let length = self.u16()? as usize;
+                String::from_utf8(self.take(length)?.to_vec()).map_err(|_| invalid("bad name"))
+            })
+            .collect()
+    }
+}
+
+/// Returns the position of the region holding a chunk.
+pub fn region_pos(pos: ChunkPos) -> (i64, i64, i64) {
+    (
+        pos.x.div_euclid(REGION_SIZE),
+        pos.y,
+        pos.z.div_euclid(REGION_SIZE),
+    )
+}
+
+/// Returns the name of the file of a region.
+fn region_file_name((x, y, z): (i64, i64, i64)) -> String {
+    format!("r.{x}.{y}.{z}.region")
+}
+
+/// Returns the index of a chunk within its region.
+fn chunk_index(pos: ChunkPos) -> usize {
+    (pos.z.rem_euclid(REGION_SIZE) * REGION_SIZE + pos.x.rem_euclid(REGION_SIZE)) as usize
+}
+
+/// Compresses the data of a chunk, unless that would make it bigger.
+fn compress(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    let compressed = encoder
+        .write_all(data)
+        .and_then(|_| encoder.finish())
+        .ok()
+        .filter(|compressed| compressed.len() < data.len());
+
+    let (method, body) = match &compressed {
+        Some(compressed) => (ZLIB, compressed.as_slice()),
+        None => (UNCOMPRESSED, data),
+    };
+    let mut stored = vec![method];
+    stored.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    stored.extend_from_slice(body);
+    stored
+}
+
+/// Decompresses the stored data of a chunk.
+fn decompress(stored: &[u8]) -> io::Result<Vec<u8>> {
+    let mut reader = Reader {
+        bytes: stored,
+        pos: 0,
+    };
+    let method = reader.take(1)?[0];
+    let length = reader.u32()? as usize;
+    let body = &stored[reader.pos..];
+    let data = match method {
+        UNCOMPRESSED => body.to_vec(),
+        ZLIB => {
+            let mut data = Vec::with_capacity(length);
+            ZlibDecoder::new(body).read_to_end(&mut data)?;
+            data
+        }
+        _ => return Err(invalid("unknown chunk compression")),
+    };
+    if data.len() != length {
+        return Err(invalid("chunk data has the wrong length"));
+    }
+    Ok(data)
+}
+
+/// Returns an error for a region file that can't be read.
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// A directory for a test to save regions in, which is removed when the test
+/// ends, whether it passes or not.
+#[cfg(test)]
+pub(crate) struct TestDir(PathBuf);
+
+#[cfg(test)]
+impl TestDir {
+    /// Names a directory after a test, removing anything left in it by an
+    /// earlier run.
+    pub(crate) fn new(name: &str) -> TestDir {
+        let path = std::env::temp_dir().join(format!("delve-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        TestDir(path)
+    }
+
+    /// Returns the path of the directory.
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        // A test may have left a file in its place.
+        let _ = fs::remove_dir_all(&self.0).or_else(|_| fs::remove_file(&self.0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a database with the named blocks, in order, and biomes called
+    /// "plains" and "desert".
+    fn database(names: &[&str]) -> Database {
+        let grass = BlockId::from_index(0);
+        Database::test_blocks(names)
+            .with_biome("plains", grass, grass)
+            .with_biome("desert", grass, grass)
+    }
+
+    /// Creates a chunk of stone below y = 4 with a dirt block on top, and a
+    /// desert in one corner.
+    fn chunk(db: &Database) -> Chunk {
+        let stone = db.block_id("stone").unwrap();
+        let dirt = db.block_id("dirt").unwrap();
+        let mut chunk = Chunk::new();
+        for y in 0..4 {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    chunk.set(LocalPos::new(x, y, z), stone);
+                }
+            }
+        }
+        chunk.set(LocalPos::new(3, 4, 5), dirt);
+        chunk.set_biome(0, 0, 1);
+        chunk
+    }
+
+    fn assert_same(a: &Chunk, b: &Chunk) {
+        assert!(a.iter().eq(b.iter()));
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                assert_eq!(a.biome(x, z), b.biome(x, z));
+            }
+        }
+    }
+
+    #[test]
+    fn chunks_survive_a_round_trip() {
+        let db = database(&["stone", "dirt"]);
+        let pos = ChunkPos::new(-3, 1, 40);
+        let mut region = RegionFile::new();
+        region.save(pos, &chunk(&db), &db);
+        region.save(ChunkPos::new(-2, 1, 40), &Chunk::filled(BlockId::AIR), &db);
+
+        let read = RegionFile::read(&region.to_bytes()).unwrap();
+        assert_same(&read.load(pos, &db).unwrap().unwrap(), &chunk(&db));
+        assert!(read
+            .load(ChunkPos::new(-2, 1, 40), &db)
+            .unwrap()
+            .unwrap()
+            .is_empty());
+        assert!(read.load(ChunkPos::new(-1, 1, 40), &db).unwrap().is_none());
+    }
+
+    #[test]
+    fn chunk_data_is_compressed() {
+        let db = database(&["stone", "dirt"]);
+        let mut region = RegionFile::new();
+        region.save(ChunkPos::new(0, 0, 0), &chunk(&db), &db);
+        let stored = region.chunks[0].as_ref().unwrap();
+        assert_eq!(stored[0], ZLIB);
+        assert!(stored.len() < decompress(stored).unwrap().len());
+    }
+
+    #[test]
+    fn blocks_are_found_by_name_after_reordering() {
+        let old = database(&["stone", "dirt"]);
+        let new = database(&["dirt", "sand", "stone"]);
+        let mut region = RegionFile::new();
+        region.save(ChunkPos::new(0, 0, 0), &chunk(&old), &old);
+
+        let read = RegionFile::read(&region.to_bytes()).unwrap();
+        let loaded = read.load(ChunkPos::new(0, 0, 0), &new).unwrap().unwrap();
+        assert_same(&loaded, &chunk(&new));
+    }
+
+    #[test]
+    fn missing_blocks_load_as_the_placeholder() {
+        let old = database(&["stone", "dirt"]);
+        let new = database(&["stone", MISSING_BLOCK]);
+        let mut region = RegionFile::new();
+        region.save(ChunkPos::new(0, 0, 0), &chunk(&old), &old);
+
+        let read = RegionFile::read(&region.to_bytes()).unwrap();
+        assert!(read.missing_blocks(&new).eq(["dirt"]));
+        let loaded = read.load(ChunkPos::new(0, 0, 0), &new).unwrap().unwrap();
+        assert_eq!(
+            loaded.get(LocalPos::new(3, 4, 5)),
+            new.block_id(MISSING_BLOCK).unwrap()
+        );
+        assert_eq!(
+            loaded.get(LocalPos::new(3, 3, 5)),
+            new.block_id("stone").unwrap()
+        );
+    }
+
+    #[test]
+    fn missing_blocks_keep_their_names() {
+        let old = database(&["stone", "dirt"]);
+        let dirt = LocalPos::new(3, 4, 5);
+        for new in [database(&["stone", MISSING_BLOCK]), database(&["stone"])] {
+            let mut region = RegionFile::new();
+            region.save(ChunkPos::new(0, 0, 0), &chunk(&old), &old);
+            let mut loaded = region.load(ChunkPos::new(0, 0, 0), &new).unwrap().unwrap();
+            assert_eq!(loaded.unknown_key(dirt).map(|key| &**key), Some("dirt"));
+
+            // Saving again with the new database keeps the dirt, but not once
+            // it has been replaced.
+            region.save(ChunkPos::new(0, 0, 0), &loaded, &new);
+            let reloaded = region.load(ChunkPos::new(0, 0, 0), &old).unwrap().unwrap();
+            assert_same(&reloaded, &chunk(&old));
+
+            loaded.set(dirt, BlockId::AIR);
+            assert!(loaded.unknown_key(dirt).is_none());
+            region.save(ChunkPos::new(0, 0, 0), &loaded, &new);
+            let reloaded = region.load(ChunkPos::new(0, 0, 0), &old).unwrap().unwrap();
+            assert!(reloaded.get(dirt).is_air());
+        }
+    }
+
+    #[test]
+    fn written_regions_are_forgotten() {
+        let db = database(&["stone", "dirt"]);
+        let dir = TestDir::new("region-forget");
+        let (near, far) = (ChunkPos::new(0, 0, 0), ChunkPos::new(100, 0, 0));
+        let mut store = RegionStore::open(dir.path()).unwrap();
+        store.save_chunk(near, &chunk(&db), &db).unwrap();
+        store.save_chunk(far, &chunk(&db), &db).unwrap();
+
+        // Regions that haven't been written are kept, and so are the ones in
+        // use.
+        store.forget_unused(&HashSet::new());
+        assert_eq!(store.regions.len(), 2);
+        store.flush().unwrap();
+        store.forget_unused(&HashSet::from([region_pos(near)]));
+        assert!(store.regions.keys().eq([&region_pos(near)]));
+        assert_same(&store.load_chunk(far, &db).unwrap().unwrap(), &chunk(&db));
+    }
+
+    #[test]
+    fn newer_and_broken_files_are_refused() {
+        let mut bytes = RegionFile::new().to_bytes();
+        assert!(RegionFile::read(&bytes).is_ok());
+        assert!(RegionFile::read(&bytes[..100]).is_err());
+        bytes[4..8].copy_from_slice(&(REGION_VERSION + 1).to_le_bytes());
+        assert!(RegionFile::read(&bytes).is_err());
+        assert!(RegionFile::read(b"nope").is_err());
+    }
+
+    #[test]
+    fn store_writes_regions_to_disk() {
+        let db = database(&["stone", "dirt"]);
+        let dir = TestDir::new("region");
+        let pos = ChunkPos::new(33, -1, -1);
+
+        let mut store = RegionStore::open(dir.path()).unwrap();
+        store.save_chunk(pos, &chunk(&db), &db).unwrap();
+        store.flush().unwrap();
+        assert!(dir.path().join("r.1.-1.-1.region").exists());
+
+        let mut store = RegionStore::open(dir.path()).unwrap();
+        assert_same(&store.load_chunk(pos, &db).unwrap().unwrap(), &chunk(&db));
+        assert!(store
+            .load_chunk(ChunkPos::new(0, 0, 0), &db)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn failed_flushes_are_tried_again() {
+        let db = database(&["stone", "dirt"]);
+        let dir = TestDir::new("region-retry");
+        let positions = [ChunkPos::new(0, 0, 0), ChunkPos::new(-40, 2, 70)];
+
+        let mut store = RegionStore::open(dir.path()).unwrap();
+        for pos in positions {
+            store.save_chunk(pos, &chunk(&db), &db).unwrap();
+        }
+        // With a file in the way of the directory, no region can be written.
+        fs::remove_dir_all(dir.path()).unwrap();
+        fs::write(dir.path(), b"").unwrap();
+        assert!(store.flush().is_err());
+
+        fs::remove_file(dir.path()).unwrap();
+        fs::create_dir(dir.path()).unwrap();
+        store.flush().unwrap();
+        let mut store = RegionStore::open(dir.path()).unwrap();
+        for pos in positions {
+            assert_same(&store.load_chunk(pos, &db).unwrap().unwrap(), &chunk(&db));
+        }
+    }
+}
@@ -0,0 +1,836 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+    io,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex, RwLock, RwLockReadGuard,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use tokio::runtime::Handle;
+use tracing::error;
+
+use super::{
+    chunks_within, region_pos, ChunkMesh, ChunkPos, FeaturePlacer, MeshMode, RegionStore,
+    TerrainGenerator, World,
+};
+use crate::resources::Database;
+
+/// How often the regions with unloaded chunks saved in them are written to
+/// disk.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Loads, lights and meshes the chunks around a centre (usually the player)
+/// on worker threads.
+///
+/// # Notes
+///
+/// Each chunk that comes into range goes through two kinds of job, which
+/// run on the blocking threads of the tokio runtime:
+///
+/// 1. A load job reads the chunk from the region files, or generates it if
+///    it has never been saved, then adds it to the world, places its
+///    features and lights it.
+/// 2. A mesh job builds the mesh of a chunk once it, or one of the chunks
+///    around it, has been lit.
+///
+/// Jobs wait in a queue and are started nearest the centre first, with no
+/// more running at once than there are cores.  When the centre moves, the
+/// queue is sorted again, and the jobs of chunks that have gone out of range
+/// are cancelled: queued jobs are never started, and running ones throw
+/// their work away.  Chunks that go out of range are saved to the region
+/// files and removed from the world by an unload job.
+///
+/// Saved chunks are only kept in memory until a flush job writes their
+/// regions to disk, which happens every [FLUSH_INTERVAL], whenever the last
+/// chunk of a region is unloaded and when the world is saved.  Flush jobs
+/// write the files without holding the region files' lock, so load jobs
+/// aren't held up by the disk, and then drop the regions with no chunks in
+/// the world from memory.
+///
+/// Finished meshes are held until they are asked for by
+/// [ChunkStreamer::update], which hands back only a few each time so that
+/// uploading them never holds up a frame.
+///
+/// The world is behind a lock that load and unload jobs write to and mesh
+/// jobs read from, so only one chunk is lit at a time.
+///
+pub struct ChunkStreamer {
+    /// Everything the jobs work on.
+    shared: Arc<Shared>,
+
+    /// The runtime that jobs are started on.
+    runtime: Handle,
+
+    /// The most jobs that run at once.
+    workers: usize,
+
+    /// The number of jobs running.
+    running: usize,
+
+    /// The distance (in chunks) from the centre within which chunks are
+    /// loaded.
+    render_distance: u32,
+
+    /// The chunk at the centre of the loaded area, once it has been set.
+    centre: Option<ChunkPos>,
+
+    /// The chunks that are loaded or on their way in or out of the world.
+    slots: HashMap<ChunkPos, Slot>,
+
+    /// The jobs waiting to start.
+    queue: BinaryHeap<Reverse<Queued>>,
+
+    /// The number of jobs queued so far, which keeps jobs of the same
+    /// priority in the order they were queued.
+    queued: u64,
+
+    /// Where finished jobs report back.
+    sender: Sender<Finished>,
+
+    /// Where finished jobs are received.
+    receiver: Receiver<Finished>,
+
+    /// The newest finished mesh of each chunk that hasn't been handed back.
+    ready: HashMap<ChunkPos, ChunkMesh>,
+
+    /// The chunks that have gone out of range since the last update.
+    unloaded: Vec<ChunkPos>,
+
+    /// When the last flush job was queued.
+    last_flush: Instant,
+}
+
+/// The chunks handed back by [ChunkStreamer::update].
+#[derive(Debug, Default)]
+pub struct StreamedChunks {
+    /// The finished meshes to upload, nearest the centre first.  A mesh
+    /// replaces any earlier mesh of the same chunk.
+    pub meshes: Vec<(ChunkPos, ChunkMesh)>,
+
+    /// The chunks that went out of range, whose meshes should be dropped.
+    pub unloaded: Vec<ChunkPos>,
+}
+
+/// The state that jobs share.
+struct Shared {
+    database: Arc<Database>,
+    generator: TerrainGenerator,
+    world: RwLock<World>,
+    regions: Mutex<RegionStore>,
+    features: Mutex<FeaturePlacer>,
+}
+
+/// A chunk that is loaded or on its way in or out of the world.
+struct Slot {
+    /// Set when the chunk goes out of range, to cancel its jobs.  A chunk
+    /// that comes back into range gets a new ticket.
+    cancelled: Arc<AtomicBool>,
+
+    /// What is happening to the chunk.
+    stage: Stage,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stage {
+    /// The chunk's load job is queued or running.
+    Loading,
+
+    /// The chunk went out of range while its load job was queued or
+    /// running.  `reload` is set if it has come back into range since.
+    Cancelled { reload: bool },
+
+    /// The chunk is in the world.  `meshing` is set while its mesh job is
+    /// queued or running, and `dirty` if it needs meshing again after that.
+    Loaded { meshing: bool, dirty: bool },
+
+    /// The chunk's unload job is queued or running.
+    Unloading { reload: bool },
+}
+
+/// A job waiting in the queue.  Unload jobs go first, then the jobs of the
+/// chunks nearest the centre.
+struct Queued {
+    unload: bool,
+    distance: i64,
+    order: u64,
+    job: Job,
+}
+
+enum Job {
+    Load(ChunkPos),
+    Mesh(ChunkPos),
+    Unload(Vec<ChunkPos>),
+    Flush(HashSet<(i64, i64, i64)>),
+}
+
+/// What a job reports back when it finishes.
+enum Finished {
+    /// A load job finished.  `changed` holds the chunks whose light changed
+    /// or that features were placed in, or is `None` if the job was
+    /// cancelled before the chunk was added to the world.
+    Loaded {
+        pos: ChunkPos,
+        changed: Option<Vec<ChunkPos>>,
+    },
+
+    /// A mesh job finished with the ticket it was queued with.  The mesh is
+    /// `None` if the job was cancelled.
+    Meshed {
+        pos: ChunkPos,
+        ticket: Arc<AtomicBool>,
+        mesh: Option<ChunkMesh>,
+    },
+
+    /// An unload job finished.
+    Unloaded(Vec<ChunkPos>),
+
+    /// A flush job finished.
+    Flushed,
+}
+
+impl ChunkStreamer {
+    /// Creates a chunk streamer with an empty world.
+    ///
+    /// # Parameters
+    ///
+    /// * `database` - The database that the chunks' block IDs refer to.
+    /// * `generator` - Generates the chunks that haven't been saved.
+    /// * `regions` - The region files that chunks are loaded from and saved
+    ///   to.
+    /// * `features` - Places the features of generated chunks.
+    /// * `render_distance` - The distance (in chunks) from the centre within
+    ///   which chunks are loaded.
+    ///
+    /// # Panics
+    ///
+    /// If called outside a tokio runtime.
+    ///
+    pub fn new(
+        database: Arc<Database>,
+        generator: TerrainGenerator,
+        regions: RegionStore,
+        features: FeaturePlacer,
+        render_distance: u32,
+    ) -> ChunkStreamer {
+        let (sender, receiver) = mpsc::channel();
+        ChunkStreamer {
+            shared: Arc::new(Shared {
+                database,
+                generator,
+                world: RwLock::new(World::new(render_distance)),
+                regions: Mutex::new(regions),
+                features: Mutex::new(features),
+            }),
+            runtime: Handle::current(),
+            workers: thread::available_parallelism().map_or(1, |n| n.get()),
+            running: 0,
+            render_distance,
+            centre: None,
+            slots: HashMap::new(),
+            queue: BinaryHeap::new(),
+            queued: 0,
+            sender,
+            receiver,
+            ready: HashMap::new(),
+            unloaded: Vec::new(),
+            last_flush: Instant::now(),
+        }
+    }
+
+    /// Returns the world.  This waits for any chunk being lit or unloaded.
+    pub fn world(&self) -> RwLockReadGuard<'_, World> {
+        self.shared.world.read().unwrap()
+    }
+
+    /// Returns true if no jobs are queued or running.
+    pub fn is_idle(&self) -> bool {
+        self.running == 0 && self.queue.is_empty()
+    }
+
+    /// Moves the centre of the loaded area, collects the jobs that have
+    /// finished and starts the next ones.
+    ///
+    /// # Parameters
+    ///
+    /// * `centre` - The chunk at the centre of the loaded area.
+    /// * `max_meshes` - The most meshes to hand back.
+    ///
+    /// # Returns
+    ///
+    /// Up to `max_meshes` finished meshes, nearest first, and the chunks that
+    /// went out of range.  The rest of the meshes are kept for later updates.
+    ///
+    pub fn update(&mut self, centre: ChunkPos, max_meshes: usize) -> StreamedChunks {
+        if self.centre != Some(centre) {
+            self.move_centre(centre);
+        }
+        while let Ok(finished) = self.receiver.try_recv() {
+            self.running -= 1;
+            self.finish(finished);
+        }
+        if self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.flush(self.regions_in_use());
+        }
+        self.start_jobs();
+
+        let mut ready = self.ready.keys().copied().collect::<Vec<_>>();
+        ready.sort_by_key(|pos| pos.distance_squared(centre));
+        let meshes = ready
+            .into_iter()
+            .take(max_meshes)
+            .filter_map(|pos| self.ready.remove(&pos).map(|mesh| (pos, mesh)))
+            .collect();
+        StreamedChunks {
+            meshes,
+            unloaded: std::mem::take(&mut self.unloaded),
+        }
+    }
+
+    /// Waits for the running jobs to finish, then saves every loaded chunk
+    /// and the blocks that features left for other chunks, and writes them
+    /// all to disk.
+    ///
+    /// # Parameters
+    ///
+    /// * `dir` - The directory that the blocks features left are saved in
+    ///   (see [FeaturePlacer::save]).
+    ///
+    pub fn save<P>(&mut self, dir: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        while self.running > 0 {
+            let Ok(finished) = self.receiver.recv() else {
+                break;
+            };
+            self.running -= 1;
+            self.finish(finished);
+        }
+        let world = self.shared.world.read().unwrap();
+        let database = &self.shared.database;
+        self.shared
+            .regions
+            .lock()
+            .unwrap()
+            .save_world(&world, database)?;
+        let mut features = self.shared.features.lock().unwrap();
+        for (pos, _) in world.chunks() {
+            features.chunk_saved(pos);
+        }
+        features.save(dir, database)
+    }
+
+    /// Cancels the jobs of the chunks that have gone out of range, queues
+    /// the chunks that have come into range and sorts the queue again.
+    fn move_centre(&mut self, centre: ChunkPos) {
+        self.centre = Some(centre);
+        let distance = self.render_distance as i64;
+
+        let mut unload = Vec::new();
+        for (&pos, slot) in &mut self.slots {
+            if pos.distance_squared(centre) <= distance * distance {
+                continue;
+            }
+            slot.cancelled.store(true, Ordering::Relaxed);
+            match &mut slot.stage {
+                Stage::Loading => slot.stage = Stage::Cancelled { reload: false },
+                Stage::Loaded { .. } => {
+                    slot.stage = Stage::Unloading { reload: false };
+                    unload.push(pos);
+                }
+                Stage::Cancelled { reload } | Stage::Unloading { reload } => *reload = false,
+            }
+            self.ready.remove(&pos);
+        }
+        self.unloaded.extend_from_slice(&unload);
+        if !unload.is_empty() {
+            self.push(Job::Unload(unload));
+        }
+
+        for pos in chunks_within(centre, self.render_distance) {
+            match self.slots.get_mut(&pos).map(|slot| &mut slot.stage) {
+                None => self.load(pos),
+                Some(Stage::Cancelled { reload } | Stage::Unloading { reload }) => *reload = true,
+                Some(_) => {}
+            }
+        }
+
+        self.queue = std::mem::take(&mut self.queue)
+            .into_iter()
+            .map(|Reverse(queued)| Reverse(self.queued(queued.job, queued.order)))
+            .collect();
+    }
+
+    /// Starts queued jobs until as many are running as there are workers.
+    /// Cancelled jobs are dropped without starting.
+    fn start_jobs(&mut self) {
+        while self.running < self.workers {
+            let Some(Reverse(queued)) = self.queue.pop() else {
+                break;
+            };
+            let cancelled = match &queued.job {
+                Job::Load(pos) | Job::Mesh(pos) => self.slots[pos].cancelled.clone(),
+                Job::Unload(_) | Job::Flush(_) => Arc::new(AtomicBool::new(false)),
+            };
+            if cancelled.load(Ordering::Relaxed) {
+                match queued.job {
+                    Job::Load(pos) => self.finish(Finished::Loaded { pos, changed: None }),
+                    Job::Mesh(pos) => self.finish(Finished::Meshed {
+                        pos,
+                        ticket: cancelled,
+                        mesh: None,
+                    }),
+                    Job::Unload(_) | Job::Flush(_) => {}
+                }
+                continue;
+            }
+
+            let shared = self.shared.clone();
+            let sender = self.sender.clone();
+            self.running += 1;
+            self.runtime.spawn_blocking(move || {
+                let finished = shared.run(queued.job, cancelled);
+                let _ = sender.send(finished);
+            });
+        }
+    }
+
+    /// Moves a chunk on to its next stage after one of its jobs finishes.
+    fn finish(&mut self, finished: Finished) {
+        match finished {
+            Finished::Loaded { pos, changed } => {
+                let Some(slot) = self.slots.get_mut(&pos) else {
+                    return;
+                };
+                match (slot.stage, changed) {
+                    (Stage::Loading, Some(changed)) => {
+                        slot.stage = Stage::Loaded {
+                            meshing: false,
+                            dirty: false,
+                        };
+                        for x in -1..=1 {
+                            for y in -1..=1 {
+                                for z in -1..=1 {
+                                    self.mesh(ChunkPos::new(pos.x + x, pos.y + y, pos.z + z));
+                                }
+                            }
+                        }
+                        for pos in changed {
+                            self.mesh(pos);
+                        }
+                    }
+                    (Stage::Cancelled { reload }, Some(_)) => {
+                        slot.stage = Stage::Unloading { reload };
+                        self.push(Job::Unload(vec![pos]));
+                    }
+                    (Stage::Cancelled { reload: true }, None) => self.load(pos),
+                    (Stage::Cancelled { reload: false }, None) => {
+                        self.slots.remove(&pos);
+                    }
+                    (stage, _) => unreachable!("chunk {pos:?} loaded while {stage:?}"),
+                }
+            }
+
+            Finished::Meshed { pos, ticket, mesh } => {
+                let Some(Slot {
+                    cancelled,
+                    stage: Stage::Loaded { meshing, dirty },
+                }) = self.slots.get_mut(&pos)
+                else {
+                    return;
+                };
+                if !Arc::ptr_eq(cancelled, &ticket) {
+                    return;
+                }
+                *meshing = false;
+                if let Some(mesh) = mesh {
+                    self.ready.insert(pos, mesh);
+                }
+                if std::mem::take(dirty) {
+                    self.mesh(pos);
+                }
+            }
+
+            Finished::Unloaded(unloaded) => {
+                for &pos in &unloaded {
+                    match self.slots.get(&pos).map(|slot| slot.stage) {
+                        Some(Stage::Unloading { reload: true }) => self.load(pos),
+                        Some(Stage::Unloading { reload: false }) => {
+                            self.slots.remove(&pos);
+                        }
+                        _ => {}
+                    }
+                }
+
+                // Regions left with no chunks in the world won't be saved to
+                // again for a while, so they are written straight away.
+                let in_use = self.regions_in_use();
+                if unloaded
+                    .iter()
+                    .any(|&pos| !in_use.contains(&region_pos(pos)))
+                {
+                    self.flush(in_use);
+                }
+            }
+
+            Finished::Flushed => {}
+        }
+    }
+
+    /// Returns the positions of the regions holding chunks that are loaded
+    /// or on their way in or out of the world.
+    fn regions_in_use(&self) -> HashSet<(i64, i64, i64)> {
+        self.slots.keys().map(|&pos| region_pos(pos)).collect()
+    }
+
+    /// Queues a flush job that keeps the given regions in memory.
+    fn flush(&mut self, in_use: HashSet<(i64, i64, i64)>) {
+        self.last_flush = Instant::now();
+        self.push(Job::Flush(in_use));
+    }
+
+    /// Queues the load job of a chunk, with a new ticket.
+    fn load(&mut self, pos: ChunkPos) {
+        self.slots.insert(
+            pos,
+            Slot {
+                cancelled: Arc::new(AtomicBool::new(false)),
+                stage: Stage::Loading,
+            },
+        );
+        self.push(Job::Load(pos));
+    }
+
+    /// Queues the mesh job of a chunk if it is loaded, or marks it to be
+    /// meshed again if its mesh job is already queued or running.
+    fn mesh(&mut self, pos: ChunkPos) {
+        let Some(Slot {
+            stage: Stage::Loaded { meshing, dirty },
+            ..
+        }) = self.slots.get_mut(&pos)
+        else {
+            return;
+        };
+        if *meshing {
+            *dirty = true;
+        } else {
+            *meshing = true;
+            self.push(Job::Mesh(pos));
+        }
+    }
+
+    /// Adds a job to the queue.
+    fn push(&mut self, job: Job) {
+        let queued = self.queued(job, self.queued);
+        self.queued += 1;
+        self.queue.push(Reverse(queued));
+    }
+
+    /// Returns a job ready for the queue, with its priority from the
+    /// current centre.
+    fn queued(&self, job: Job, order: u64) -> Queued {
+        let centre = self.centre.unwrap_or_default();
+        let (unload, distance) = match &job {
+            Job::Load(pos) | Job::Mesh(pos) => (false, pos.distance_squared(centre)),
+            Job::Unload(_) | Job::Flush(_) => (true, 0),
+        };
+        Queued {
+            unload,
+            distance,
+            order,
+            job,
+        }
+    }
+}
+
+impl Queued {
+    /// Returns what the queue is sorted by, smallest first.
+    fn key(&self) -> (Reverse<bool>, i64, u64) {
+        (Reverse(self.unload), self.distance, self.order)
+    }
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl Shared {
+    /// Runs a job on a worker thread.
+    fn run(&self, job: Job, cancelled: Arc<AtomicBool>) -> Finished {
+        let database = &self.database;
+        match job {
+            Job::Load(pos) => {
+                let saved = match self.regions.lock().unwrap().load_chunk(pos, database) {
+                    Ok(chunk) => chunk,
+                    Err(err) => {
+                        error!("Error loading chunk {pos:?}, generating it again: {err}");
+                        None
+                    }
+                };
+                let generated = saved.is_none();
+                let chunk = saved.unwrap_or_else(|| self.generator.generate(pos));
+
+                let mut world = self.world.write().unwrap();
+                if cancelled.load(Ordering::Relaxed) {
+                    return Finished::Loaded { pos, changed: None };
+                }
+                world.insert_chunk(pos, chunk);
+                let mut lit = vec![pos];
+                let mut features = self.features.lock().unwrap();
+                if generated {
+                    lit.extend(features.decorate(&mut world, &self.generator, database, pos));
+                } else {
+                    // Features in chunks made since this one was saved may
+                    // have left blocks for it.
+                    features.apply_deferred(&mut world, database, pos);
+                }
+                drop(features);
+                let mut changed = world.light_chunks(&lit, database);
+                changed.extend(lit);
+                Finished::Loaded {
+                    pos,
+                    changed: Some(changed),
+                }
+            }
+
+            Job::Mesh(pos) => {
+                let world = self.world.read().unwrap();
+                let mesh = (!cancelled.load(Ordering::Relaxed))
+                    .then(|| ChunkMesh::build(&world, pos, database, MeshMode::Greedy));
+                Finished::Meshed {
+                    pos,
+                    ticket: cancelled,
+                    mesh,
+                }
+            }
+
+            Job::Unload(unloaded) => {
+                // The chunks are taken out under the lock but saved after it
+                // is let go, so writing to disk doesn't hold up the world.
+                // They can't be loaded again until this job has finished.
+                let chunks = {
+                    let mut world = self.world.write().unwrap();
+                    unloaded
+                        .iter()
+                        .filter_map(|&pos| world.remove_chunk(pos).map(|chunk| (pos, chunk)))
+                        .collect::<Vec<_>>()
+                };
+                let mut regions = self.regions.lock().unwrap();
+                let saved = chunks
+                    .iter()
+                    .filter(
+                        |(pos, chunk)| match regions.save_chunk(*pos, chunk, database) {
+                            Ok(()) => true,
+                            Err(err) => {
+                                error!("Error saving chunk {pos:?}: {err}");
+                                false
+                            }
+                        },
+                    )
+                    .map(|&(pos, _)| pos)
+                    .collect::<Vec<_>>();
+                drop(regions);
+                let mut features = self.features.lock().unwrap();
+                for pos in saved {
+                    features.chunk_saved(pos);
+                }
+                Finished::Unloaded(unloaded)
+            }
+
+            Job::Flush(in_use) => {
+                // The files are written without the lock, so that chunks can
+                // still be loaded in the meantime.
+                let writes = self.regions.lock().unwrap().take_writes();
+                for write in writes {
+                    let result = write.write();
+                    if let Err(err) = self.regions.lock().unwrap().written(write, result) {
+                        error!("Error saving the world: {err}");
+                    }
+                }
+                self.regions.lock().unwrap().forget_unused(&in_use);
+                Finished::Flushed
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::world::{BlockId, TestDir};
+
+    /// Creates a database with grass (block 0), dirt (1) and stone (2), and a
+    /// single biome of grass over dirt.
+    fn database() -> Database {
+        Database::test_blocks(&["grass", "dirt", "stone"]).with_biome(
+            "plains",
+            BlockId::from_index(0),
+            BlockId::from_index(1),
+        )
+    }
+
+    /// Creates a streamer that saves to a test's directory.
+    fn streamer(dir: &TestDir) -> ChunkStreamer {
+        let db = Arc::new(database());
+        let generator = TerrainGenerator::new(7, &db).unwrap();
+        let regions = RegionStore::open(dir.path()).unwrap();
+        ChunkStreamer::new(db, generator, regions, FeaturePlacer::new(), 1)
+    }
+
+    /// Updates a streamer until all its jobs are done, collecting what it
+    /// hands back.
+    fn run(streamer: &mut ChunkStreamer, centre: ChunkPos) -> StreamedChunks {
+        let mut all = StreamedChunks::default();
+        loop {
+            let streamed = streamer.update(centre, 2);
+            assert!(streamed.meshes.len() <= 2);
+            all.meshes.extend(streamed.meshes);
+            all.unloaded.extend(streamed.unloaded);
+            if streamer.is_idle() && streamer.ready.is_empty() {
+                return all;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn loaded(streamer: &ChunkStreamer) -> HashSet<ChunkPos> {
+        streamer.world().chunks().map(|(pos, _)| pos).collect()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn chunks_are_loaded_and_meshed() {
+        let dir = TestDir::new("stream-load");
+        let mut streamer = streamer(&dir);
+        let centre = ChunkPos::new(0, 0, 0);
+        let streamed = run(&mut streamer, centre);
+
+        let expected = chunks_within(centre, 1);
+        assert_eq!(loaded(&streamer), HashSet::from_iter(expected.clone()));
+        assert_eq!(
+            streamed
+                .meshes
+                .iter()
+                .map(|(pos, _)| *pos)
+                .collect::<HashSet<_>>(),
+            HashSet::from_iter(expected.clone())
+        );
+        assert!(streamed.unloaded.is_empty());
+
+        // The last mesh of each chunk matches one built from the whole world.
+        let world = streamer.world();
+        let mut meshes = HashMap::new();
+        meshes.extend(streamed.meshes);
+        for pos in expected {
+            let mesh = ChunkMesh::build(&world, pos, &streamer.shared.database, MeshMode::Greedy);
+            assert_eq!(meshes[&pos], mesh, "chunk {pos:?}");
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn chunks_out_of_range_are_unloaded_and_saved() {
+        let dir = TestDir::new("stream-unload");
+        let mut streamer = streamer(&dir);
+        let first = ChunkPos::new(0, 0, 0);
+        run(&mut streamer, first);
+        let second = ChunkPos::new(5, 0, 0);
+        let streamed = run(&mut streamer, second);
+
+        assert_eq!(
+            loaded(&streamer),
+            HashSet::from_iter(chunks_within(second, 1))
+        );
+        assert_eq!(
+            streamed.unloaded.into_iter().collect::<HashSet<_>>(),
+            HashSet::from_iter(chunks_within(first, 1))
+        );
+        let mut regions = streamer.shared.regions.lock().unwrap();
+        let saved = regions
+            .load_chunk(first, &streamer.shared.database)
+            .unwrap()
+            .unwrap();
+        assert!(saved
+            .iter()
+            .eq(streamer.shared.generator.generate(first).iter()));
+
+        // The region west of the first chunk has no chunks left in the
+        // world, so it has been written to disk.
+        let west = ChunkPos::new(-1, 0, 0);
+        let mut stored = RegionStore::open(dir.path()).unwrap();
+        assert!(stored
+            .load_chunk(west, &streamer.shared.database)
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn saving_writes_loaded_chunks_to_disk() {
+        let dir = TestDir::new("stream-save");
+        let mut streamer = streamer(&dir);
+        let centre = ChunkPos::new(0, 0, 0);
+        run(&mut streamer, centre);
+        streamer.update(ChunkPos::new(8, 0, 0), 0);
+        streamer.save(dir.path()).unwrap();
+
+        let mut stored = RegionStore::open(dir.path()).unwrap();
+        for pos in chunks_within(centre, 1) {
+            let chunk = stored.load_chunk(pos, &streamer.shared.database).unwrap();
+            assert!(chunk.is_some(), "chunk {pos:?}");
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn stale_jobs_are_cancelled() {
+        let dir = TestDir::new("stream-cancel");
+        let mut streamer = streamer(&dir);
+        let centre = ChunkPos::new(0, 0, 0);
+        for x in [0, 10, 20, 0] {
+            streamer.update(ChunkPos::new(x, 0, 0), 0);
+        }
+        run(&mut streamer, centre);
+        let expected = HashSet::from_iter(chunks_within(centre, 1));
+        assert_eq!(loaded(&streamer), expected);
+        assert_eq!(HashSet::from_iter(streamer.slots.keys().copied()), expected);
+    }
+
+    #[test]
+    fn nearer_jobs_start_first() {
+        let mut queue = BinaryHeap::new();
+        for (order, (unload, distance)) in [(false, 4), (false, 1), (true, 9), (false, 1)]
+            .into_iter()
+            .enumerate()
+        {
+            queue.push(Reverse(Queued {
+                unload,
+                distance,
+                order: order as u64,
+                job: Job::Unload(vec![]),
+            }));
+        }
+        let order = std::iter::from_fn(|| queue.pop().map(|Reverse(queued)| queued.order))
+            .collect::<Vec<_>>();
+        assert_eq!(order, [2, 1, 3, 0]);
+    }
+}
@@ -0,0 +1,573 @@
+use super::{hash, BlockId, BlockPos, Chunk, ChunkPos, Face, LocalPos, Noise, Random, CHUNK_SIZE};
+use crate::resources::{Biome, Database, Ore};
+
+/// The height around which the ground lies.
+const BASE_HEIGHT: f64 = 0.0;
+
+/// How far (in blocks) the broad shape of the land rises and falls.
+const LAND_HEIGHT: f64 = 32.0;
+
+/// How far (in blocks) hills rise and fall on top of the land.
+const HILL_HEIGHT: f64 = 12.0;
+
+/// The least and most dirt there is under the grass.
+const SOIL_DEPTH: (i64, i64) = (2, 5);
+
+/// How far apart (in climate) a column can be from a biome and still have
+/// its height blended with the biome's.
+const BIOME_BLEND: f32 = 0.08;
+
+/// How far the climate noise is stretched, so that the climates at the ends
+/// of the range are still reached.
+const CLIMATE_SPREAD: f64 = 2.0;
+
+/// How far (in blocks) below the surface the large caves stay, so they don't
+/// open up into huge holes in the ground.
+const CAVE_COVER: i64 = 8;
+
+/// The cheese noise above which large caves are carved out.
+const CHEESE_THRESHOLD: f64 = 0.35;
+
+/// How close to zero both spaghetti noises must be to carve a tunnel.  Each
+/// noise is zero on a wavy sheet, and tunnels follow the lines where two
+/// sheets cross.
+const SPAGHETTI_WIDTH: f64 = 0.04;
+
+/// The number of blocks in the largest ore vein.  Veins can't be longer than
+/// a chunk, so only the chunks next to a chunk can start veins that reach
+/// into it.
+pub const MAX_VEIN_SIZE: u32 = CHUNK_SIZE as u32;
+
+/// Makes the terrain of a world from its seed.
+///
+/// The shape of the ground is made from layers of noise: a broad heightmap
+/// for the lie of the land and a finer one for hills.  Two more layers give
+/// each column a temperature and humidity, which pick its biome.  Biomes
+/// raise the ground and scale the hills, and their heights are blended near
+/// their borders so the ground changes smoothly from one to the next.  Each
+/// column has the biome's surface block on top, a few blocks of its filler
+/// under that and stone below.  Caves are carved out of the ground with 3D
+/// noise: large open caverns where one layer of noise is high ("cheese" caves)
+/// and long winding tunnels where two layers are both close to zero
+/// ("spaghetti" caves).  Veins of the ores declared in the database are then
+/// scattered through what is left.
+///
+/// The same seed always makes the same chunks, whatever order they are made
+/// in.
+#[derive(Clone, Debug)]
+pub struct TerrainGenerator {
+    /// The seed of the world.
+    seed: u64,
+
+    /// The broad shape of the land.
+    land: Noise,
+
+    /// The hills on top of the land.
+    hills: Noise,
+
+    /// How deep the soil is.
+    soil: Noise,
+
+    /// How hot each column is.
+    temperature: Noise,
+
+    /// How wet each column is.
+    humidity: Noise,
+
+    /// The large open caves.
+    cheese: Noise,
+
+    /// The two layers of noise whose crossings make winding tunnels.
+    spaghetti: [Noise; 2],
+
+    /// The ores scattered through the ground.
+    ores: Vec<Ore>,
+
+    /// The biomes the world is split into.
+    biomes: Vec<Biome>,
+
+    /// The block the ground is made of below the biome's blocks.
+    stone: BlockId,
+}
+
+/// The ground of one column of the world.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Column {
+    /// The height of the top block of the ground.
+    height: i64,
+
+    /// The number of filler blocks under the surface block.
+    soil: i64,
+
+    /// The index of the biome of the column in the database.
+    biome: usize,
+}
+
+impl TerrainGenerator {
+    /// Creates a terrain generator.
+    ///
+    /// # Parameters
+    ///
+    /// * `seed` - The seed of the world.
+    /// * `database` - The database holding the `stone` block that the ground
+    ///   is made of, the biomes and the ores scattered through the ground.
+    ///
+    /// # Returns
+    ///
+    /// The generator, or `None` if the database is missing the stone block
+    /// or has no biomes.
+    ///
+    pub fn new(seed: u64, database: &Database) -> Option<TerrainGenerator> {
+        if database.biomes().is_empty() {
+            return None;
+        }
+        Some(TerrainGenerator {
+            seed,
+            land: Noise::new(seed, 3, 256.0, 0.5),
+            hills: Noise::new(seed.wrapping_add(1), 4, 48.0, 0.5),
+            soil: Noise::new(seed.wrapping_add(2), 1, 16.0, 0.5),
+            temperature: Noise::new(seed.wrapping_add(6), 2, 512.0, 0.5),
+            humidity: Noise::new(seed.wrapping_add(7), 2, 512.0, 0.5),
+            cheese: Noise::new(seed.wrapping_add(3), 2, 64.0, 0.5),
+            spaghetti: [
+                Noise::new(seed.wrapping_add(4), 2, 48.0, 0.5),
+                Noise::new(seed.wrapping_add(5), 2, 48.0, 0.5),
+            ],
+            ores: database.ores().to_vec(),
+            biomes: database.biomes().to_vec(),
+            stone: database.block_id("stone")?,
+        })
+    }
+
+    /// Returns the seed of the world.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the height of the top block of the ground in a column.
+    pub fn height(&self, x: i64, z: i64) -> i64 {
+        self.column(x, z).height
+    }
+
+    /// Returns the index in the database of the biome of a column.
+    pub fn biome(&self, x: i64, z: i64) -> usize {
+        self.column(x, z).biome
+    }
+
+    /// Returns the height and biome of the top block of the ground in a
+    /// column, or `None` if a cave has carved that block away.
+    pub fn surface(&self, x: i64, z: i64) -> Option<(i64, usize)> {
+        let column = self.column(x, z);
+        let top = BlockPos::new(x, column.height, z);
+        (!self.is_cave(column, top)).then_some((column.height, column.biome))
+    }
+
+    /// Returns the temperature and humidity of a column, each from 0 to 1.
+    pub fn climate(&self, x: i64, z: i64) -> (f32, f32) {
+        let (x, z) = (x as f64, z as f64);
+        let climate = |noise: &Noise| {
+            ((noise.sample2(x, z) * CLIMATE_SPREAD + 1.0) / 2.0).clamp(0.0, 1.0) as f32
+        };
+        (climate(&self.temperature), climate(&self.humidity))
+    }
+
+    /// Makes the blocks of a chunk.
+    pub fn generate(&self, pos: ChunkPos) -> Chunk {
+        let origin = pos.origin();
+        let columns: Vec<Column> = (0..CHUNK_SIZE * CHUNK_SIZE)
+            .map(|i| {
+                let (x, z) = ((i % CHUNK_SIZE) as i64, (i / CHUNK_SIZE) as i64);
+                self.column(origin.x + x, origin.z + z)
+            })
+            .collect();
+
+        let mut chunk = Chunk::new();
+        for (i, column) in columns.iter().enumerate() {
+            chunk.set_biome(i % CHUNK_SIZE, i / CHUNK_SIZE, column.biome);
+        }
+
+        // Chunks wholly above the surface are left empty.
+        if columns.iter().all(|column| column.height < origin.y) {
+            return chunk;
+        }
+
+        for (i, column) in columns.iter().enumerate() {
+            let (x, z) = (i % CHUNK_SIZE, i / CHUNK_SIZE);
+            for y in 0..CHUNK_SIZE {
+                let pos = BlockPos::new(
+                    origin.x + x as i64,
+                    origin.y + y as i64,
+                    origin.z + z as i64,
+                );
+                let block = self.block(*column, pos.y);
+                if !block.is_air() && !self.is_cave(*column, pos) {
+                    chunk.set(LocalPos::new(x, y, z), block);
+                }
+            }
+        }
+        self.place_ores(pos, &mut chunk);
+        chunk
+    }
+
+    /// Works out the ground of a column.
+    ///
+    /// # Notes
+    ///
+    /// The column is in the biome nearest to its climate (the first one if
+    /// several hold it).  The height is blended from every biome within
+    /// [BIOME_BLEND] of the climate, weighted by how near they are, so it
+    /// doesn't jump at the border between biomes.
+    ///
+    fn column(&self, x: i64, z: i64) -> Column {
+        let (temperature, humidity) = self.climate(x, z);
+        let (x, z) = (x as f64, z as f64);
+        let hills = self.hills.sample2(x, z) * HILL_HEIGHT;
+        let biome_height = |biome: &Biome| biome.height() as f64 + hills * biome.hilliness() as f64;
+
+        let (mut biome, mut nearest) = (0, f32::MAX);
+        let (mut total, mut weights) = (0.0, 0.0);
+        for (index, candidate) in self.biomes.iter().enumerate() {
+            let distance = candidate.climate_distance(temperature, humidity);
+            if distance < nearest {
+                (biome, nearest) = (index, distance);
+            }
+            let weight = (1.0 - distance / BIOME_BLEND).max(0.0).powi(2) as f64;
+            total += biome_height(candidate) * weight;
+            weights += weight;
+        }
+        let shape = if weights > 0.0 {
+            total / weights
+        } else {
+            biome_height(&self.biomes[biome])
+        };
+
+        let height = BASE_HEIGHT + self.land.sample2(x, z) * LAND_HEIGHT + shape;
+        let (least, most) = SOIL_DEPTH;
+        let soil = (self.soil.sample2(x, z) + 1.0) / 2.0 * (most - least) as f64;
+        Column {
+            height: height.floor() as i64,
+            soil: least + soil.round() as i64,
+            biome,
+        }
+    }
+
+    /// Returns whether a block in the ground is carved out by a cave.
+    fn is_cave(&self, column: Column, pos: BlockPos) -> bool {
+        let (x, y, z) = (pos.x as f64, pos.y as f64, pos.z as f64);
+        if pos.y < column.height - CAVE_COVER && self.cheese.sample3(x, y, z) > CHEESE_THRESHOLD {
+            return true;
+        }
+        self.spaghetti
+            .iter()
+            .all(|noise| noise.sample3(x, y, z).abs() < SPAGHETTI_WIDTH)
+    }
+
+    /// Scatters the veins of ore that reach into a chunk.
+    ///
+    /// # Notes
+    ///
+    /// Each vein is a random walk from a block in the chunk it starts in, and
+    /// its random numbers only depend on the seed, the ore and that chunk.
+    /// Veins are followed from the chunks all around this one too, so a vein
+    /// that crosses into a neighbouring chunk carries on there.
+    ///
+    fn place_ores(&self, pos: ChunkPos, chunk: &mut Chunk) {
+        let bottom = pos.origin().y;
+        let top = bottom + CHUNK_SIZE as i64 - 1;
+        for (index, ore) in self.ores.iter().enumerate() {
+            let (min_height, max_height) = ore.heights();
+            if max_height < bottom || min_height > top {
+                continue;
+            }
+
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    for dz in -1..=1 {
+                        let start = ChunkPos::new(pos.x + dx, pos.y + dy, pos.z + dz);
+                        let seed = self.seed ^ (index as u64) << 32;
+                        let mut random = Random::new(hash(seed, start.x, start.y, start.z));
+                        let frequency = ore.frequency() as f64;
+                        let veins =
+                            frequency as u64 + (random.fraction() < frequency.fract()) as u64;
+
+                        for _ in 0..veins {
+                            let size = CHUNK_SIZE as u64;
+                            let origin = start.origin();
+                            let mut block = BlockPos::new(
+                                origin.x + random.below(size) as i64,
+                                origin.y + random.below(size) as i64,
+                                origin.z + random.below(size) as i64,
+                            );
+                            for _ in 0..ore.size() {
+                                if block.chunk() == pos
+                                    && (min_height..=max_height).contains(&block.y)
+                                    && chunk.get(block.local()) == ore.replaces()
+                                {
+                                    chunk.set(block.local(), ore.block());
+                                }
+                                block = block.neighbour(Face::ALL[random.below(6) as usize]);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the block at a height in a column.
+    fn block(&self, column: Column, y: i64) -> BlockId {
+        if y > column.height {
+            BlockId::AIR
+        } else if y == column.height {
+            self.biomes[column.biome].surface()
+        } else if y >= column.height - column.soil {
+            self.biomes[column.biome].filler()
+        } else {
+            self.stone
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::BlockPos;
+
+    /// Creates a database with the blocks terrain is made of: grass (block
+    /// 0), dirt (block 1) and stone (block 2), plus an ore (block 3) that
+    /// isn't scattered unless a test adds it to the ores.  Every climate is
+    /// in a single biome of grass over dirt.
+    fn database() -> Database {
+        Database::test_blocks(&["grass", "dirt", "stone", "coal"]).with_biome(
+            "biome",
+            BlockId::from_index(0),
+            BlockId::from_index(1),
+        )
+    }
+
+    /// Creates a biome over a range of temperatures, with dirt under its
+    /// surface.
+    fn biome(temperature: (f32, f32), surface: BlockId, height: f32) -> Biome {
+        Biome {
+            name: "biome".into(),
+            temperature,
+            humidity: (0.0, 1.0),
+            surface,
+            filler: BlockId::from_index(1),
+            tree_density: 0.0,
+            grass_tint: [1.0; 3],
+            height,
+            hilliness: 1.0,
+        }
+    }
+
+    fn generator(seed: u64) -> TerrainGenerator {
+        TerrainGenerator::new(seed, &database()).unwrap()
+    }
+
+    /// Creates a generator that scatters the ore (block 3) through stone.
+    fn generator_with_ore(
+        seed: u64,
+        size: u32,
+        heights: (i64, i64),
+        frequency: f32,
+    ) -> TerrainGenerator {
+        let mut db = database();
+        db.ores.push(Ore {
+            block: BlockId::from_index(3),
+            replaces: BlockId::from_index(2),
+            size,
+            min_height: heights.0,
+            max_height: heights.1,
+            frequency,
+        });
+        TerrainGenerator::new(seed, &db).unwrap()
+    }
+
+    /// Returns a fingerprint (FNV-1a) of the blocks of a chunk.
+    fn fingerprint(chunk: &Chunk) -> u64 {
+        chunk.iter().fold(0xcbf2_9ce4_8422_2325, |hash, (_, id)| {
+            let index = id.index().map_or(0, |index| index as u64 + 1);
+            (hash ^ index).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+    }
+
+    #[test]
+    fn same_seed_gives_same_chunks() {
+        let (first, second) = (generator(42), generator(42));
+        for x in -1..=1 {
+            for y in -2..=1 {
+                let pos = ChunkPos::new(x, y, 3);
+                let chunk = first.generate(pos);
+                assert!(chunk.iter().eq(second.generate(pos).iter()));
+            }
+        }
+        let pos = ChunkPos::new(0, 0, 0);
+        assert_ne!(
+            fingerprint(&generator(42).generate(pos)),
+            fingerprint(&generator(43).generate(pos))
+        );
+    }
+
+    #[test]
+    fn columns_are_grass_over_dirt_over_stone() {
+        let generator = generator(7);
+        let (grass, dirt, stone) = (
+            BlockId::from_index(0),
+            BlockId::from_index(1),
+            BlockId::from_index(2),
+        );
+        for (x, z) in [(0, 0), (100, -40), (-333, 77)] {
+            let column = generator.column(x, z);
+            assert!((SOIL_DEPTH.0..=SOIL_DEPTH.1).contains(&column.soil));
+            let height = generator.height(x, z);
+            assert_eq!(generator.block(column, height + 1), BlockId::AIR);
+            assert_eq!(generator.block(column, height), grass);
+            for depth in 1..=column.soil {
+                assert_eq!(generator.block(column, height - depth), dirt);
+            }
+            assert_eq!(generator.block(column, height - column.soil - 1), stone);
+        }
+    }
+
+    #[test]
+    fn chunks_match_their_columns() {
+        let generator = generator(3);
+        let height = generator.height(32, -16);
+        let top = BlockPos::new(32, height, -16);
+        let chunk = generator.generate(top.chunk());
+        assert_eq!(chunk.get(top.local()), BlockId::from_index(0));
+
+        assert!(generator.generate(ChunkPos::new(0, 10, 0)).is_empty());
+        let deep = generator.generate(ChunkPos::new(0, -10, 0));
+        assert!(deep
+            .block_counts()
+            .all(|(id, _)| id.is_air() || id == BlockId::from_index(2)));
+    }
+
+    #[test]
+    fn biomes_follow_the_climate() {
+        let mut db = database();
+        db.biomes = vec![
+            biome((0.0, 0.5), BlockId::from_index(0), 0.0),
+            biome((0.5, 1.0), BlockId::from_index(2), 24.0),
+        ];
+        let generator = TerrainGenerator::new(11, &db).unwrap();
+
+        let mut seen = [false; 2];
+        for x in (-4000..4000).step_by(16) {
+            let (temperature, _) = generator.climate(x, 0);
+            let biome = generator.biome(x, 0);
+            assert_eq!(biome, (temperature > 0.5) as usize);
+            seen[biome] = true;
+
+            // The ground rises smoothly into the raised biome.
+            let height = generator.height(x, 0);
+            assert!((height - generator.height(x + 1, 0)).abs() <= 4);
+
+            let top = BlockPos::new(x, height, 0);
+            let chunk = generator.generate(top.chunk());
+            let local = top.local();
+            assert_eq!(chunk.biome(local.x, local.z), biome);
+            if !chunk.get(local).is_air() {
+                assert_eq!(chunk.get(local), db.biomes[biome].surface());
+            }
+        }
+        assert_eq!(seen, [true, true]);
+        assert!(TerrainGenerator::new(
+            11,
+            &Database {
+                biomes: vec![],
+                ..database()
+            }
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn caves_are_carved_underground() {
+        let generator = generator(5);
+        let (mut ground, mut caves) = (0, 0);
+        for x in -2..2 {
+            for z in -2..2 {
+                for y in -4..0 {
+                    let pos = ChunkPos::new(x, y, z);
+                    let chunk = generator.generate(pos);
+                    for (local, id) in chunk.iter() {
+                        let block = pos.block(local);
+                        let column = generator.column(block.x, block.z);
+                        if generator.block(column, block.y).is_air() {
+                            assert!(id.is_air());
+                        } else {
+                            ground += 1;
+                            caves += id.is_air() as usize;
+                        }
+                    }
+                }
+            }
+        }
+        assert!(caves > 0 && caves < ground / 4);
+    }
+
+    #[test]
+    fn ores_replace_stone_within_their_heights() {
+        let generator = generator_with_ore(9, 8, (-40, -20), 4.0);
+        let plain = self::generator(9);
+
+        let mut ores = 0;
+        for x in -1..=1 {
+            for y in -4..=0 {
+                let pos = ChunkPos::new(x, y, 0);
+                let (chunk, without) = (generator.generate(pos), plain.generate(pos));
+                for ((local, id), (_, before)) in chunk.iter().zip(without.iter()) {
+                    if id == BlockId::from_index(3) {
+                        assert!((-40..=-20).contains(&pos.block(local).y));
+                        assert_eq!(before, BlockId::from_index(2));
+                        ores += 1;
+                    } else {
+                        assert_eq!(id, before);
+                    }
+                }
+            }
+        }
+        assert!(ores > 0);
+    }
+
+    /// Locks in the terrain of a seed, so changes to the generator that
+    /// would change existing worlds are noticed.
+    #[test]
+    fn terrain_snapshot() {
+        let generator = generator_with_ore(1234, 8, (-64, 0), 2.5);
+        let heights = (0..6)
+            .map(|z| {
+                (0..6)
+                    .map(|x| generator.height(x * 16, z * 16).to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            heights,
+            [
+                "0 4 3 2 0 4",
+                "-1 1 3 5 6 7",
+                "1 -3 1 4 10 9",
+                "-3 -6 -4 3 11 8",
+                "-4 -10 -5 2 8 6",
+                "-8 -6 0 1 4 2",
+            ]
+        );
+
+        let fingerprints = [(0, 0, 0), (0, -1, 0), (-3, 0, 5), (7, -2, -7)]
+            .map(|(x, y, z)| fingerprint(&generator.generate(ChunkPos::new(x, y, z))));
+        assert_eq!(
+            fingerprints,
+            [
+                945112029384296513,
+                14632788109421160290,
+                13346994205906133797,
+                17835727650717865084,
+            ]
+        );
+    }
+}
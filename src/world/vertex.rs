@@ -0,0 +1,231 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::{vertex_attr_array, BufferAddress, VertexBufferLayout, VertexStepMode};
+use wgpu_macros::VertexLayout;
+
+use super::{ChunkPos, Face, CHUNK_SIZE};
+
+/// The number of steps that a block is split into along each axis.  Vertex
+/// positions, texture coordinates and block models are all measured in
+/// steps, so model shapes can be as fine as an eighth of a block.
+pub const BLOCK_STEPS: u32 = 8;
+
+/// The light level of a vertex in full daylight.
+pub const FULL_LIGHT: u8 = 0xf0;
+
+/// The ambient occlusion level of a vertex with nothing around it.
+pub const NO_OCCLUSION: u8 = 3;
+
+/// The largest number of tiles that vertices can refer to.
+pub const MAX_TILES: usize = 1 << 11;
+
+/// A vertex of a block face, packed into 8 bytes.
+///
+/// # Notes
+///
+/// This must match the decoder in `block.wgsl`.  Positions and texture
+/// coordinates are measured in steps of 1/[BLOCK_STEPS] of a block or tile.
+/// The first word holds where the vertex is and how it is lit:
+///
+/// | Bits  | Field                                                     |
+/// |-------|-----------------------------------------------------------|
+/// | 0-7   | x position, relative to the chunk origin (0-128)          |
+/// | 8-15  | y position, relative to the chunk origin (0-128)          |
+/// | 16-23 | z position, relative to the chunk origin (0-128)          |
+/// | 24-31 | light level: sky light in the high nibble, block light in the low nibble |
+///
+/// The second word holds how the vertex is textured and shaded:
+///
+/// | Bits  | Field                                                     |
+/// |-------|-----------------------------------------------------------|
+/// | 0-7   | u (0-128)                                                 |
+/// | 8-15  | v (0-128)                                                 |
+/// | 16-26 | tile index into the database's tiles                      |
+/// | 27-29 | face direction, as an index into [Face::ALL]              |
+/// | 30-31 | ambient occlusion level (0 dark - 3 unoccluded)           |
+///
+/// The shader looks the tile up in a table of tile rectangles and layers
+/// (see `Database::create_tile_table`) and wraps the UVs into it, so the same
+/// mesh works for both texture atlases and texture arrays.
+///
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Zeroable, Pod, VertexLayout)]
+pub struct TerrainVertex {
+    position: u32,
+    texture: u32,
+}
+
+impl TerrainVertex {
+    /// Packs a vertex.
+    ///
+    /// # Parameters
+    ///
+    /// * `position` - The position relative to the chunk origin, in steps.
+    /// * `face` - The direction the face is facing.
+    /// * `ao` - The ambient occlusion level, 0 (dark) to 3 (unoccluded).
+    /// * `light` - The light level, sky light in the high nibble and block
+    ///   light in the low nibble.
+    /// * `tile` - The index of the tile in the database.
+    /// * `uv` - The texture coordinates, in steps.  Coordinates past a whole
+    ///   tile repeat it.
+    ///
+    pub fn new(
+        position: [u32; 3],
+        face: Face,
+        ao: u8,
+        light: u8,
+        tile: usize,
+        uv: [u32; 2],
+    ) -> TerrainVertex {
+        let max = CHUNK_SIZE as u32 * BLOCK_STEPS;
+        debug_assert!(position.iter().chain(&uv).all(|&c| c <= max));
+        debug_assert!(ao <= 3 && tile < MAX_TILES);
+
+        TerrainVertex {
+            position: position[0] | position[1] << 8 | position[2] << 16 | (light as u32) << 24,
+            texture: uv[0]
+                | uv[1] << 8
+                | (tile as u32) << 16
+                | (face.index() as u32) << 27
+                | (ao as u32) << 30,
+        }
+    }
+
+    /// Returns the position relative to the chunk origin, in steps.
+    pub fn local_position(self) -> [u32; 3] {
+        [
+            self.position & 0xff,
+            (self.position >> 8) & 0xff,
+            (self.position >> 16) & 0xff,
+        ]
+    }
+
+    /// Returns the light level.
+    pub fn light(self) -> u8 {
+        (self.position >> 24) as u8
+    }
+
+    /// Returns the texture coordinates, in steps.
+    pub fn uv(self) -> [u32; 2] {
+        [self.texture & 0xff, (self.texture >> 8) & 0xff]
+    }
+
+    /// Returns the index of the tile in the database.
+    pub fn tile(self) -> usize {
+        ((self.texture >> 16) & 0x7ff) as usize
+    }
+
+    /// Returns the direction the face is facing.
+    pub fn face(self) -> Face {
+        Face::from_index(((self.texture >> 27) & 0x7) as usize).unwrap_or(Face::Front)
+    }
+
+    /// Returns the ambient occlusion level.
+    pub fn ao(self) -> u8 {
+        (self.texture >> 30) as u8
+    }
+}
+
+/// The position of a chunk in the world, given to the vertices of its mesh
+/// as instance data.
+///
+/// # Notes
+///
+/// The origin is the position of the chunk's first block.  The shader adds
+/// it to the vertex positions, which are relative to the chunk, after taking
+/// away the block the camera is in, so that positions stay small enough for
+/// `f32` far from the origin of the world.
+///
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Zeroable, Pod)]
+pub struct ChunkOrigin {
+    origin: [i32; 3],
+}
+
+impl ChunkOrigin {
+    /// The layout of the instance buffer.  The origin is read at location 2,
+    /// after the two words of [TerrainVertex].
+    pub const LAYOUT: VertexBufferLayout<'static> = VertexBufferLayout {
+        array_stride: std::mem::size_of::<ChunkOrigin>() as BufferAddress,
+        step_mode: VertexStepMode::Instance,
+        attributes: &vertex_attr_array![2 => Sint32x3],
+    };
+
+    /// Returns the origin of a chunk.
+    pub fn new(pos: ChunkPos) -> ChunkOrigin {
+        let origin = pos.origin();
+        ChunkOrigin {
+            origin: [origin.x as i32, origin.y as i32, origin.z as i32],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX: u32 = CHUNK_SIZE as u32 * BLOCK_STEPS;
+
+    #[test]
+    fn vertex_is_eight_bytes() {
+        assert_eq!(std::mem::size_of::<TerrainVertex>(), 8);
+    }
+
+    #[test]
+    fn fields_round_trip() {
+        for face in Face::ALL {
+            let vertex = TerrainVertex::new([MAX, 0, 73], face, 2, 0xa5, 1234, [MAX, 3]);
+            assert_eq!(vertex.local_position(), [MAX, 0, 73]);
+            assert_eq!(vertex.face(), face);
+            assert_eq!(vertex.ao(), 2);
+            assert_eq!(vertex.light(), 0xa5);
+            assert_eq!(vertex.tile(), 1234);
+            assert_eq!(vertex.uv(), [MAX, 3]);
+        }
+    }
+
+    /// Unpacks every field of a vertex, with the position and texture
+    /// coordinates split into their parts.
+    fn decode(vertex: TerrainVertex) -> [u32; 9] {
+        let [x, y, z] = vertex.local_position();
+        let [u, v] = vertex.uv();
+        let face = vertex.face().index() as u32;
+        let (ao, light, tile) = (vertex.ao(), vertex.light(), vertex.tile());
+        [x, y, z, face, ao as u32, light as u32, tile as u32, u, v]
+    }
+
+    #[test]
+    fn fields_do_not_overlap() {
+        // Each field is set on its own to values that between them set every
+        // bit of the field, and all the other fields must still read as 0.
+        // Positions and texture coordinates only go up to 128, so 127 covers
+        // the bits below the top one.
+        let coords: &[u32] = &[MAX - 1, MAX];
+        let fields: [&[u32]; 9] = [
+            coords,
+            coords,
+            coords,
+            &[3, 4],
+            &[3],
+            &[0xff],
+            &[MAX_TILES as u32 - 1],
+            coords,
+            coords,
+        ];
+        for (field, values) in fields.into_iter().enumerate() {
+            for &value in values {
+                let mut expected = [0; 9];
+                expected[field] = value;
+                let [x, y, z, face, ao, light, tile, u, v] = expected;
+                let vertex = TerrainVertex::new(
+                    [x, y, z],
+                    Face::ALL[face as usize],
+                    ao as u8,
+                    light as u8,
+                    tile as usize,
+                    [u, v],
+                );
+                assert_eq!(decode(vertex), expected);
+            }
+        }
+    }
+}